@@ -0,0 +1,12 @@
+//! Local stand-in for the Jupiter RFQ system
+//!
+//! The [`Orchestrator`] polls `/tokens` on the configured webhooks, fans out quote requests with
+//! the deadline headers and timeout of the RFQ system, and builds the fill transaction of the best
+//! quote with the `order-engine-sdk`. It then signs the transaction as the taker, forwards it to
+//! `/swap` and, when given an RPC, executes the accepted fill. The whole maker loop can so be
+//! tested without Jupiter.
+pub mod orchestrator;
+pub mod webhook;
+
+pub use orchestrator::{Orchestrator, RfqOutcome, RfqRequest};
+pub use webhook::Webhook;
@@ -0,0 +1,147 @@
+use std::{process::ExitCode, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use clap::Parser;
+use mock_rfq::{Orchestrator, RfqRequest, Webhook};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::{EncodableKey, Signer},
+};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use webhook_api::enums::{QuoteType, SwapState};
+
+#[derive(Parser)]
+struct Args {
+    /// Webhooks to quote, as URL or URL#API_KEY
+    #[clap(
+        env,
+        long,
+        value_delimiter = ',',
+        default_value = "http://localhost:8080"
+    )]
+    webhooks: Vec<Webhook>,
+
+    #[clap(
+        env,
+        long,
+        default_value = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"
+    )]
+    token_in: Pubkey,
+
+    #[clap(
+        env,
+        long,
+        default_value = "So11111111111111111111111111111111111111112"
+    )]
+    token_out: Pubkey,
+
+    /// Atomic units of token_in for ExactIn, of token_out for ExactOut
+    #[clap(env, long, default_value_t = 1_000_000)]
+    amount: u64,
+
+    #[clap(env, long, default_value = "ExactIn")]
+    quote_type: QuoteType,
+
+    #[clap(env, long, default_value_t = 2)]
+    fee_bps: u16,
+
+    /// Compute unit price suggested to the makers, in micro lamports
+    #[clap(env, long)]
+    suggested_prioritization_fees: Option<u64>,
+
+    /// Keypair of the taker, a random taker is used when not set
+    #[clap(env, long)]
+    taker_keypair: Option<String>,
+
+    #[clap(env, long, default_value_t = 250)]
+    quote_timeout_ms: u64,
+
+    /// Number of RFQs to run
+    #[clap(env, long, default_value_t = 1)]
+    rounds: usize,
+
+    #[clap(env, long, default_value_t = 1000)]
+    round_interval_ms: u64,
+
+    #[clap(env, long, default_value_t = 60)]
+    tokens_poll_interval_secs: u64,
+
+    /// Fetch the blockhash from and execute the accepted fills on this RPC, typically a
+    /// solana-test-validator with the order engine deployed
+    #[clap(env, long)]
+    rpc_url: Option<String>,
+}
+
+#[async_std::main]
+async fn main() -> Result<ExitCode> {
+    let env_filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env_lossy();
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer())
+        .with(env_filter)
+        .try_init()?;
+
+    let args = Args::parse();
+    let taker = match &args.taker_keypair {
+        Some(taker_keypair) => Keypair::read_from_file(taker_keypair)
+            .map_err(|error| anyhow::anyhow!("Invalid taker keypair file: {error}"))?,
+        None => Keypair::new(),
+    };
+    tracing::info!("taker pubkey: {}", taker.pubkey());
+
+    let orchestrator = Arc::new(Orchestrator::new(
+        args.webhooks,
+        Duration::from_millis(args.quote_timeout_ms),
+        args.rpc_url.map(RpcClient::new),
+    ));
+    orchestrator.refresh_tokens().await;
+    orchestrator.spawn_token_polling(Duration::from_secs(args.tokens_poll_interval_secs));
+
+    let request = RfqRequest {
+        token_in: args.token_in,
+        token_out: args.token_out,
+        amount: args.amount,
+        quote_type: args.quote_type,
+        fee_bps: args.fee_bps,
+        suggested_prioritization_fees: args.suggested_prioritization_fees,
+    };
+    let mut accepted = 0;
+    for round in 0..args.rounds {
+        if round > 0 {
+            async_std::task::sleep(Duration::from_millis(args.round_interval_ms)).await;
+        }
+        match orchestrator.rfq(&request, &taker).await {
+            Ok(Some(outcome)) => {
+                tracing::info!(
+                    "Swap of quote {} by {}: {:?}",
+                    outcome.quote.quote_id,
+                    outcome.webhook.url,
+                    outcome.swap.state
+                );
+                if let Some(simulation) = &outcome.simulation {
+                    tracing::info!(
+                        "Fill executed, error: {:?}, units consumed: {:?}",
+                        simulation.err,
+                        simulation.units_consumed
+                    );
+                }
+                if outcome.swap.state == SwapState::Accepted {
+                    accepted += 1;
+                }
+            }
+            Ok(None) => tracing::warn!("No webhook quoted"),
+            Err(error) => tracing::error!("RFQ failed: {error:?}"),
+        }
+    }
+
+    tracing::info!("{accepted} of {} swaps accepted", args.rounds);
+    Ok(if accepted == args.rounds {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -0,0 +1,410 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use futures::future::join_all;
+use order_engine_sdk::{
+    builder::{AccountKind, FillTransactionBuilder},
+    fill::Order,
+    trailer::FillTrailer,
+};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::{
+    config::RpcSimulateTransactionConfig, response::RpcSimulateTransactionResult,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    pubkey,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::VersionedTransaction,
+};
+use webhook_api::{
+    enums::{Protocol, QuoteType, SwapState},
+    responses::SwapResponse,
+    validated::{ValidatedQuoteRequest, ValidatedQuoteResponse, ValidatedSwapRequest},
+};
+
+use crate::webhook::{Webhook, WebhookClient};
+
+/// Quotes received later are dropped, as the RFQ system does
+pub const DEFAULT_QUOTE_TIMEOUT: Duration = Duration::from_millis(250);
+/// Time given to the taker to sign the fill transaction
+pub const FILL_EXPIRY: Duration = Duration::from_secs(55);
+
+const NATIVE_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
+
+/// What the taker asks for
+#[derive(Debug, Clone)]
+pub struct RfqRequest {
+    pub token_in: Pubkey,
+    pub token_out: Pubkey,
+    pub amount: u64,
+    pub quote_type: QuoteType,
+    pub fee_bps: u16,
+    pub suggested_prioritization_fees: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct RfqOutcome {
+    pub webhook: Webhook,
+    pub quote: ValidatedQuoteResponse,
+    /// Fill transaction sent to `/swap`, only signed by the taker
+    pub transaction: VersionedTransaction,
+    pub swap: SwapResponse,
+    /// Execution of the accepted fill on the RPC, when one is configured
+    pub simulation: Option<RpcSimulateTransactionResult>,
+}
+
+/// Plays the role of the RFQ system against a set of webhooks
+pub struct Orchestrator {
+    webhooks: Vec<WebhookClient>,
+    /// Tokens listed by each webhook, in the order of `webhooks`
+    supported_tokens: RwLock<Vec<HashSet<Pubkey>>>,
+    quote_timeout: Duration,
+    rpc_client: Option<RpcClient>,
+}
+
+impl Orchestrator {
+    /// Without an RPC the fills use a made up blockhash and are not executed
+    pub fn new(
+        webhooks: Vec<Webhook>,
+        quote_timeout: Duration,
+        rpc_client: Option<RpcClient>,
+    ) -> Self {
+        Self {
+            supported_tokens: RwLock::new(vec![HashSet::new(); webhooks.len()]),
+            webhooks: webhooks.into_iter().map(WebhookClient::new).collect(),
+            quote_timeout,
+            rpc_client,
+        }
+    }
+
+    /// Fetch `/tokens` from every webhook, a webhook that fails is not sent any quote request
+    pub async fn refresh_tokens(&self) {
+        let tokens = join_all(self.webhooks.iter().map(|webhook| webhook.tokens())).await;
+        let tokens = self
+            .webhooks
+            .iter()
+            .zip(tokens)
+            .map(|(webhook, tokens)| {
+                tokens.unwrap_or_else(|error| {
+                    tracing::warn!(
+                        "Failed to get the tokens of {}: {error:#}",
+                        webhook.webhook.url
+                    );
+                    HashSet::new()
+                })
+            })
+            .collect();
+        *self.supported_tokens.write().unwrap() = tokens;
+    }
+
+    /// Refresh the tokens in the background
+    pub fn spawn_token_polling(self: &Arc<Self>, interval: Duration) {
+        let orchestrator = self.clone();
+        async_std::task::spawn(async move {
+            loop {
+                async_std::task::sleep(interval).await;
+                orchestrator.refresh_tokens().await;
+            }
+        });
+    }
+
+    fn webhooks_quoting(&self, token_in: &Pubkey, token_out: &Pubkey) -> Vec<usize> {
+        self.supported_tokens
+            .read()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter(|(_, tokens)| tokens.contains(token_in) && tokens.contains(token_out))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Send the quote request to every webhook listing both tokens, keeping the valid quotes
+    /// received in time
+    pub async fn request_quotes(
+        &self,
+        quote_request: &ValidatedQuoteRequest,
+    ) -> Vec<(usize, ValidatedQuoteResponse)> {
+        let indexes = self.webhooks_quoting(&quote_request.token_in, &quote_request.token_out);
+        let quotes = join_all(
+            indexes
+                .iter()
+                .map(|&index| self.webhooks[index].quote(quote_request, self.quote_timeout)),
+        )
+        .await;
+
+        indexes
+            .into_iter()
+            .zip(quotes)
+            .filter_map(|(index, quote)| {
+                let url = &self.webhooks[index].webhook.url;
+                match quote {
+                    Ok(Some(quote)) if answers(quote_request, &quote) => Some((index, quote)),
+                    Ok(Some(quote)) => {
+                        tracing::warn!(
+                            "Dropping quote of {url} not matching the request: {quote:?}"
+                        );
+                        None
+                    }
+                    Ok(None) => None,
+                    Err(error) => {
+                        tracing::warn!("Failed to get a quote from {url}: {error:#}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Run a full RFQ: quote, build the fill of the best quote, sign it as the taker and forward
+    /// it to the webhook that quoted
+    ///
+    /// `None` when no webhook quoted.
+    pub async fn rfq(&self, request: &RfqRequest, taker: &Keypair) -> Result<Option<RfqOutcome>> {
+        let quote_request = ValidatedQuoteRequest {
+            request_id: uuid::Uuid::new_v4().to_string(),
+            quote_id: uuid::Uuid::new_v4().to_string(),
+            token_in: request.token_in,
+            amount: request.amount,
+            token_out: request.token_out,
+            quote_type: request.quote_type,
+            protocol: Protocol::V1,
+            taker: Some(taker.pubkey()),
+            suggested_prioritization_fees: request.suggested_prioritization_fees,
+            fee_bps: request.fee_bps,
+            is_wsol: None,
+            receiver: None,
+        };
+        let quotes = self.request_quotes(&quote_request).await;
+        let Some((index, quote)) = best_quote(request.quote_type, quotes) else {
+            return Ok(None);
+        };
+        let webhook = &self.webhooks[index];
+        tracing::info!("Best quote from {}: {quote:?}", webhook.webhook.url);
+
+        let recent_blockhash = match &self.rpc_client {
+            Some(rpc_client) => rpc_client.get_latest_blockhash().await?,
+            None => Hash::new_unique(),
+        };
+        let expire_at = (SystemTime::now() + FILL_EXPIRY)
+            .duration_since(UNIX_EPOCH)?
+            .as_secs() as i64;
+        let compute_unit_price = quote
+            .prioritization_fee_to_use
+            .or(request.suggested_prioritization_fees)
+            .unwrap_or_default();
+        let transaction = fill_transaction(
+            &quote,
+            taker,
+            request.fee_bps,
+            compute_unit_price,
+            expire_at,
+            recent_blockhash,
+        )?;
+
+        let swap = webhook
+            .swap(ValidatedSwapRequest {
+                request_id: quote_request.request_id,
+                quote_id: quote_request.quote_id,
+                transaction: transaction.clone(),
+            })
+            .await?;
+        let simulation = match (&self.rpc_client, &swap.state) {
+            (Some(rpc_client), SwapState::Accepted) => {
+                Some(simulate_fill(rpc_client, &transaction).await?)
+            }
+            _ => None,
+        };
+
+        Ok(Some(RfqOutcome {
+            webhook: webhook.webhook.clone(),
+            quote,
+            transaction,
+            swap,
+            simulation,
+        }))
+    }
+}
+
+/// The quote echoes the request and quotes a positive amount
+fn answers(quote_request: &ValidatedQuoteRequest, quote: &ValidatedQuoteResponse) -> bool {
+    let amounts = match quote_request.quote_type {
+        QuoteType::ExactIn => quote.amount_in == quote_request.amount && quote.amount_out > 0,
+        QuoteType::ExactOut => quote.amount_out == quote_request.amount && quote.amount_in > 0,
+    };
+    amounts
+        && quote.request_id == quote_request.request_id
+        && quote.quote_id == quote_request.quote_id
+        && quote.token_in == quote_request.token_in
+        && quote.token_out == quote_request.token_out
+        && quote.quote_type == quote_request.quote_type
+        && quote_request.protocol.accepts(&quote.protocol)
+}
+
+/// Most output for exact in, least input for exact out
+pub fn best_quote(
+    quote_type: QuoteType,
+    quotes: Vec<(usize, ValidatedQuoteResponse)>,
+) -> Option<(usize, ValidatedQuoteResponse)> {
+    match quote_type {
+        QuoteType::ExactIn => quotes.into_iter().max_by_key(|(_, quote)| quote.amount_out),
+        QuoteType::ExactOut => quotes.into_iter().min_by_key(|(_, quote)| quote.amount_in),
+    }
+}
+
+/// Both sides hold token accounts, WSOL for the native mint
+fn account_kind(mint: &Pubkey) -> AccountKind {
+    if mint == &NATIVE_MINT {
+        AccountKind::Wsol
+    } else {
+        AccountKind::Token
+    }
+}
+
+/// Build the fill transaction of a quote and sign it as the taker, the maker signs in `/swap`
+pub fn fill_transaction(
+    quote: &ValidatedQuoteResponse,
+    taker: &Keypair,
+    fee_bps: u16,
+    compute_unit_price: u64,
+    expire_at: i64,
+    recent_blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let order = Order {
+        taker: taker.pubkey(),
+        maker: quote.maker,
+        in_amount: quote.amount_in,
+        input_mint: quote.token_in,
+        out_amount: quote.amount_out,
+        output_mint: quote.token_out,
+        expire_at,
+        expected_trailer: Some(FillTrailer::new(
+            fee_bps,
+            quote.quote_type == QuoteType::ExactOut,
+        )),
+    };
+    let mut transaction = FillTransactionBuilder::new(order)
+        .taker_accounts(
+            account_kind(&quote.token_in),
+            account_kind(&quote.token_out),
+        )
+        .maker_accounts(
+            account_kind(&quote.token_in),
+            account_kind(&quote.token_out),
+        )
+        .compute_unit_price(compute_unit_price)
+        .recent_blockhash(recent_blockhash)
+        .build_transaction()?;
+
+    let num_required_signatures = usize::from(transaction.message.header().num_required_signatures);
+    let taker_index = transaction.message.static_account_keys()[..num_required_signatures]
+        .iter()
+        .position(|key| key == &taker.pubkey())
+        .ok_or_else(|| anyhow!("The taker is not a signer of the fill transaction"))?;
+    transaction.signatures[taker_index] = taker.sign_message(&transaction.message.serialize());
+    Ok(transaction)
+}
+
+/// Execute the fill without verifying the signatures, the maker signature stays with the webhook
+async fn simulate_fill(
+    rpc_client: &RpcClient,
+    transaction: &VersionedTransaction,
+) -> Result<RpcSimulateTransactionResult> {
+    Ok(rpc_client
+        .simulate_transaction_with_config(
+            transaction,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                commitment: Some(CommitmentConfig::processed()),
+                ..Default::default()
+            },
+        )
+        .await?
+        .value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use order_engine_sdk::{
+        fill::validate_fill_sanitized_message,
+        policy::FillValidationPolicy,
+        transaction::{versioned_message_to_sanitized_message, NoAddressLookupTables},
+    };
+
+    const USDC: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+
+    fn quote(quote_type: QuoteType, amount_in: u64, amount_out: u64) -> ValidatedQuoteResponse {
+        ValidatedQuoteResponse {
+            request_id: "request".to_string(),
+            quote_id: "quote".to_string(),
+            token_in: USDC,
+            amount_in,
+            token_out: NATIVE_MINT,
+            quote_type,
+            protocol: Protocol::V1,
+            amount_out,
+            maker: Pubkey::new_unique(),
+            prioritization_fee_to_use: None,
+            taker: None,
+            receiver: None,
+        }
+    }
+
+    #[test]
+    fn test_best_quote() {
+        let exact_in = vec![
+            (0, quote(QuoteType::ExactIn, 100, 5)),
+            (1, quote(QuoteType::ExactIn, 100, 7)),
+            (2, quote(QuoteType::ExactIn, 100, 6)),
+        ];
+        assert_eq!(best_quote(QuoteType::ExactIn, exact_in).unwrap().0, 1);
+
+        let exact_out = vec![
+            (0, quote(QuoteType::ExactOut, 12, 100)),
+            (1, quote(QuoteType::ExactOut, 11, 100)),
+        ];
+        assert_eq!(best_quote(QuoteType::ExactOut, exact_out).unwrap().0, 1);
+        assert_eq!(best_quote(QuoteType::ExactIn, vec![]), None);
+    }
+
+    #[test]
+    fn test_fill_transaction() {
+        let taker = Keypair::new();
+        let quote = quote(QuoteType::ExactOut, 1_000_000, 4_000_000);
+        let transaction =
+            fill_transaction(&quote, &taker, 5, 10_000, i64::MAX, Hash::new_unique()).unwrap();
+
+        // the maker pays the fees and signs first, only the taker has signed
+        assert_eq!(transaction.message.static_account_keys()[0], quote.maker);
+        assert_eq!(transaction.verify_with_results(), vec![false, true]);
+
+        let sanitized_message =
+            versioned_message_to_sanitized_message(transaction.message, &NoAddressLookupTables)
+                .unwrap();
+        let order = Order {
+            taker: taker.pubkey(),
+            maker: quote.maker,
+            in_amount: quote.amount_in,
+            input_mint: quote.token_in,
+            out_amount: quote.amount_out,
+            output_mint: quote.token_out,
+            expire_at: i64::MAX,
+            expected_trailer: Some(FillTrailer::new(5, true)),
+        };
+        let validated_fill = validate_fill_sanitized_message(
+            &sanitized_message,
+            order,
+            &FillValidationPolicy::default(),
+        )
+        .unwrap();
+        assert_eq!(validated_fill.compute_unit_price, 10_000);
+    }
+}
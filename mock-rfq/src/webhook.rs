@@ -0,0 +1,139 @@
+use std::{
+    collections::HashSet,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use reqwest::{RequestBuilder, StatusCode};
+use solana_sdk::pubkey::Pubkey;
+use webhook_api::{
+    deadline::{REQUEST_START_HEADER, REQUEST_TIMEOUT_HEADER},
+    requests::{QuoteRequest, SwapRequest},
+    responses::{QuoteResponse, SwapResponse},
+    validated::{ValidatedQuoteRequest, ValidatedQuoteResponse, ValidatedSwapRequest},
+};
+
+pub const API_KEY_HEADER: &str = "X-API-KEY";
+
+/// A registered market maker webhook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    pub url: String,
+    pub api_key: Option<String>,
+}
+
+/// Parses `URL` or `URL#API_KEY`
+impl FromStr for Webhook {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (url, api_key) = match s.split_once('#') {
+            Some((url, api_key)) => (url, Some(api_key.to_string())),
+            None => (s, None),
+        };
+        ensure!(!url.is_empty(), "Missing webhook url in {s}");
+        Ok(Self {
+            url: url.trim_end_matches('/').to_string(),
+            api_key,
+        })
+    }
+}
+
+/// Calls a webhook the way the RFQ system does
+pub struct WebhookClient {
+    pub webhook: Webhook,
+    client: reqwest::Client,
+}
+
+impl WebhookClient {
+    pub fn new(webhook: Webhook) -> Self {
+        Self {
+            webhook,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn request(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.webhook.api_key {
+            Some(api_key) => request.header(API_KEY_HEADER, api_key),
+            None => request,
+        }
+    }
+
+    /// Tokens the webhook is willing to quote
+    pub async fn tokens(&self) -> Result<HashSet<Pubkey>> {
+        let response = self
+            .request(self.client.get(format!("{}/tokens", self.webhook.url)))
+            .send()
+            .await?
+            .error_for_status()?;
+        response
+            .json::<Vec<String>>()
+            .await?
+            .iter()
+            .map(|token| Pubkey::from_str(token).with_context(|| format!("Invalid token {token}")))
+            .collect()
+    }
+
+    /// `None` when the webhook does not quote, answering with a 404
+    ///
+    /// The request carries the deadline headers and is dropped once `timeout` has elapsed.
+    pub async fn quote(
+        &self,
+        quote_request: &ValidatedQuoteRequest,
+        timeout: Duration,
+    ) -> Result<Option<ValidatedQuoteResponse>> {
+        let request_start = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let response = self
+            .request(self.client.post(format!("{}/quote", self.webhook.url)))
+            .header(REQUEST_START_HEADER, request_start.to_string())
+            .header(REQUEST_TIMEOUT_HEADER, timeout.as_millis().to_string())
+            .timeout(timeout)
+            .json(&QuoteRequest::from(quote_request.clone()))
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let quote_response: QuoteResponse = response.error_for_status()?.json().await?;
+        Ok(Some(ValidatedQuoteResponse::try_from(quote_response)?))
+    }
+
+    pub async fn swap(&self, swap_request: ValidatedSwapRequest) -> Result<SwapResponse> {
+        let response = self
+            .request(self.client.post(format!("{}/swap", self.webhook.url)))
+            .json(&SwapRequest::from(swap_request))
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!(
+                "Swap failed with status {status}: {}",
+                response.text().await?
+            ));
+        }
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_from_str() {
+        assert_eq!(
+            "http://localhost:8080/#secret".parse::<Webhook>().unwrap(),
+            Webhook {
+                url: "http://localhost:8080".to_string(),
+                api_key: Some("secret".to_string())
+            }
+        );
+        assert_eq!(
+            "http://localhost:8080".parse::<Webhook>().unwrap().api_key,
+            None
+        );
+        assert!("#secret".parse::<Webhook>().is_err());
+    }
+}
@@ -0,0 +1,407 @@
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address_with_program_id,
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    },
+    token::{self, spl_token::native_mint},
+    token_2022,
+};
+use anyhow::{anyhow, ensure, Result};
+use base64::prelude::*;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    message::{v0, AddressLookupTableAccount, VersionedMessage},
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::VersionedTransaction,
+};
+
+use crate::{fill::Order, order_engine, trailer::FillTrailer};
+
+pub const DEFAULT_COMPUTE_UNIT_LIMIT: u32 = 100_000;
+
+/// How a user holds one side of the trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccountKind {
+    /// Lamports held directly by the user, the mint must be the native mint
+    NativeSol,
+    /// Associated token account of the native mint
+    Wsol,
+    /// Associated token account of a SPL Token mint
+    #[default]
+    Token,
+    /// Associated token account of a Token-2022 mint
+    Token2022,
+}
+
+impl AccountKind {
+    pub fn token_program(&self) -> Pubkey {
+        match self {
+            AccountKind::NativeSol | AccountKind::Wsol | AccountKind::Token => token::ID,
+            AccountKind::Token2022 => token_2022::ID,
+        }
+    }
+
+    /// The associated token account of the owner, none when native SOL is used
+    pub fn token_account(&self, owner: &Pubkey, mint: &Pubkey) -> Option<Pubkey> {
+        match self {
+            AccountKind::NativeSol => None,
+            _ => Some(get_associated_token_address_with_program_id(
+                owner,
+                mint,
+                &self.token_program(),
+            )),
+        }
+    }
+
+    fn is_native_mint(&self) -> bool {
+        matches!(self, AccountKind::NativeSol | AccountKind::Wsol)
+    }
+}
+
+/// PDA used by the program to unwrap WSOL into native SOL on behalf of the maker
+pub fn temporary_wsol_token_account(maker: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            order_engine::constants::TEMPORARY_WSOL_TOKEN_ACCOUNT,
+            maker.as_ref(),
+        ],
+        &order_engine::ID,
+    )
+    .0
+}
+
+/// Builds the fill transaction the same way the RFQ API does before forwarding it to `/swap`
+///
+/// The message contains, in order: the compute unit price and limit, the idempotent creation of
+/// the taker output token account paid by the taker and the fill instruction followed by the
+/// fee trailer.
+pub struct FillTransactionBuilder {
+    order: Order,
+    taker_input: AccountKind,
+    taker_output: AccountKind,
+    maker_input: AccountKind,
+    maker_output: AccountKind,
+    trailer: FillTrailer,
+    compute_unit_limit: u32,
+    compute_unit_price: u64,
+    create_taker_output_token_account: bool,
+    recent_blockhash: Hash,
+    address_lookup_table_accounts: Vec<AddressLookupTableAccount>,
+}
+
+impl FillTransactionBuilder {
+    pub fn new(order: Order) -> Self {
+        let trailer = order.expected_trailer.unwrap_or_default();
+        Self {
+            order,
+            taker_input: AccountKind::default(),
+            taker_output: AccountKind::default(),
+            maker_input: AccountKind::default(),
+            maker_output: AccountKind::default(),
+            trailer,
+            compute_unit_limit: DEFAULT_COMPUTE_UNIT_LIMIT,
+            compute_unit_price: 0,
+            create_taker_output_token_account: true,
+            recent_blockhash: Hash::default(),
+            address_lookup_table_accounts: vec![],
+        }
+    }
+
+    pub fn taker_accounts(mut self, input: AccountKind, output: AccountKind) -> Self {
+        self.taker_input = input;
+        self.taker_output = output;
+        self
+    }
+
+    pub fn maker_accounts(mut self, input: AccountKind, output: AccountKind) -> Self {
+        self.maker_input = input;
+        self.maker_output = output;
+        self
+    }
+
+    pub fn trailer(mut self, trailer: FillTrailer) -> Self {
+        self.trailer = trailer;
+        self
+    }
+
+    pub fn compute_unit_limit(mut self, compute_unit_limit: u32) -> Self {
+        self.compute_unit_limit = compute_unit_limit;
+        self
+    }
+
+    pub fn compute_unit_price(mut self, compute_unit_price: u64) -> Self {
+        self.compute_unit_price = compute_unit_price;
+        self
+    }
+
+    pub fn create_taker_output_token_account(mut self, create: bool) -> Self {
+        self.create_taker_output_token_account = create;
+        self
+    }
+
+    pub fn recent_blockhash(mut self, recent_blockhash: Hash) -> Self {
+        self.recent_blockhash = recent_blockhash;
+        self
+    }
+
+    pub fn address_lookup_table_accounts(
+        mut self,
+        address_lookup_table_accounts: Vec<AddressLookupTableAccount>,
+    ) -> Self {
+        self.address_lookup_table_accounts = address_lookup_table_accounts;
+        self
+    }
+
+    fn validate_account_kinds(&self) -> Result<()> {
+        let Order {
+            input_mint,
+            output_mint,
+            ..
+        } = &self.order;
+        for (side, kind, mint) in [
+            ("taker input", self.taker_input, input_mint),
+            ("maker input", self.maker_input, input_mint),
+            ("taker output", self.taker_output, output_mint),
+            ("maker output", self.maker_output, output_mint),
+        ] {
+            ensure!(
+                kind.is_native_mint() == (mint == &native_mint::ID),
+                "Account kind {kind:?} of the {side} does not match mint {mint}"
+            );
+        }
+        ensure!(
+            self.taker_input.token_program() == self.maker_input.token_program(),
+            "Taker and maker input token programs differ"
+        );
+        ensure!(
+            self.taker_output.token_program() == self.maker_output.token_program(),
+            "Taker and maker output token programs differ"
+        );
+        Ok(())
+    }
+
+    /// The program only needs the temporary WSOL account when the sender holds WSOL and the
+    /// receiver expects native SOL
+    fn uses_temporary_wsol_token_account(&self) -> bool {
+        self.taker_input == AccountKind::Wsol && self.maker_input == AccountKind::NativeSol
+            || self.maker_output == AccountKind::Wsol && self.taker_output == AccountKind::NativeSol
+    }
+
+    pub fn fill_instruction(&self) -> Result<Instruction> {
+        self.validate_account_kinds()?;
+        let Order {
+            taker,
+            maker,
+            in_amount,
+            input_mint,
+            out_amount,
+            output_mint,
+            expire_at,
+            ..
+        } = &self.order;
+
+        let mut accounts = order_engine::client::accounts::Fill {
+            taker: *taker,
+            maker: *maker,
+            taker_input_mint_token_account: self.taker_input.token_account(taker, input_mint),
+            maker_input_mint_token_account: self.maker_input.token_account(maker, input_mint),
+            taker_output_mint_token_account: self.taker_output.token_account(taker, output_mint),
+            maker_output_mint_token_account: self.maker_output.token_account(maker, output_mint),
+            input_mint: *input_mint,
+            input_token_program: self.taker_input.token_program(),
+            output_mint: *output_mint,
+            output_token_program: self.taker_output.token_program(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
+        if self.uses_temporary_wsol_token_account() {
+            accounts.push(AccountMeta::new(temporary_wsol_token_account(maker), false));
+        }
+
+        let mut data = order_engine::client::args::Fill {
+            input_amount: *in_amount,
+            output_amount: *out_amount,
+            expire_at: *expire_at,
+        }
+        .data();
+        data.extend(self.trailer.encode());
+
+        Ok(Instruction {
+            program_id: order_engine::ID,
+            accounts,
+            data,
+        })
+    }
+
+    pub fn instructions(&self) -> Result<Vec<Instruction>> {
+        let mut instructions = vec![
+            ComputeBudgetInstruction::set_compute_unit_price(self.compute_unit_price),
+            ComputeBudgetInstruction::set_compute_unit_limit(self.compute_unit_limit),
+        ];
+        if self.create_taker_output_token_account && self.taker_output != AccountKind::NativeSol {
+            instructions.push(create_associated_token_account_idempotent(
+                &self.order.taker,
+                &self.order.taker,
+                &self.order.output_mint,
+                &self.taker_output.token_program(),
+            ));
+        }
+        instructions.push(self.fill_instruction()?);
+        Ok(instructions)
+    }
+
+    pub fn build_message(&self) -> Result<VersionedMessage> {
+        let message = v0::Message::try_compile(
+            &self.order.maker,
+            &self.instructions()?,
+            &self.address_lookup_table_accounts,
+            self.recent_blockhash,
+        )
+        .map_err(|error| anyhow!("Failed to compile fill message: {error}"))?;
+        Ok(VersionedMessage::V0(message))
+    }
+
+    /// Unsigned transaction with a default signature for the maker and the taker
+    pub fn build_transaction(&self) -> Result<VersionedTransaction> {
+        let message = self.build_message()?;
+        let num_required_signatures = usize::from(message.header().num_required_signatures);
+        Ok(VersionedTransaction {
+            signatures: vec![Signature::default(); num_required_signatures],
+            message,
+        })
+    }
+
+    /// The transaction encoded as in `SwapRequest::transaction`
+    pub fn build_base64(&self) -> Result<String> {
+        let transaction = self.build_transaction()?;
+        Ok(BASE64_STANDARD.encode(bincode::serialize(&transaction)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        fill::{validate_fill_sanitized_message, ValidatedFill},
+        policy::FillValidationPolicy,
+        transaction::{
+            deserialize_transaction_base64, versioned_message_to_sanitized_message,
+            NoAddressLookupTables,
+        },
+    };
+    use anchor_lang::pubkey;
+
+    // Transaction built by the RFQ API, also used by the acceptance tests
+    const RFQ_TRANSACTION: &str = "AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAgAIABw1+jAiHYL/eHd3PMsF/IJuCQu5SqvEx+s2I0OosbQsG8kjAG1BZAFRV2dywxrzs3LT7Wy6rwamoK1c5K6qkDwTmwoAL86DDaPJrpECH4O7FIcjNK8aXLr8U+vEPOkKqMIbT6oz1rKyozQUgdRIXXEPO9Upd2Z7eIKFrVSU3OPOX3N7E3kRk8Ll8XsOf5Ir4ISzHf+0ZUtqBSXSNVE5iS+sA4iF2IlhNfbkvqPIGGddbql5WIVIAOvUkFwCrBoXw04EAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAMGRm/lIRcy/+ytunLDm+e8jOW7xfcSayxDmzpAAAAABHnZx8wQNd5yEfmetIwJ1wsr31vfni5WuKH7taLqMycG3fbh12Whk9nL4UbO63msHLSF7V9bN5E6jPWFfv8AqUpYSftyo7vpH9xbDmpX9jxaHLRbIGem7Qys02OVyKECjJclj04kifG7PRApFI4NgwtaE5na/xCEBI572Nvp+FnG+nrzvtutOj1l82qryXQxsbvkwtL24OR8pgIDRS9dYSdenhhyIvZ+yaOk0Giv3sQzHPEybygyONx7iDX7IHF2BAcACQMK0gAAAAAAAAcABQIdmAAACwYBBAEMBgkBAQoLAQACBQQDCAkMCQYjqGC3o1wKKKAAypo7AAAAAJgWfQEAAAAAaiqpZwAAAAAKAAAA";
+
+    fn rfq_order() -> Order {
+        Order {
+            taker: pubkey!("5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9"),
+            maker: pubkey!("9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM"),
+            in_amount: 1_000_000_000,
+            input_mint: pubkey!("JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN"),
+            out_amount: 24_975_000,
+            output_mint: pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"),
+            expire_at: 1_739_139_690,
+            expected_trailer: Some(FillTrailer::new(10, false)),
+        }
+    }
+
+    #[test]
+    fn test_reproduces_rfq_transaction() {
+        let transaction = FillTransactionBuilder::new(rfq_order())
+            .compute_unit_price(53_770)
+            .compute_unit_limit(38_941)
+            .recent_blockhash(
+                Hash::from_str("3egafgGdVLBfYntpABAQueZdedLSSpgR9UWzM7hp4c8D").unwrap(),
+            )
+            .build_base64()
+            .unwrap();
+        assert_eq!(transaction, RFQ_TRANSACTION);
+
+        let sanitized_message = versioned_message_to_sanitized_message(
+            deserialize_transaction_base64(&transaction)
+                .unwrap()
+                .message,
+            &NoAddressLookupTables,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_fill_sanitized_message(
+                &sanitized_message,
+                rfq_order(),
+                &FillValidationPolicy::default()
+            )
+            .unwrap(),
+            ValidatedFill {
+                compute_unit_limit: 38_941,
+                compute_unit_price: 53_770,
+                trailer: Some(FillTrailer::new(10, false)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_account_kind_combinations_pass_validation() {
+        use AccountKind::*;
+
+        for (taker_accounts, maker_accounts, input_native, output_native, temporary_wsol) in [
+            ((Token, Token), (Token, Token), false, false, false),
+            ((Token2022, Token), (Token2022, Token), false, false, false),
+            ((NativeSol, Token), (NativeSol, Token), true, false, false),
+            ((NativeSol, Token), (Wsol, Token), true, false, false),
+            ((Wsol, Token), (NativeSol, Token), true, false, true),
+            ((Token, NativeSol), (Token, Wsol), false, true, true),
+            ((Token, Wsol), (Token, NativeSol), false, true, false),
+            ((Token, Wsol), (Token, Wsol), false, true, false),
+        ] {
+            let mut order = rfq_order();
+            if input_native {
+                order.input_mint = native_mint::ID;
+            }
+            if output_native {
+                order.output_mint = native_mint::ID;
+            }
+            let builder = FillTransactionBuilder::new(order.clone())
+                .taker_accounts(taker_accounts.0, taker_accounts.1)
+                .maker_accounts(maker_accounts.0, maker_accounts.1);
+
+            let fill_instruction = builder.fill_instruction().unwrap();
+            assert_eq!(
+                fill_instruction.accounts.len(),
+                if temporary_wsol { 12 } else { 11 },
+                "{taker_accounts:?} {maker_accounts:?}"
+            );
+
+            let sanitized_message = versioned_message_to_sanitized_message(
+                builder.build_message().unwrap(),
+                &NoAddressLookupTables,
+            )
+            .unwrap();
+            validate_fill_sanitized_message(
+                &sanitized_message,
+                order,
+                &FillValidationPolicy::default(),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_account_kind_must_match_mint() {
+        let error = FillTransactionBuilder::new(rfq_order())
+            .taker_accounts(AccountKind::NativeSol, AccountKind::Token)
+            .build_message()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Account kind NativeSol of the taker input does not match mint JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN"
+        );
+    }
+}
@@ -0,0 +1,468 @@
+//! Everything a fill transaction carries, decoded without checking it against an order
+//!
+//! Both fill validators start from [`decode_fill_sanitized_message`], makers can also use
+//! [`decode_fill_transaction`] to log what a swap request asks them to sign.
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use anchor_spl::associated_token;
+use solana_sdk::{
+    borsh1::try_from_slice_unchecked,
+    compute_budget::{self, ComputeBudgetInstruction},
+    message::SanitizedMessage,
+    pubkey::Pubkey,
+    sysvar::instructions::BorrowedInstruction,
+};
+
+use crate::{
+    error::{FillValidationError, Result},
+    order_engine,
+    policy::LIGHTHOUSE_PROGRAM_ID,
+    trailer::FillTrailer,
+    transaction::{
+        deserialize_transaction_base64, versioned_message_to_sanitized_message,
+        AddressLookupTableLoader, NoAddressLookupTables,
+    },
+};
+
+/// The 11 accounts of the fill instruction, in order
+///
+/// Anchor passes the order engine program id for an optional account that is absent, these are
+/// decoded as `None`: the side pays or receives native SOL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FillAccounts {
+    pub taker: Pubkey,
+    pub maker: Pubkey,
+    pub taker_input_mint_token_account: Option<Pubkey>,
+    pub maker_input_mint_token_account: Option<Pubkey>,
+    pub taker_output_mint_token_account: Option<Pubkey>,
+    pub maker_output_mint_token_account: Option<Pubkey>,
+    pub input_mint: Pubkey,
+    pub input_token_program: Pubkey,
+    pub output_mint: Pubkey,
+    pub output_token_program: Pubkey,
+    pub system_program: Pubkey,
+}
+
+impl FillAccounts {
+    fn decode(accounts: &[Pubkey]) -> Result<Self> {
+        let optional = |account: &Pubkey| (account != &order_engine::ID).then_some(*account);
+        let [taker, maker, taker_input_mint_token_account, maker_input_mint_token_account, taker_output_mint_token_account, maker_output_mint_token_account, input_mint, input_token_program, output_mint, output_token_program, system_program, ..] =
+            accounts
+        else {
+            return Err(FillValidationError::NotEnoughFillAccounts {
+                actual: accounts.len(),
+            });
+        };
+        Ok(Self {
+            taker: *taker,
+            maker: *maker,
+            taker_input_mint_token_account: optional(taker_input_mint_token_account),
+            maker_input_mint_token_account: optional(maker_input_mint_token_account),
+            taker_output_mint_token_account: optional(taker_output_mint_token_account),
+            maker_output_mint_token_account: optional(maker_output_mint_token_account),
+            input_mint: *input_mint,
+            input_token_program: *input_token_program,
+            output_mint: *output_mint,
+            output_token_program: *output_token_program,
+            system_program: *system_program,
+        })
+    }
+}
+
+/// An associated token account program instruction creating a token account
+///
+/// Accounts missing from a malformed instruction are `None`, such an instruction fails on-chain.
+/// Other instructions of the program are only listed in
+/// [`DecodedFill::other_associated_token_account_instructions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateAssociatedTokenAccount {
+    /// Position of the instruction in the message
+    pub index: usize,
+    /// `CreateIdempotent` rather than `Create`, which fails when the account exists
+    pub idempotent: bool,
+    pub payer: Option<Pubkey>,
+    pub associated_token_account: Option<Pubkey>,
+    pub owner: Option<Pubkey>,
+    pub mint: Option<Pubkey>,
+    pub token_program: Option<Pubkey>,
+}
+
+impl CreateAssociatedTokenAccount {
+    fn decode(index: usize, accounts: &[Pubkey], data: &[u8]) -> Option<Self> {
+        let idempotent = match data {
+            [] | [0] => false,
+            [1] => true,
+            _ => return None,
+        };
+        let account = |position: usize| accounts.get(position).copied();
+        Some(Self {
+            index,
+            idempotent,
+            payer: account(0),
+            associated_token_account: account(1),
+            owner: account(2),
+            mint: account(3),
+            token_program: account(5),
+        })
+    }
+}
+
+/// A Lighthouse instruction, usually an assertion appended by the wallet of the taker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LighthouseInstruction {
+    /// Position of the instruction in the message
+    pub index: usize,
+    pub accounts: Vec<Pubkey>,
+    pub data: Vec<u8>,
+}
+
+impl LighthouseInstruction {
+    pub fn discriminator(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+}
+
+/// The fill instruction of a transaction and the instructions built around it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedFill {
+    /// Position of the fill instruction in the message
+    pub index: usize,
+    pub accounts: FillAccounts,
+    pub input_amount: u64,
+    pub output_amount: u64,
+    pub expire_at: i64,
+    /// Fee and direction appended to the fill instruction, if any
+    pub trailer: Option<FillTrailer>,
+    /// Bytes left after the anchor arguments, `trailer` is decoded from the first ones
+    pub trailer_data: Vec<u8>,
+    /// The last limit set when there are several
+    pub compute_unit_limit: Option<u32>,
+    /// Positions of the `SetComputeUnitLimit` instructions after the first one
+    pub repeated_compute_unit_limits: Vec<usize>,
+    pub compute_unit_price: Option<u64>,
+    pub create_associated_token_accounts: Vec<CreateAssociatedTokenAccount>,
+    /// Positions of the associated token account program instructions that do not create a token
+    /// account, such as `RecoverNested`
+    pub other_associated_token_account_instructions: Vec<usize>,
+    pub lighthouse_instructions: Vec<LighthouseInstruction>,
+}
+
+impl DecodedFill {
+    pub fn fee_bps(&self) -> Option<u16> {
+        self.trailer.map(|trailer| trailer.fee_bps)
+    }
+
+    /// `None` when the fill has no trailer to tell the direction
+    pub fn is_exact_out(&self) -> Option<bool> {
+        self.trailer.map(|trailer| trailer.is_exact_out())
+    }
+}
+
+/// Split the fill instruction data into its anchor arguments and the trailer bytes that follow them
+pub(crate) fn parse_fill_data(
+    index: usize,
+    data: &[u8],
+) -> Result<(order_engine::client::args::Fill, &[u8])> {
+    // Must slice off anchor's discriminator first
+    let Some((discriminator, mut ix_data)) = data.split_at_checked(8) else {
+        return Err(FillValidationError::InvalidFillDiscriminator { index });
+    };
+    if discriminator != order_engine::client::args::Fill::DISCRIMINATOR {
+        return Err(FillValidationError::InvalidFillDiscriminator { index });
+    }
+
+    let fill_ix = order_engine::client::args::Fill::deserialize(&mut ix_data)
+        .map_err(|e| FillValidationError::InvalidFillData(e.to_string()))?;
+
+    // What is left after the anchor arguments is the trailer
+    Ok((fill_ix, ix_data))
+}
+
+/// Decode the fill instruction, compute budget, token account creations and Lighthouse
+/// instructions of a message
+///
+/// Instructions of other programs are left to the validators.
+pub fn decode_fill_sanitized_message(sanitized_message: &SanitizedMessage) -> Result<DecodedFill> {
+    let mut fill = None;
+    let mut compute_unit_limit = None;
+    let mut repeated_compute_unit_limits = vec![];
+    let mut compute_unit_price = None;
+    let mut create_associated_token_accounts = vec![];
+    let mut other_associated_token_account_instructions = vec![];
+    let mut lighthouse_instructions = vec![];
+
+    for (
+        index,
+        BorrowedInstruction {
+            program_id,
+            accounts,
+            data,
+        },
+    ) in sanitized_message
+        .decompile_instructions()
+        .into_iter()
+        .enumerate()
+    {
+        let pubkeys = || accounts.iter().map(|a| *a.pubkey).collect::<Vec<_>>();
+        if program_id == &compute_budget::ID {
+            let compute_budget_ix = try_from_slice_unchecked::<ComputeBudgetInstruction>(data)
+                .map_err(|_| FillValidationError::InvalidComputeBudgetInstruction { index })?;
+            match compute_budget_ix {
+                ComputeBudgetInstruction::SetComputeUnitLimit(limit) => {
+                    // The validators decide whether a repeated limit is acceptable
+                    if compute_unit_limit.is_some() {
+                        repeated_compute_unit_limits.push(index);
+                    }
+                    compute_unit_limit = Some(limit);
+                }
+                ComputeBudgetInstruction::SetComputeUnitPrice(price) => {
+                    if compute_unit_price.is_some() {
+                        return Err(FillValidationError::DuplicateComputeUnitPrice { index });
+                    }
+                    compute_unit_price = Some(price);
+                }
+                _ => return Err(FillValidationError::UnexpectedComputeBudgetInstruction { index }),
+            }
+        } else if program_id == &associated_token::ID {
+            match CreateAssociatedTokenAccount::decode(index, &pubkeys(), data) {
+                Some(create_associated_token_account) => {
+                    create_associated_token_accounts.push(create_associated_token_account)
+                }
+                None => other_associated_token_account_instructions.push(index),
+            }
+        } else if program_id == &order_engine::ID {
+            if fill.is_some() {
+                return Err(FillValidationError::DuplicateFillInstruction { index });
+            }
+            let accounts = FillAccounts::decode(&pubkeys())?;
+            let (fill_ix, trailer_data) = parse_fill_data(index, data)?;
+            fill = Some((index, accounts, fill_ix, trailer_data.to_vec()));
+        } else if program_id == &LIGHTHOUSE_PROGRAM_ID {
+            lighthouse_instructions.push(LighthouseInstruction {
+                index,
+                accounts: pubkeys(),
+                data: data.to_vec(),
+            });
+        }
+    }
+
+    let (index, accounts, fill_ix, trailer_data) =
+        fill.ok_or(FillValidationError::MissingFillInstruction)?;
+    Ok(DecodedFill {
+        index,
+        accounts,
+        input_amount: fill_ix.input_amount,
+        output_amount: fill_ix.output_amount,
+        expire_at: fill_ix.expire_at,
+        trailer: FillTrailer::decode_remaining(&trailer_data),
+        trailer_data,
+        compute_unit_limit,
+        repeated_compute_unit_limits,
+        compute_unit_price,
+        create_associated_token_accounts,
+        other_associated_token_account_instructions,
+        lighthouse_instructions,
+    })
+}
+
+/// Decode the base64 transaction of a swap request that uses no address lookup table
+pub fn decode_fill_transaction(transaction: &str) -> Result<DecodedFill> {
+    decode_fill_transaction_with_loader(transaction, &NoAddressLookupTables)
+}
+
+/// Decode the base64 transaction of a swap request, `loader` resolves the address lookup tables
+/// of a v0 message
+pub fn decode_fill_transaction_with_loader<L: AddressLookupTableLoader + ?Sized>(
+    transaction: &str,
+    loader: &L,
+) -> Result<DecodedFill> {
+    let invalid_transaction =
+        |error: anyhow::Error| FillValidationError::InvalidTransaction(error.to_string());
+    let versioned_transaction =
+        deserialize_transaction_base64(transaction).map_err(invalid_transaction)?;
+    let sanitized_message =
+        versioned_message_to_sanitized_message(versioned_transaction.message, loader)
+            .map_err(invalid_transaction)?;
+    decode_fill_sanitized_message(&sanitized_message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::{AccountKind, FillTransactionBuilder},
+        fill::Order,
+    };
+    use anchor_spl::token::spl_token::native_mint;
+    use solana_sdk::{
+        hash::Hash,
+        instruction::Instruction,
+        message::{v0, VersionedMessage},
+        system_program,
+    };
+
+    #[test]
+    fn test_decode_fill_transaction() {
+        let order = Order {
+            taker: Pubkey::new_unique(),
+            maker: Pubkey::new_unique(),
+            in_amount: 1_000_000,
+            input_mint: Pubkey::new_unique(),
+            out_amount: 5_000_000,
+            output_mint: native_mint::ID,
+            expire_at: 1_700_000_000,
+            expected_trailer: None,
+        };
+        let trailer = FillTrailer::new(20, true);
+        let builder = FillTransactionBuilder::new(order.clone())
+            .taker_accounts(AccountKind::Token, AccountKind::Wsol)
+            .maker_accounts(AccountKind::Token, AccountKind::NativeSol)
+            .create_taker_output_token_account(true)
+            .trailer(trailer)
+            .compute_unit_limit(100_000)
+            .compute_unit_price(1_500);
+
+        let decoded_fill = decode_fill_transaction(&builder.build_base64().unwrap()).unwrap();
+        let taker_output_mint_token_account = AccountKind::Wsol
+            .token_account(&order.taker, &order.output_mint)
+            .unwrap();
+        assert_eq!(
+            decoded_fill.accounts,
+            FillAccounts {
+                taker: order.taker,
+                maker: order.maker,
+                taker_input_mint_token_account: AccountKind::Token
+                    .token_account(&order.taker, &order.input_mint),
+                maker_input_mint_token_account: AccountKind::Token
+                    .token_account(&order.maker, &order.input_mint),
+                taker_output_mint_token_account: Some(taker_output_mint_token_account),
+                maker_output_mint_token_account: None,
+                input_mint: order.input_mint,
+                input_token_program: AccountKind::Token.token_program(),
+                output_mint: order.output_mint,
+                output_token_program: AccountKind::Wsol.token_program(),
+                system_program: system_program::ID,
+            }
+        );
+        assert_eq!(decoded_fill.input_amount, order.in_amount);
+        assert_eq!(decoded_fill.output_amount, order.out_amount);
+        assert_eq!(decoded_fill.expire_at, order.expire_at);
+        assert_eq!(decoded_fill.fee_bps(), Some(20));
+        assert_eq!(decoded_fill.is_exact_out(), Some(true));
+        assert_eq!(decoded_fill.compute_unit_limit, Some(100_000));
+        assert_eq!(decoded_fill.compute_unit_price, Some(1_500));
+        let [create_associated_token_account] =
+            decoded_fill.create_associated_token_accounts.as_slice()
+        else {
+            panic!("Expected one token account creation");
+        };
+        assert!(create_associated_token_account.idempotent);
+        assert_eq!(create_associated_token_account.payer, Some(order.taker));
+        assert_eq!(
+            create_associated_token_account.associated_token_account,
+            Some(taker_output_mint_token_account)
+        );
+        assert!(decoded_fill.lighthouse_instructions.is_empty());
+        assert_eq!(
+            decoded_fill.index,
+            decoded_fill.create_associated_token_accounts[0].index + 1
+        );
+
+        let error = decode_fill_transaction("not a transaction").unwrap_err();
+        assert!(matches!(error, FillValidationError::InvalidTransaction(_)));
+        assert!(error.is_malformed());
+    }
+
+    #[test]
+    fn test_decode_fill_sanitized_message_errors() {
+        let order = Order {
+            taker: Pubkey::new_unique(),
+            maker: Pubkey::new_unique(),
+            in_amount: 1,
+            input_mint: Pubkey::new_unique(),
+            out_amount: 2,
+            output_mint: Pubkey::new_unique(),
+            expire_at: 1_700_000_000,
+            expected_trailer: None,
+        };
+        let builder = FillTransactionBuilder::new(order.clone());
+        let decode = |instructions: &[Instruction]| {
+            let message =
+                v0::Message::try_compile(&order.maker, instructions, &[], Hash::default()).unwrap();
+            let sanitized_message = versioned_message_to_sanitized_message(
+                VersionedMessage::V0(message),
+                &NoAddressLookupTables,
+            )
+            .unwrap();
+            decode_fill_sanitized_message(&sanitized_message)
+        };
+
+        let instructions = builder.instructions().unwrap();
+        let fill_ix = builder.fill_instruction().unwrap();
+        assert!(decode(&instructions).is_ok());
+        assert_eq!(
+            decode(&instructions[..instructions.len() - 1]),
+            Err(FillValidationError::MissingFillInstruction)
+        );
+        assert_eq!(
+            decode(&[instructions.as_slice(), &[fill_ix.clone()]].concat()),
+            Err(FillValidationError::DuplicateFillInstruction {
+                index: instructions.len()
+            })
+        );
+
+        let mut short_fill_ix = fill_ix;
+        short_fill_ix.accounts.truncate(10);
+        assert_eq!(
+            decode(&[short_fill_ix]),
+            Err(FillValidationError::NotEnoughFillAccounts { actual: 10 })
+        );
+    }
+
+    #[test]
+    fn test_decode_fill_sanitized_message_left_to_validators() {
+        let order = Order {
+            taker: Pubkey::new_unique(),
+            maker: Pubkey::new_unique(),
+            in_amount: 1,
+            input_mint: Pubkey::new_unique(),
+            out_amount: 2,
+            output_mint: Pubkey::new_unique(),
+            expire_at: 1_700_000_000,
+            expected_trailer: None,
+        };
+        let fill_ix = FillTransactionBuilder::new(order.clone())
+            .fill_instruction()
+            .unwrap();
+        let recover_nested_ix = Instruction {
+            program_id: associated_token::ID,
+            accounts: vec![],
+            data: vec![2],
+        };
+        let message = v0::Message::try_compile(
+            &order.maker,
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(100_000),
+                recover_nested_ix,
+                ComputeBudgetInstruction::set_compute_unit_limit(200_000),
+                fill_ix,
+            ],
+            &[],
+            Hash::default(),
+        )
+        .unwrap();
+        let sanitized_message = versioned_message_to_sanitized_message(
+            VersionedMessage::V0(message),
+            &NoAddressLookupTables,
+        )
+        .unwrap();
+
+        let decoded_fill = decode_fill_sanitized_message(&sanitized_message).unwrap();
+        assert_eq!(decoded_fill.compute_unit_limit, Some(200_000));
+        assert_eq!(decoded_fill.repeated_compute_unit_limits, vec![2]);
+        assert!(decoded_fill.create_associated_token_accounts.is_empty());
+        assert_eq!(
+            decoded_fill.other_associated_token_account_instructions,
+            vec![1]
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! What a fill costs the maker, once the wallet of the taker had a say in the compute unit price
+//!
+//! Wallets tend to raise the compute unit price of the transaction they sign, the priority fee
+//! paid by the maker as the fee payer is only known from the transaction received on /swap.
+use solana_sdk::message::SanitizedMessage;
+
+use crate::{
+    error::FillCostError,
+    fill::{Order, ValidatedFill},
+    solvency::LAMPORTS_PER_SIGNATURE,
+};
+
+const BPS_DENOMINATOR: u128 = 10_000;
+
+/// Price of a token in lamports, `lamports` for `amount` atomic units
+///
+/// e.g. USDC at 150 per SOL is `LamportPrice { lamports: 1_000_000_000, amount: 150_000_000 }`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LamportPrice {
+    pub lamports: u64,
+    pub amount: u64,
+}
+
+impl LamportPrice {
+    /// Price of native SOL and WSOL
+    pub const NATIVE: Self = Self {
+        lamports: 1,
+        amount: 1,
+    };
+
+    /// Value of `amount` in lamports, rounded down or up, `None` when the price has no amount
+    fn value(&self, amount: u64, round_up: bool) -> Option<u128> {
+        let lamports = u128::from(amount) * u128::from(self.lamports);
+        let amount = u128::from(self.amount);
+        (amount > 0).then(|| {
+            if round_up {
+                lamports.div_ceil(amount)
+            } else {
+                lamports / amount
+            }
+        })
+    }
+}
+
+/// Prices the maker values both sides of a fill at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FillPrices {
+    pub input: LamportPrice,
+    pub output: LamportPrice,
+}
+
+/// Bounds past which the maker rejects a fill, `None` disables a bound
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FillCostLimits {
+    /// Most lamports of signature and priority fees the maker pays for a fill
+    pub max_network_fee: Option<u64>,
+    /// Least edge the fill must keep once every cost is accounted, only checked with prices
+    pub min_edge_bps: Option<i64>,
+}
+
+/// Amounts and fees of a validated fill, from the point of view of the maker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FillEconomics {
+    /// Received from the taker
+    pub in_amount: u64,
+    /// Sent to the taker
+    pub out_amount: u64,
+    pub fee_bps: u16,
+    /// Fee on the output, not transferred by the fill but accounted to the maker afterwards
+    pub fee_amount: u64,
+    /// Signature fees, paid by the maker as the fee payer
+    pub transaction_fee: u64,
+    pub priority_fee: u64,
+}
+
+impl FillEconomics {
+    /// Economics of `validated_fill`, the result of validating `sanitized_message` against `order`
+    ///
+    /// The fee bps are taken from the trailer of the fill, 0 when there is none.
+    pub fn new(
+        sanitized_message: &SanitizedMessage,
+        order: &Order,
+        validated_fill: &ValidatedFill,
+    ) -> Self {
+        let fee_bps = validated_fill
+            .trailer
+            .map(|trailer| trailer.fee_bps)
+            .unwrap_or_default();
+        // the transferred output is what is left of the quoted output after the fee, e.g. 990
+        // USDC are sent for a quote of 1000 USDC at 100 bps
+        let fee_amount = BPS_DENOMINATOR
+            .checked_sub(u128::from(fee_bps))
+            .filter(|kept_bps| *kept_bps > 0)
+            .and_then(|kept_bps| {
+                u64::try_from(
+                    (u128::from(order.out_amount) * u128::from(fee_bps)).div_ceil(kept_bps),
+                )
+                .ok()
+            })
+            .unwrap_or(u64::MAX);
+        Self {
+            in_amount: order.in_amount,
+            out_amount: order.out_amount,
+            fee_bps,
+            fee_amount,
+            transaction_fee: sanitized_message
+                .get_signature_details()
+                .num_transaction_signatures()
+                .saturating_mul(LAMPORTS_PER_SIGNATURE),
+            priority_fee: validated_fill.priority_fee(),
+        }
+    }
+
+    /// Signature and priority fees, in lamports
+    pub fn network_fee(&self) -> u64 {
+        self.transaction_fee.saturating_add(self.priority_fee)
+    }
+
+    /// Lamports the fill costs the maker: the output and its fee valued at `output`, and the
+    /// network fee
+    pub fn all_in_cost(&self, output: LamportPrice) -> u64 {
+        output
+            .value(self.out_amount.saturating_add(self.fee_amount), true)
+            .and_then(|cost| u64::try_from(cost).ok())
+            .unwrap_or(u64::MAX)
+            .saturating_add(self.network_fee())
+    }
+
+    /// What the maker keeps of the input value once the all-in cost is paid, in bps of the input
+    /// value, negative for a loss
+    ///
+    /// `None` when the input is worth nothing at these prices.
+    pub fn edge_bps(&self, prices: &FillPrices) -> Option<i64> {
+        let input_value = prices
+            .input
+            .value(self.in_amount, false)
+            .filter(|input_value| *input_value > 0)?;
+        let edge = (input_value as i128 - i128::from(self.all_in_cost(prices.output)))
+            * BPS_DENOMINATOR as i128
+            / input_value as i128;
+        Some(i64::try_from(edge).unwrap_or(i64::MIN))
+    }
+
+    /// Check the fill is within `limits`, the edge is only checked when `prices` are known
+    pub fn check(
+        &self,
+        limits: &FillCostLimits,
+        prices: Option<&FillPrices>,
+    ) -> Result<(), FillCostError> {
+        if let Some(max_network_fee) = limits.max_network_fee {
+            if self.network_fee() > max_network_fee {
+                return Err(FillCostError::NetworkFeeTooHigh {
+                    network_fee: self.network_fee(),
+                    max_network_fee,
+                });
+            }
+        }
+        if let (Some(min_edge_bps), Some(prices)) = (limits.min_edge_bps, prices) {
+            let edge_bps = self.edge_bps(prices).unwrap_or(i64::MIN);
+            if edge_bps < min_edge_bps {
+                return Err(FillCostError::EdgeTooLow {
+                    edge_bps,
+                    min_edge_bps,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::FillTransactionBuilder,
+        fill::validate_fill_sanitized_message,
+        policy::FillValidationPolicy,
+        trailer::FillTrailer,
+        transaction::{versioned_message_to_sanitized_message, NoAddressLookupTables},
+    };
+    use solana_sdk::pubkey::Pubkey;
+    use webhook_api::enums::RejectionReason;
+
+    #[test]
+    fn test_fill_economics() {
+        // 1 SOL for 990 USDC once 100 bps of fee are taken off the quoted 1000 USDC
+        let order = Order {
+            taker: Pubkey::new_unique(),
+            maker: Pubkey::new_unique(),
+            in_amount: 1_000_000_000,
+            input_mint: Pubkey::new_unique(),
+            out_amount: 990_000_000,
+            output_mint: Pubkey::new_unique(),
+            expire_at: i64::MAX,
+            expected_trailer: Some(FillTrailer::new(100, false)),
+        };
+        let economics = |compute_unit_price| {
+            let sanitized_message = versioned_message_to_sanitized_message(
+                FillTransactionBuilder::new(order.clone())
+                    .compute_unit_limit(200_000)
+                    .compute_unit_price(compute_unit_price)
+                    .build_message()
+                    .unwrap(),
+                &NoAddressLookupTables,
+            )
+            .unwrap();
+            let validated_fill = validate_fill_sanitized_message(
+                &sanitized_message,
+                order.clone(),
+                &FillValidationPolicy::default(),
+            )
+            .unwrap();
+            FillEconomics::new(&sanitized_message, &order, &validated_fill)
+        };
+
+        let quoted = economics(50_000);
+        assert_eq!(quoted.fee_bps, 100);
+        assert_eq!(quoted.fee_amount, 10_000_000);
+        assert_eq!(quoted.transaction_fee, 10_000);
+        assert_eq!(quoted.priority_fee, 10_000);
+
+        // the maker values SOL at 1010 USDC
+        let prices = FillPrices {
+            input: LamportPrice::NATIVE,
+            output: LamportPrice {
+                lamports: 1_000_000_000,
+                amount: 1_010_000_000,
+            },
+        };
+        // 1000 USDC are worth 990_099_010 lamports, rounded up
+        assert_eq!(quoted.all_in_cost(prices.output), 990_099_010 + 20_000);
+        assert_eq!(quoted.edge_bps(&prices), Some(98));
+
+        let limits = FillCostLimits {
+            max_network_fee: Some(100_000),
+            min_edge_bps: Some(50),
+        };
+        assert_eq!(quoted.check(&limits, Some(&prices)), Ok(()));
+
+        // the wallet raised the compute unit price after the quote
+        let inflated = economics(5_000_000);
+        assert_eq!(inflated.priority_fee, 1_000_000);
+        let error = inflated.check(&limits, None).unwrap_err();
+        assert_eq!(
+            error,
+            FillCostError::NetworkFeeTooHigh {
+                network_fee: 1_010_000,
+                max_network_fee: 100_000,
+            }
+        );
+        assert_eq!(
+            RejectionReason::from(error),
+            RejectionReason::RiskLimitExceeded
+        );
+
+        // the market moved against the maker
+        let prices = FillPrices {
+            output: LamportPrice {
+                lamports: 1_000_000_000,
+                amount: 995_000_000,
+            },
+            ..prices
+        };
+        assert_eq!(quoted.edge_bps(&prices), Some(-50));
+        assert_eq!(
+            quoted.check(&limits, Some(&prices)),
+            Err(FillCostError::EdgeTooLow {
+                edge_bps: -50,
+                min_edge_bps: 50,
+            })
+        );
+        assert_eq!(
+            quoted.check(&FillCostLimits::default(), Some(&prices)),
+            Ok(())
+        );
+    }
+}
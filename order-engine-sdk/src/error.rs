@@ -0,0 +1,411 @@
+use solana_sdk::{pubkey::Pubkey, signer::SignerError};
+use thiserror::Error;
+use webhook_api::enums::RejectionReason;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FillValidationError {
+    #[error("Fee payer was not the expected maker {expected} but was {actual}")]
+    UnexpectedFeePayer { expected: Pubkey, actual: Pubkey },
+
+    #[error("Not enough signers, expected at least {expected} but got {actual}")]
+    NotEnoughSigners { expected: u64, actual: u64 },
+
+    #[error("Missing signer at index {index}")]
+    MissingSigner { index: usize },
+
+    #[error("Signer at index {index} was {actual} but expected {expected}")]
+    SignerMismatch {
+        index: usize,
+        expected: Pubkey,
+        actual: Pubkey,
+    },
+
+    #[error("Number of required signatures {actual} did not match {expected}")]
+    RequiredSignaturesMismatch { expected: u8, actual: u8 },
+
+    #[error("Invalid compute budget instruction data at index {index}")]
+    InvalidComputeBudgetInstruction { index: usize },
+
+    #[error("Unexpected compute budget instruction at index {index}")]
+    UnexpectedComputeBudgetInstruction { index: usize },
+
+    #[error("Compute unit limit is already set at index {index}")]
+    DuplicateComputeUnitLimit { index: usize },
+
+    #[error("Compute unit price is already set at index {index}")]
+    DuplicateComputeUnitPrice { index: usize },
+
+    #[error("Missing compute unit limit")]
+    MissingComputeUnitLimit,
+
+    #[error("Missing compute unit price")]
+    MissingComputeUnitPrice,
+
+    #[error("Incorrect associated token account program data at index {index}")]
+    InvalidAssociatedTokenAccountInstruction { index: usize },
+
+    #[error("Associated token account at index {index} must not be paid by the maker")]
+    AssociatedTokenAccountPaidByMaker { index: usize },
+
+    #[error("Duplicated fill instruction at index {index}")]
+    DuplicateFillInstruction { index: usize },
+
+    #[error("Not a fill instruction at index {index}")]
+    InvalidFillDiscriminator { index: usize },
+
+    #[error("Invalid fill ix data {0}")]
+    InvalidFillData(String),
+
+    #[error("Not enough accounts in fill instruction, got {actual}")]
+    NotEnoughFillAccounts { actual: usize },
+
+    #[error("Invalid {account}, expected {expected} but was {actual}")]
+    AccountMismatch {
+        account: &'static str,
+        expected: Pubkey,
+        actual: Pubkey,
+    },
+
+    #[error("Invalid input amount, expected {expected} but was {actual}")]
+    InputAmountMismatch { expected: u64, actual: u64 },
+
+    #[error("Invalid output amount, expected {expected} but was {actual}")]
+    OutputAmountMismatch { expected: u64, actual: u64 },
+
+    #[error("Incorrect expiry, expected {expected} but was {actual}")]
+    ExpiryMismatch { expected: i64, actual: i64 },
+
+    #[error("Invalid fill trailer length {len}, expected {expected}", expected = crate::trailer::FILL_TRAILER_LEN)]
+    InvalidTrailerLength { len: usize },
+
+    #[error("Unknown fill trailer flags {flags:#010b}")]
+    UnknownTrailerFlags { flags: u8 },
+
+    #[error("Missing fill trailer")]
+    MissingTrailer,
+
+    #[error("Incorrect fee bps {actual}, expected {expected}")]
+    FeeBpsMismatch { expected: u16, actual: u16 },
+
+    #[error("Incorrect swap direction, expected exact out {expected_exact_out}")]
+    DirectionMismatch { expected_exact_out: bool },
+
+    #[error("Missing fill instruction")]
+    MissingFillInstruction,
+
+    /// Not base64, not a transaction, or its address lookup tables could not be loaded
+    #[error("Could not decode the transaction: {0}")]
+    InvalidTransaction(String),
+
+    #[error("Unexpected program id {program_id} at index {index}")]
+    UnexpectedProgramId { index: usize, program_id: Pubkey },
+
+    #[error("Number of instructions {actual} cannot be less than original {expected}")]
+    NotEnoughInstructions { expected: usize, actual: usize },
+
+    #[error("Instruction program id {actual} did not match the original message at index {index}, {expected}")]
+    ProgramIdMismatch {
+        index: usize,
+        expected: Pubkey,
+        actual: Pubkey,
+    },
+
+    #[error("Instruction accounts did not match the original message {index}, {program_id}")]
+    InstructionAccountsMismatch { index: usize, program_id: Pubkey },
+
+    #[error("Instruction did not match the original at index {index}, {program_id}")]
+    InstructionDataMismatch { index: usize, program_id: Pubkey },
+
+    #[error(
+        "Forbidden instruction discriminator {discriminator:?} of {program_id} at index {index}"
+    )]
+    ForbiddenInstruction {
+        index: usize,
+        program_id: Pubkey,
+        discriminator: Option<u8>,
+    },
+
+    #[error("Too many instructions, got {actual} but at most {max} are allowed")]
+    TooManyInstructions { max: usize, actual: usize },
+
+    #[error("Associated token account creation at index {index} is not allowed")]
+    AssociatedTokenAccountNotAllowed { index: usize },
+
+    #[error("Compute unit limit {limit} is outside of [{min}, {max}]")]
+    ComputeUnitLimitOutOfBounds { limit: u32, min: u32, max: u32 },
+
+    #[error("Compute unit price {price} is outside of [{min}, {max}]")]
+    ComputeUnitPriceOutOfBounds { price: u64, min: u64, max: u64 },
+
+    #[error("Invalid Lighthouse instruction at index {index}")]
+    InvalidLighthouseInstruction { index: usize },
+
+    #[error("Lighthouse assertion at index {index} targets the maker account {account}")]
+    LighthouseAssertionOnMakerAccount { index: usize, account: Pubkey },
+
+    #[error("Lighthouse assertion at index {index} can never be satisfied by the fill")]
+    UnsatisfiableLighthouseAssertion { index: usize },
+
+    #[error("Order expiring at {expire_at} has {seconds_to_expiry}s left, at least {min_seconds_to_expiry}s are required")]
+    ExpiresTooSoon {
+        expire_at: i64,
+        seconds_to_expiry: i64,
+        min_seconds_to_expiry: u64,
+    },
+}
+
+impl FillValidationError {
+    /// The transaction could not be decoded, as opposed to decoding into something unexpected
+    pub fn is_malformed(&self) -> bool {
+        matches!(
+            self,
+            Self::InvalidTransaction(_)
+                | Self::InvalidComputeBudgetInstruction { .. }
+                | Self::InvalidFillData(_)
+                | Self::InvalidTrailerLength { .. }
+                | Self::InvalidLighthouseInstruction { .. }
+        )
+    }
+}
+
+impl From<&FillValidationError> for RejectionReason {
+    fn from(error: &FillValidationError) -> Self {
+        // Every variant is listed so that a new one has to be classified
+        match error {
+            FillValidationError::UnexpectedFeePayer { .. }
+            | FillValidationError::NotEnoughSigners { .. }
+            | FillValidationError::MissingSigner { .. }
+            | FillValidationError::SignerMismatch { .. }
+            | FillValidationError::RequiredSignaturesMismatch { .. } => {
+                RejectionReason::SignatureVerificationFailed
+            }
+            FillValidationError::ExpiresTooSoon { .. } => RejectionReason::QuoteExpired,
+            // The transaction fills something else than what was quoted
+            FillValidationError::AccountMismatch { .. }
+            | FillValidationError::InputAmountMismatch { .. }
+            | FillValidationError::OutputAmountMismatch { .. }
+            | FillValidationError::ExpiryMismatch { .. }
+            | FillValidationError::InvalidTrailerLength { .. }
+            | FillValidationError::UnknownTrailerFlags { .. }
+            | FillValidationError::MissingTrailer
+            | FillValidationError::FeeBpsMismatch { .. }
+            | FillValidationError::DirectionMismatch { .. }
+            | FillValidationError::NotEnoughInstructions { .. }
+            | FillValidationError::ProgramIdMismatch { .. }
+            | FillValidationError::InstructionAccountsMismatch { .. }
+            | FillValidationError::InstructionDataMismatch { .. } => {
+                RejectionReason::TransactionMismatch
+            }
+            // Valid transactions outside of the limits of the maker policy
+            FillValidationError::TooManyInstructions { .. }
+            | FillValidationError::AssociatedTokenAccountNotAllowed { .. }
+            | FillValidationError::ComputeUnitLimitOutOfBounds { .. }
+            | FillValidationError::ComputeUnitPriceOutOfBounds { .. } => {
+                RejectionReason::RiskLimitExceeded
+            }
+            // The transaction carries unexpected instructions or data
+            FillValidationError::InvalidComputeBudgetInstruction { .. }
+            | FillValidationError::UnexpectedComputeBudgetInstruction { .. }
+            | FillValidationError::DuplicateComputeUnitLimit { .. }
+            | FillValidationError::DuplicateComputeUnitPrice { .. }
+            | FillValidationError::MissingComputeUnitLimit
+            | FillValidationError::MissingComputeUnitPrice
+            | FillValidationError::InvalidAssociatedTokenAccountInstruction { .. }
+            | FillValidationError::AssociatedTokenAccountPaidByMaker { .. }
+            | FillValidationError::DuplicateFillInstruction { .. }
+            | FillValidationError::InvalidFillDiscriminator { .. }
+            | FillValidationError::InvalidFillData(_)
+            | FillValidationError::NotEnoughFillAccounts { .. }
+            | FillValidationError::MissingFillInstruction
+            | FillValidationError::InvalidTransaction(_)
+            | FillValidationError::UnexpectedProgramId { .. }
+            | FillValidationError::ForbiddenInstruction { .. }
+            | FillValidationError::InvalidLighthouseInstruction { .. }
+            | FillValidationError::LighthouseAssertionOnMakerAccount { .. }
+            | FillValidationError::UnsatisfiableLighthouseAssertion { .. } => {
+                RejectionReason::BotActivityDetected
+            }
+        }
+    }
+}
+
+impl From<FillValidationError> for RejectionReason {
+    fn from(error: FillValidationError) -> Self {
+        Self::from(&error)
+    }
+}
+
+/// Reasons to refuse co-signing a fill transaction, see [`crate::sign::sign_fill_transaction`]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SignFillError {
+    #[error("{signer} is not a required signer of the transaction")]
+    NotASigner { signer: Pubkey },
+
+    #[error("Transaction has {actual} signatures but requires {expected}")]
+    SignatureCountMismatch { expected: usize, actual: usize },
+
+    #[error("Missing signature of {signer} at index {index}")]
+    MissingSignature { index: usize, signer: Pubkey },
+
+    #[error("Invalid signature of {signer} at index {index}")]
+    InvalidSignature { index: usize, signer: Pubkey },
+
+    #[error("Failed to sign the transaction: {0}")]
+    Signer(#[from] SignerError),
+}
+
+impl From<&SignFillError> for RejectionReason {
+    fn from(_: &SignFillError) -> Self {
+        RejectionReason::SignatureVerificationFailed
+    }
+}
+
+impl From<SignFillError> for RejectionReason {
+    fn from(error: SignFillError) -> Self {
+        Self::from(&error)
+    }
+}
+
+/// Why the maker cannot settle a fill, see [`crate::solvency::check_maker_solvency`]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SolvencyError {
+    #[error(transparent)]
+    InvalidFill(#[from] FillValidationError),
+
+    #[error("Maker has {available} lamports but needs {required}")]
+    InsufficientLamports { required: u64, available: u64 },
+
+    #[error("Maker token account {token_account} holds {available} but {required} are sent")]
+    InsufficientTokenBalance {
+        token_account: Pubkey,
+        required: u64,
+        available: u64,
+    },
+
+    #[error(
+        "Maker has {available} lamports but needs {required} to create the temporary WSOL account"
+    )]
+    InsufficientLamportsForTemporaryWsolAccount { required: u64, available: u64 },
+}
+
+impl From<&SolvencyError> for RejectionReason {
+    fn from(error: &SolvencyError) -> Self {
+        match error {
+            SolvencyError::InvalidFill(error) => RejectionReason::from(error),
+            SolvencyError::InsufficientLamports { .. }
+            | SolvencyError::InsufficientTokenBalance { .. } => {
+                RejectionReason::InsufficientBalance
+            }
+            SolvencyError::InsufficientLamportsForTemporaryWsolAccount { .. } => {
+                RejectionReason::InsufficientBalanceForAtaCreation
+            }
+        }
+    }
+}
+
+impl From<SolvencyError> for RejectionReason {
+    fn from(error: SolvencyError) -> Self {
+        Self::from(&error)
+    }
+}
+
+/// Why a fill costs the maker more than it accepts, see [`crate::economics::FillEconomics::check`]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FillCostError {
+    #[error("Network fee of {network_fee} lamports is above {max_network_fee}")]
+    NetworkFeeTooHigh {
+        network_fee: u64,
+        max_network_fee: u64,
+    },
+
+    #[error("Edge of {edge_bps} bps is below {min_edge_bps}")]
+    EdgeTooLow { edge_bps: i64, min_edge_bps: i64 },
+}
+
+impl From<&FillCostError> for RejectionReason {
+    fn from(error: &FillCostError) -> Self {
+        match error {
+            FillCostError::NetworkFeeTooHigh { .. } => RejectionReason::RiskLimitExceeded,
+            FillCostError::EdgeTooLow { .. } => RejectionReason::PriceMoved,
+        }
+    }
+}
+
+impl From<FillCostError> for RejectionReason {
+    fn from(error: FillCostError) -> Self {
+        Self::from(&error)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, FillValidationError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejection_reason_mapping() {
+        let signer_mismatch = FillValidationError::SignerMismatch {
+            index: 1,
+            expected: Pubkey::new_unique(),
+            actual: Pubkey::new_unique(),
+        };
+        assert_eq!(
+            RejectionReason::from(&signer_mismatch),
+            RejectionReason::SignatureVerificationFailed
+        );
+        assert!(!signer_mismatch.is_malformed());
+
+        let unexpected_program = FillValidationError::UnexpectedProgramId {
+            index: 3,
+            program_id: Pubkey::new_unique(),
+        };
+        assert_eq!(
+            RejectionReason::from(unexpected_program),
+            RejectionReason::BotActivityDetected
+        );
+
+        assert_eq!(
+            RejectionReason::from(FillValidationError::OutputAmountMismatch {
+                expected: 1,
+                actual: 2
+            }),
+            RejectionReason::TransactionMismatch
+        );
+        assert_eq!(
+            RejectionReason::from(FillValidationError::ExpiresTooSoon {
+                expire_at: 0,
+                seconds_to_expiry: 1,
+                min_seconds_to_expiry: 5
+            }),
+            RejectionReason::QuoteExpired
+        );
+
+        assert!(FillValidationError::InvalidTrailerLength { len: 1 }.is_malformed());
+
+        // Policy limits are not bot activity
+        for error in [
+            FillValidationError::ComputeUnitPriceOutOfBounds {
+                price: 1_000,
+                min: 0,
+                max: 999,
+            },
+            FillValidationError::ComputeUnitLimitOutOfBounds {
+                limit: 100_000,
+                min: 200_000,
+                max: u32::MAX,
+            },
+            FillValidationError::TooManyInstructions { max: 3, actual: 4 },
+            FillValidationError::AssociatedTokenAccountNotAllowed { index: 0 },
+        ] {
+            assert_eq!(
+                RejectionReason::from(error),
+                RejectionReason::RiskLimitExceeded
+            );
+        }
+        assert_eq!(
+            RejectionReason::from(FillValidationError::UnknownTrailerFlags { flags: 2 }),
+            RejectionReason::TransactionMismatch
+        );
+    }
+}
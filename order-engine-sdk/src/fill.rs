@@ -1,23 +1,17 @@
-use crate::order_engine;
-use anchor_lang::{pubkey, AnchorDeserialize, Discriminator};
+use crate::{
+    decode::decode_fill_sanitized_message,
+    error::{FillValidationError, Result},
+    order_engine,
+    policy::FillValidationPolicy,
+    trailer::FillTrailer,
+};
 use anchor_spl::associated_token;
-use anyhow::{anyhow, bail, ensure, Context, Result};
 use solana_sdk::{
-    borsh1::try_from_slice_unchecked,
-    compute_budget::{self, ComputeBudgetInstruction},
-    message::SanitizedMessage,
-    pubkey::Pubkey,
+    compute_budget, message::SanitizedMessage, pubkey::Pubkey,
     sysvar::instructions::BorrowedInstruction,
 };
 
-const LIGHTHOUSE_PROGRAM_ID: Pubkey = pubkey!("L2TExMFKdjpN9kozasaurPirfHy9P8sbXoAN1qA3S95");
-
-// We only allow certain instruction from the Lighthouse program.
-//
-// If we allow the MemoryWrite instruction, the hacker can drain the signer.
-// https://github.com/Jac0xb/lighthouse/blob/main/programs/lighthouse/lighthouse.json
-const ALLOWED_LIGHTHOUSE_DISCRIMINATORS: &[u8] = &[5, 6, 9, 10];
-
+#[derive(Clone, Debug)]
 pub struct Order {
     pub taker: Pubkey,
     pub maker: Pubkey,
@@ -26,124 +20,177 @@ pub struct Order {
     pub out_amount: u64,
     pub output_mint: Pubkey,
     pub expire_at: i64,
+    /// When set, fills whose trailer fee or direction differ from the quote are rejected
+    pub expected_trailer: Option<FillTrailer>,
 }
 
+#[derive(PartialEq, Debug)]
 pub struct ValidatedFill {
     pub compute_unit_limit: u32,
     /// The maker should verify that the trade is still viable should the compute unit price change drastically
     /// The compute unit price might change from the original tx as wallets tend to mutate it
     pub compute_unit_price: u64,
+    /// Fee and direction appended to the fill instruction, if any
+    pub trailer: Option<FillTrailer>,
+}
+
+impl ValidatedFill {
+    /// Lamports paid on top of the signature fees, the price applies to the whole limit
+    pub fn priority_fee(&self) -> u64 {
+        let micro_lamports =
+            u128::from(self.compute_unit_price) * u128::from(self.compute_unit_limit);
+        u64::try_from(micro_lamports.div_ceil(1_000_000)).unwrap_or(u64::MAX)
+    }
+}
+
+fn ensure_account(account: &'static str, expected: &Pubkey, actual: &Pubkey) -> Result<()> {
+    if expected != actual {
+        return Err(FillValidationError::AccountMismatch {
+            account,
+            expected: *expected,
+            actual: *actual,
+        });
+    }
+    Ok(())
 }
 
 /// Given the knowledge of the order, validate the fill transaction
 pub fn validate_fill_sanitized_message(
     sanitized_message: &SanitizedMessage,
     order: Order,
+    policy: &FillValidationPolicy,
 ) -> Result<ValidatedFill> {
     let fee_payer = sanitized_message.fee_payer();
-    ensure!(
-        fee_payer == &order.maker,
-        "Fee payer was not the expected maker {fee_payer} but was {}",
-        order.maker,
-    );
-
-    ensure!(
-        sanitized_message
-            .get_signature_details()
-            .num_transaction_signatures()
-            >= 2,
-        "Too many signers"
-    );
+    if fee_payer != &order.maker {
+        return Err(FillValidationError::UnexpectedFeePayer {
+            expected: order.maker,
+            actual: *fee_payer,
+        });
+    }
+
+    let num_transaction_signatures = sanitized_message
+        .get_signature_details()
+        .num_transaction_signatures();
+    if num_transaction_signatures < 2 {
+        return Err(FillValidationError::NotEnoughSigners {
+            expected: 2,
+            actual: num_transaction_signatures,
+        });
+    }
 
     let second_signer = sanitized_message
         .account_keys()
         .get(1)
-        .context("Missing taker")?;
-    ensure!(
-        second_signer == &order.taker,
-        "Second transaction signer is not the taker"
-    );
-
-    let mut fill_ix_found = false;
-    let mut compute_unit_limit = None;
-    let mut compute_unit_price = None;
-
-    for BorrowedInstruction {
-        program_id,
-        accounts,
-        data,
-    } in sanitized_message.decompile_instructions()
+        .ok_or(FillValidationError::MissingSigner { index: 1 })?;
+    if second_signer != &order.taker {
+        return Err(FillValidationError::SignerMismatch {
+            index: 1,
+            expected: order.taker,
+            actual: *second_signer,
+        });
+    }
+
+    let instructions = sanitized_message.decompile_instructions();
+    policy.check_instruction_count(instructions.len())?;
+    let decoded_fill = decode_fill_sanitized_message(sanitized_message)?;
+
+    for (
+        index,
+        BorrowedInstruction {
+            program_id, data, ..
+        },
+    ) in instructions.into_iter().enumerate()
     {
-        if program_id == &compute_budget::ID {
-            // Compute budget should have been driven from the fee payer, certainly need to validate
-            let compute_budget_ix = try_from_slice_unchecked::<ComputeBudgetInstruction>(data)?;
-            match compute_budget_ix {
-                ComputeBudgetInstruction::SetComputeUnitLimit(limit) => {
-                    compute_unit_limit = Some(limit);
-                }
-                ComputeBudgetInstruction::SetComputeUnitPrice(price) => {
-                    ensure!(
-                        compute_unit_price.is_none(),
-                        "Compute unit price is already set"
-                    );
-                    compute_unit_price = Some(price);
-                }
-                _ => bail!("Unexpected compute budget instruction"),
-            }
-        } else if program_id == &associated_token::ID {
-            // For simplicity we only allow create ata idempotent
-            ensure!(
-                data == vec![1],
-                "Incorrect associated token account program data"
-            );
+        if program_id == &associated_token::ID {
+            policy.check_associated_token_account(index)?;
+        } else if program_id != &compute_budget::ID && program_id != &order_engine::ID {
+            policy.check_extra_program(index, program_id, data)?;
+        }
+    }
 
-            // We verify the taker is paying for the token account
-            ensure!(accounts.first().map(|am| am.pubkey) != Some(&order.maker));
-        } else if program_id == &order_engine::ID {
-            ensure!(!fill_ix_found, "Duplicated fill instruction");
-            fill_ix_found = true;
-
-            ensure!(data.len() >= 8, "Not enough data in fill instruction");
-            // Must slice off anchor's discriminator first
-            let (discriminator, mut ix_data) = data.split_at(8);
-            ensure!(
-                discriminator == order_engine::client::args::Fill::DISCRIMINATOR,
-                "Not a fill discriminator"
-            );
+    policy.check_lighthouse_assertions(&decoded_fill)?;
 
-            let pubkeys = accounts.into_iter().map(|a| *a.pubkey).collect::<Vec<_>>();
-            let [taker, maker, _taker_input_mint_token_account, _maker_input_mint_token_account, _taker_output_mint_token_account, _maker_output_mint_token_account, input_mint, _input_token_program, output_mint, _output_mint_token_program, ..] =
-                pubkeys.as_slice()
-            else {
-                bail!("Not enough accounts");
-            };
-
-            // Note: The validation isn't total as we don't validate native sol against native mint expectation
-            ensure!(taker == &order.taker, "Invalid taker");
-            ensure!(maker == &order.maker, "Invalid maker");
-            ensure!(input_mint == &order.input_mint, "Invalid input mint");
-            ensure!(output_mint == &order.output_mint, "Invalid output mint");
-
-            let fill_ix = order_engine::client::args::Fill::deserialize(&mut ix_data)
-                .map_err(|e| anyhow!("Invalid fill ix data {e}"))?;
-
-            // Check the input and output amount
-            if fill_ix.input_amount != order.in_amount || fill_ix.output_amount != order.out_amount
-            {
-                bail!("Invalid fill ix");
-            }
+    // For simplicity we only allow create ata idempotent
+    if let Some(&index) = decoded_fill
+        .other_associated_token_account_instructions
+        .first()
+    {
+        return Err(FillValidationError::InvalidAssociatedTokenAccountInstruction { index });
+    }
+    for create_associated_token_account in &decoded_fill.create_associated_token_accounts {
+        let index = create_associated_token_account.index;
+        if !create_associated_token_account.idempotent {
+            return Err(FillValidationError::InvalidAssociatedTokenAccountInstruction { index });
+        }
+
+        // We verify the taker is paying for the token account
+        if create_associated_token_account.payer == Some(order.maker) {
+            return Err(FillValidationError::AssociatedTokenAccountPaidByMaker { index });
+        }
+    }
+
+    // Note: The validation isn't total as we don't validate native sol against native mint expectation
+    let accounts = &decoded_fill.accounts;
+    ensure_account("taker", &order.taker, &accounts.taker)?;
+    ensure_account("maker", &order.maker, &accounts.maker)?;
+    ensure_account("input mint", &order.input_mint, &accounts.input_mint)?;
+    ensure_account("output mint", &order.output_mint, &accounts.output_mint)?;
+
+    // Check the input and output amount
+    if decoded_fill.input_amount != order.in_amount {
+        return Err(FillValidationError::InputAmountMismatch {
+            expected: order.in_amount,
+            actual: decoded_fill.input_amount,
+        });
+    }
+    if decoded_fill.output_amount != order.out_amount {
+        return Err(FillValidationError::OutputAmountMismatch {
+            expected: order.out_amount,
+            actual: decoded_fill.output_amount,
+        });
+    }
 
-            // Check the expiry
-            ensure!(fill_ix.expire_at == order.expire_at, "Incorrect expiry");
-        } else {
-            bail!("Unexpected program id {program_id}");
+    // Check the expiry
+    if decoded_fill.expire_at != order.expire_at {
+        return Err(FillValidationError::ExpiryMismatch {
+            expected: order.expire_at,
+            actual: decoded_fill.expire_at,
+        });
+    }
+    policy.check_expiry(decoded_fill.expire_at)?;
+    policy.check_trailer(&decoded_fill.trailer_data)?;
+
+    if let Some(expected_trailer) = &order.expected_trailer {
+        let fill_trailer = decoded_fill
+            .trailer
+            .as_ref()
+            .ok_or(FillValidationError::MissingTrailer)?;
+        if fill_trailer.fee_bps != expected_trailer.fee_bps {
+            return Err(FillValidationError::FeeBpsMismatch {
+                expected: expected_trailer.fee_bps,
+                actual: fill_trailer.fee_bps,
+            });
+        }
+        if fill_trailer.is_exact_out() != expected_trailer.is_exact_out() {
+            return Err(FillValidationError::DirectionMismatch {
+                expected_exact_out: expected_trailer.is_exact_out(),
+            });
         }
     }
 
-    ensure!(fill_ix_found, "Missing fill instruction");
+    let compute_unit_limit = decoded_fill
+        .compute_unit_limit
+        .ok_or(FillValidationError::MissingComputeUnitLimit)?;
+    let compute_unit_price = decoded_fill
+        .compute_unit_price
+        .ok_or(FillValidationError::MissingComputeUnitPrice)?;
+    policy.check_compute_unit_limit(compute_unit_limit)?;
+    policy.check_compute_unit_price(compute_unit_price)?;
+
     Ok(ValidatedFill {
-        compute_unit_limit: compute_unit_limit.context("Missing compute unit limit")?,
-        compute_unit_price: compute_unit_price.context("Missing compute unit price")?,
+        compute_unit_limit,
+        compute_unit_price,
+        trailer: decoded_fill.trailer,
     })
 }
 
@@ -155,44 +202,54 @@ pub struct ValidatedSimilarFill {
     pub output_mint: Pubkey,
     pub taker_input_mint_token_account: Pubkey,
     pub expire_at: i64,
+    pub trailer: Option<FillTrailer>,
 }
 
 /// Given the original sanitized message, allow some minor changes
 pub fn validate_similar_fill_sanitized_message(
     sanitized_message: SanitizedMessage,
     original_sanitized_message: SanitizedMessage,
+    policy: &FillValidationPolicy,
 ) -> Result<ValidatedSimilarFill> {
     let message_header = sanitized_message.header();
     let original_message_header = original_sanitized_message.header();
 
-    ensure!(
-        original_message_header.num_required_signatures == message_header.num_required_signatures,
-        "Number of required signatures did not match"
-    );
+    if original_message_header.num_required_signatures != message_header.num_required_signatures {
+        return Err(FillValidationError::RequiredSignaturesMismatch {
+            expected: original_message_header.num_required_signatures,
+            actual: message_header.num_required_signatures,
+        });
+    }
     let mut account_keys_iter = sanitized_message.account_keys().iter();
-    for original_signer in original_sanitized_message
+    for (index, original_signer) in original_sanitized_message
         .account_keys()
         .iter()
         .take(usize::from(original_message_header.num_required_signatures))
+        .enumerate()
     {
         let signer = account_keys_iter
             .next()
-            .context("Not enough account keys to validate signer")?;
-        ensure!(signer == original_signer, "Signer did not match");
+            .ok_or(FillValidationError::MissingSigner { index })?;
+        if signer != original_signer {
+            return Err(FillValidationError::SignerMismatch {
+                index,
+                expected: *original_signer,
+                actual: *signer,
+            });
+        }
     }
 
     let sanitized_instructions = sanitized_message.decompile_instructions();
     let original_instructions = original_sanitized_message.decompile_instructions();
 
     // Validate that we have at least the original number of instructions
-    ensure!(
-        sanitized_instructions.len() >= original_instructions.len(),
-        "Number of instructions cannot be less than original"
-    );
-
-    let mut validated_similar_fill = None;
-    let mut compute_unit_price = None;
-    let mut compute_unit_limit = None;
+    if sanitized_instructions.len() < original_instructions.len() {
+        return Err(FillValidationError::NotEnoughInstructions {
+            expected: original_instructions.len(),
+            actual: sanitized_instructions.len(),
+        });
+    }
+    policy.check_instruction_count(sanitized_instructions.len())?;
 
     // First check matching instructions between original and sanitized
     let mut sanitized_instructions_iter = sanitized_instructions.into_iter();
@@ -207,94 +264,54 @@ pub fn validate_similar_fill_sanitized_message(
         },
     ) in original_instructions.into_iter().enumerate()
     {
-        let BorrowedInstruction {
+        // Cannot fail, we checked there are at least as many instructions as in the original
+        let Some(BorrowedInstruction {
             program_id,
             accounts,
             data,
-        } = sanitized_instructions_iter
-            .next()
-            .context("Missing instruction")?;
-        ensure!(
-            program_id == original_program_id,
-            "Instruction program id did not match the original message at index {index}, {original_program_id}"
-        );
-        ensure!(
-            accounts.len() == original_accounts.len(),
-            "Instruction accounts length was not equal at index {index}, {original_program_id}"
-        );
-        ensure!(
-            accounts
+        }) = sanitized_instructions_iter.next()
+        else {
+            return Err(FillValidationError::NotEnoughInstructions {
+                expected: original_len,
+                actual: index,
+            });
+        };
+        if program_id != original_program_id {
+            return Err(FillValidationError::ProgramIdMismatch {
+                index,
+                expected: *original_program_id,
+                actual: *program_id,
+            });
+        }
+        if accounts.len() != original_accounts.len()
+            || !accounts
                 .iter()
                 .zip(original_accounts)
                 .all(|(accounts, original_accounts)| {
                     accounts.pubkey == original_accounts.pubkey
                         && accounts.is_signer == original_accounts.is_signer
                         && accounts.is_writable == original_accounts.is_writable
-                }),
-            "Instruction accounts did not match the original message {index}, {original_program_id}"
-        );
+                })
+        {
+            return Err(FillValidationError::InstructionAccountsMismatch {
+                index,
+                program_id: *original_program_id,
+            });
+        }
+        // Allow for compute unit price and limit to change, since some wallets change it
         if original_program_id == &compute_budget::ID {
-            // Allow for compute unit price and limit to change, since some wallets change it
-            let compute_budget_ix = try_from_slice_unchecked::<ComputeBudgetInstruction>(data)?;
-            match compute_budget_ix {
-                ComputeBudgetInstruction::SetComputeUnitLimit(limit) => {
-                    ensure!(
-                        compute_unit_limit.is_none(),
-                        "Compute unit limit is already set"
-                    );
-                    compute_unit_limit = Some(limit);
-                    continue;
-                }
-                ComputeBudgetInstruction::SetComputeUnitPrice(price) => {
-                    ensure!(
-                        compute_unit_price.is_none(),
-                        "Comput unit price is already set"
-                    );
-                    compute_unit_price = Some(price);
-                    continue;
-                }
-                _ => bail!("Unexpected compute budget instruction"),
-            }
+            continue;
         }
 
-        ensure!(
-            data == original_data,
-            "Instruction did not match the original at index {index}, {original_program_id}"
-        );
-
-        // If the program_id is order_engine then we give additional information to verify
-        if program_id == &order_engine::ID {
-            ensure!(
-                validated_similar_fill.is_none(),
-                "Duplicated fill instruction"
-            );
-            ensure!(data.len() >= 8, "Not enough data in fill instruction");
-            let (discriminator, mut ix_data) = data.split_at(8);
-            ensure!(
-                discriminator == order_engine::client::args::Fill::DISCRIMINATOR,
-                "Not a fill discriminator"
-            );
+        if program_id == &associated_token::ID {
+            policy.check_associated_token_account(index)?;
+        }
 
-            let fill_ix = order_engine::client::args::Fill::deserialize(&mut ix_data)
-                .map_err(|e| anyhow!("Invalid fill ix data {e}"))?;
-            // We check if the taker has enough balance to fill the order first
-            let taker = accounts.first().context("Invalid fill ix data")?.pubkey;
-            let input_mint = accounts.get(6).context("Invalid fill ix data")?.pubkey;
-            let output_mint = accounts.get(8).context("Invalid fill ix data")?.pubkey;
-
-            let taker_input_mint_token_account = accounts
-                .get(2)
-                .context("Invalid taker input mint token account ix data")?
-                .pubkey;
-
-            validated_similar_fill = Some(ValidatedSimilarFill {
-                taker: *taker,
-                input_amount: fill_ix.input_amount,
-                input_mint: *input_mint,
-                output_mint: *output_mint,
-                taker_input_mint_token_account: *taker_input_mint_token_account,
-                expire_at: fill_ix.expire_at,
-            })
+        if data != original_data {
+            return Err(FillValidationError::InstructionDataMismatch {
+                index,
+                program_id: *original_program_id,
+            });
         }
     }
 
@@ -308,21 +325,37 @@ pub fn validate_similar_fill_sanitized_message(
         },
     ) in sanitized_instructions_iter.enumerate()
     {
-        let real_index = index + original_len;
-        ensure!(
-            program_id == &LIGHTHOUSE_PROGRAM_ID,
-            "Additional instructions can only be from Lighthouse program at {real_index}"
-        );
-
-        ensure!(
-            data.first()
-                .map(|discriminator| ALLOWED_LIGHTHOUSE_DISCRIMINATORS.contains(discriminator))
-                .unwrap_or(false),
-            "Invalid Lighthouse instruction discriminator at index {real_index}"
-        );
+        policy.check_appended_program(index + original_len, program_id, data)?;
     }
 
-    validated_similar_fill.context("Missing validated fill instruction")
+    let decoded_fill = decode_fill_sanitized_message(&sanitized_message)?;
+    // Wallets may change the compute unit limit, not set it twice
+    if let Some(&index) = decoded_fill.repeated_compute_unit_limits.first() {
+        return Err(FillValidationError::DuplicateComputeUnitLimit { index });
+    }
+    if let Some(compute_unit_limit) = decoded_fill.compute_unit_limit {
+        policy.check_compute_unit_limit(compute_unit_limit)?;
+    }
+    if let Some(compute_unit_price) = decoded_fill.compute_unit_price {
+        policy.check_compute_unit_price(compute_unit_price)?;
+    }
+    policy.check_expiry(decoded_fill.expire_at)?;
+    policy.check_trailer(&decoded_fill.trailer_data)?;
+    policy.check_lighthouse_assertions(&decoded_fill)?;
+
+    Ok(ValidatedSimilarFill {
+        taker: decoded_fill.accounts.taker,
+        input_amount: decoded_fill.input_amount,
+        input_mint: decoded_fill.accounts.input_mint,
+        output_mint: decoded_fill.accounts.output_mint,
+        // a taker paying native SOL has the program id in place of the token account
+        taker_input_mint_token_account: decoded_fill
+            .accounts
+            .taker_input_mint_token_account
+            .unwrap_or(order_engine::ID),
+        expire_at: decoded_fill.expire_at,
+        trailer: decoded_fill.trailer,
+    })
 }
 
 #[cfg(test)]
@@ -330,8 +363,10 @@ mod tests {
     use std::collections::HashSet;
 
     use super::*;
+    use crate::policy::{AllowedProgram, LIGHTHOUSE_PROGRAM_ID};
     use anchor_lang::{prelude::*, InstructionData, ToAccountMetas};
     use solana_sdk::{
+        compute_budget::ComputeBudgetInstruction,
         hash::Hash,
         instruction::Instruction,
         message::{
@@ -365,14 +400,15 @@ mod tests {
         let input_amount = 100;
         let expire_at = 1000;
         let input_mint = Pubkey::new_unique();
-        let taker_input_mint_token_account = Some(Pubkey::new_unique());
+        let taker_input_mint_token_account = Pubkey::new_unique();
+        let trailer = FillTrailer::new(20, false);
 
         let fill_ix = Instruction {
             program_id: order_engine::ID,
             accounts: order_engine::client::accounts::Fill {
                 taker,
                 maker,
-                taker_input_mint_token_account,
+                taker_input_mint_token_account: Some(taker_input_mint_token_account),
                 maker_input_mint_token_account: Some(Pubkey::new_unique()),
                 taker_output_mint_token_account: Some(Pubkey::new_unique()),
                 maker_output_mint_token_account: Some(Pubkey::new_unique()),
@@ -383,12 +419,16 @@ mod tests {
                 system_program: system_program::ID,
             }
             .to_account_metas(None),
-            data: order_engine::client::args::Fill {
-                input_amount,
-                output_amount: 200,
-                expire_at,
-            }
-            .data(),
+            data: [
+                order_engine::client::args::Fill {
+                    input_amount,
+                    output_amount: 200,
+                    expire_at,
+                }
+                .data(),
+                trailer.encode().to_vec(),
+            ]
+            .concat(),
         };
 
         let original_sanitized_message =
@@ -399,9 +439,9 @@ mod tests {
             input_amount,
             input_mint,
             output_mint: fill_ix.accounts[8].pubkey,
-            taker_input_mint_token_account: taker_input_mint_token_account
-                .unwrap_or(order_engine::ID),
+            taker_input_mint_token_account,
             expire_at,
+            trailer: Some(trailer),
         };
 
         // Identical message
@@ -409,7 +449,8 @@ mod tests {
             expected_validated_similar_fill,
             validate_similar_fill_sanitized_message(
                 original_sanitized_message.clone(),
-                original_sanitized_message.clone()
+                original_sanitized_message.clone(),
+                &FillValidationPolicy::default()
             )
             .unwrap()
         );
@@ -421,7 +462,8 @@ mod tests {
             expected_validated_similar_fill,
             validate_similar_fill_sanitized_message(
                 sanitized_message,
-                original_sanitized_message.clone()
+                original_sanitized_message.clone(),
+                &FillValidationPolicy::default()
             )
             .unwrap()
         );
@@ -447,13 +489,16 @@ mod tests {
             Hash::new_unique(),
         );
         assert_eq!(
-            "Number of required signatures did not match",
+            FillValidationError::RequiredSignaturesMismatch {
+                expected: 2,
+                actual: 1
+            },
             validate_similar_fill_sanitized_message(
                 sanitized_message,
-                original_sanitized_message.clone()
+                original_sanitized_message.clone(),
+                &FillValidationPolicy::default()
             )
             .unwrap_err()
-            .to_string()
         );
 
         // Change accounts
@@ -462,13 +507,16 @@ mod tests {
         let sanitized_message =
             make_sanitized_transaction(&maker, &[modified_fill_ix], recent_blockhash);
         assert_eq!(
-            "Instruction accounts did not match the original message 0, 61DFfeTKM7trxYcPQCM78bJ794ddZprZpAwAnLiwTpYH",
+            FillValidationError::InstructionAccountsMismatch {
+                index: 0,
+                program_id: order_engine::ID
+            },
             validate_similar_fill_sanitized_message(
                 sanitized_message,
-                original_sanitized_message.clone()
+                original_sanitized_message.clone(),
+                &FillValidationPolicy::default()
             )
             .unwrap_err()
-            .to_string()
         );
 
         // Change data
@@ -477,13 +525,16 @@ mod tests {
         let sanitized_message =
             make_sanitized_transaction(&maker, &[modified_fill_ix], recent_blockhash);
         assert_eq!(
-            "Instruction did not match the original at index 0, 61DFfeTKM7trxYcPQCM78bJ794ddZprZpAwAnLiwTpYH",
+            FillValidationError::InstructionDataMismatch {
+                index: 0,
+                program_id: order_engine::ID
+            },
             validate_similar_fill_sanitized_message(
                 sanitized_message,
-                original_sanitized_message.clone()
+                original_sanitized_message.clone(),
+                &FillValidationPolicy::default()
             )
             .unwrap_err()
-            .to_string()
         );
 
         // Add lighthouse instruction
@@ -501,7 +552,8 @@ mod tests {
             expected_validated_similar_fill,
             validate_similar_fill_sanitized_message(
                 sanitized_message,
-                original_sanitized_message.clone()
+                original_sanitized_message.clone(),
+                &FillValidationPolicy::default()
             )
             .unwrap()
         );
@@ -515,13 +567,393 @@ mod tests {
             recent_blockhash,
         );
         assert_eq!(
-            "Invalid Lighthouse instruction discriminator at index 1",
+            FillValidationError::ForbiddenInstruction {
+                index: 1,
+                program_id: LIGHTHOUSE_PROGRAM_ID,
+                discriminator: Some(1)
+            },
             validate_similar_fill_sanitized_message(
                 sanitized_message,
-                original_sanitized_message.clone()
+                original_sanitized_message.clone(),
+                &FillValidationPolicy::default()
+            )
+            .unwrap_err()
+        );
+    }
+
+    fn make_order_and_fill_ix(trailer: Option<FillTrailer>) -> (Order, Instruction) {
+        let order = Order {
+            taker: Pubkey::new_unique(),
+            maker: Pubkey::new_unique(),
+            in_amount: 100,
+            input_mint: Pubkey::new_unique(),
+            out_amount: 200,
+            output_mint: Pubkey::new_unique(),
+            expire_at: 1000,
+            expected_trailer: None,
+        };
+        let mut data = order_engine::client::args::Fill {
+            input_amount: order.in_amount,
+            output_amount: order.out_amount,
+            expire_at: order.expire_at,
+        }
+        .data();
+        if let Some(trailer) = trailer {
+            data.extend(trailer.encode());
+        }
+        let fill_ix = Instruction {
+            program_id: order_engine::ID,
+            accounts: order_engine::client::accounts::Fill {
+                taker: order.taker,
+                maker: order.maker,
+                taker_input_mint_token_account: Some(Pubkey::new_unique()),
+                maker_input_mint_token_account: Some(Pubkey::new_unique()),
+                taker_output_mint_token_account: Some(Pubkey::new_unique()),
+                maker_output_mint_token_account: Some(Pubkey::new_unique()),
+                input_mint: order.input_mint,
+                input_token_program: Pubkey::new_unique(),
+                output_mint: order.output_mint,
+                output_token_program: Pubkey::new_unique(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data,
+        };
+        (order, fill_ix)
+    }
+
+    #[test]
+    fn test_validate_fill_sanitized_message_trailer() {
+        let trailer = FillTrailer::new(20, true);
+        let (order, fill_ix) = make_order_and_fill_ix(Some(trailer));
+        let instructions = [
+            ComputeBudgetInstruction::set_compute_unit_limit(100_000),
+            ComputeBudgetInstruction::set_compute_unit_price(1_000),
+            fill_ix,
+        ];
+        let sanitized_message =
+            make_sanitized_transaction(&order.maker, &instructions, Hash::new_unique());
+
+        // The trailer is reported without being checked
+        let validated_fill = validate_fill_sanitized_message(
+            &sanitized_message,
+            order,
+            &FillValidationPolicy::default(),
+        )
+        .unwrap();
+        assert_eq!(validated_fill.trailer, Some(trailer));
+
+        // Matching expectations
+        let (mut order, fill_ix) = make_order_and_fill_ix(Some(trailer));
+        order.expected_trailer = Some(trailer);
+        let sanitized_message = make_sanitized_transaction(
+            &order.maker,
+            &[instructions[0].clone(), instructions[1].clone(), fill_ix],
+            Hash::new_unique(),
+        );
+        assert!(validate_fill_sanitized_message(
+            &sanitized_message,
+            order,
+            &FillValidationPolicy::default()
+        )
+        .is_ok());
+
+        // Fee and direction mismatches
+        for (expected_trailer, expected_error) in [
+            (
+                FillTrailer::new(2, true),
+                FillValidationError::FeeBpsMismatch {
+                    expected: 2,
+                    actual: 20,
+                },
+            ),
+            (
+                FillTrailer::new(20, false),
+                FillValidationError::DirectionMismatch {
+                    expected_exact_out: false,
+                },
+            ),
+        ] {
+            let (mut order, fill_ix) = make_order_and_fill_ix(Some(trailer));
+            order.expected_trailer = Some(expected_trailer);
+            let sanitized_message = make_sanitized_transaction(
+                &order.maker,
+                &[instructions[0].clone(), instructions[1].clone(), fill_ix],
+                Hash::new_unique(),
+            );
+            assert_eq!(
+                expected_error,
+                validate_fill_sanitized_message(
+                    &sanitized_message,
+                    order,
+                    &FillValidationPolicy::default()
+                )
+                .unwrap_err()
+            );
+        }
+
+        // Missing trailer
+        let (mut order, fill_ix) = make_order_and_fill_ix(None);
+        order.expected_trailer = Some(trailer);
+        let sanitized_message = make_sanitized_transaction(
+            &order.maker,
+            &[instructions[0].clone(), instructions[1].clone(), fill_ix],
+            Hash::new_unique(),
+        );
+        assert_eq!(
+            FillValidationError::MissingTrailer,
+            validate_fill_sanitized_message(
+                &sanitized_message,
+                order,
+                &FillValidationPolicy::default()
+            )
+            .unwrap_err()
+        );
+
+        // A trailer extended by a later version of the RFQ system is only rejected when strict
+        let (mut order, mut fill_ix) = make_order_and_fill_ix(Some(trailer));
+        fill_ix.data.push(7);
+        order.expected_trailer = Some(trailer);
+        let sanitized_message = make_sanitized_transaction(
+            &order.maker,
+            &[instructions[0].clone(), instructions[1].clone(), fill_ix],
+            Hash::new_unique(),
+        );
+        let validated_fill = validate_fill_sanitized_message(
+            &sanitized_message,
+            order.clone(),
+            &FillValidationPolicy::default(),
+        )
+        .unwrap();
+        assert_eq!(validated_fill.trailer, Some(trailer));
+        assert_eq!(
+            FillValidationError::InvalidTrailerLength { len: 4 },
+            validate_fill_sanitized_message(
+                &sanitized_message,
+                order,
+                &FillValidationPolicy {
+                    strict_trailer: true,
+                    ..Default::default()
+                }
+            )
+            .unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_validate_fill_sanitized_message_policy() {
+        let (order, fill_ix) = make_order_and_fill_ix(None);
+        let lighthouse_ix = Instruction {
+            program_id: LIGHTHOUSE_PROGRAM_ID,
+            accounts: vec![AccountMeta::new_readonly(order.input_mint, false)],
+            data: vec![5],
+        };
+        let sanitized_message = make_sanitized_transaction(
+            &order.maker,
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(100_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1_000),
+                fill_ix,
+                lighthouse_ix,
+            ],
+            Hash::new_unique(),
+        );
+
+        // Lighthouse is only tolerated when appended to a known message by default
+        assert_eq!(
+            FillValidationError::UnexpectedProgramId {
+                index: 3,
+                program_id: LIGHTHOUSE_PROGRAM_ID
+            },
+            validate_fill_sanitized_message(
+                &sanitized_message,
+                order.clone(),
+                &FillValidationPolicy::default()
+            )
+            .unwrap_err()
+        );
+
+        let policy = FillValidationPolicy {
+            extra_programs: vec![AllowedProgram::lighthouse()],
+            ..Default::default()
+        };
+        assert!(
+            validate_fill_sanitized_message(&sanitized_message, order.clone(), &policy).is_ok()
+        );
+
+        for (policy, expected_error) in [
+            (
+                FillValidationPolicy {
+                    extra_programs: vec![AllowedProgram::lighthouse()],
+                    max_compute_unit_price: 999,
+                    ..Default::default()
+                },
+                FillValidationError::ComputeUnitPriceOutOfBounds {
+                    price: 1_000,
+                    min: 0,
+                    max: 999,
+                },
+            ),
+            (
+                FillValidationPolicy {
+                    extra_programs: vec![AllowedProgram::lighthouse()],
+                    min_compute_unit_limit: 200_000,
+                    ..Default::default()
+                },
+                FillValidationError::ComputeUnitLimitOutOfBounds {
+                    limit: 100_000,
+                    min: 200_000,
+                    max: u32::MAX,
+                },
+            ),
+            (
+                FillValidationPolicy {
+                    extra_programs: vec![AllowedProgram::lighthouse()],
+                    max_instructions: 3,
+                    ..Default::default()
+                },
+                FillValidationError::TooManyInstructions { max: 3, actual: 4 },
+            ),
+        ] {
+            assert_eq!(
+                expected_error,
+                validate_fill_sanitized_message(&sanitized_message, order.clone(), &policy)
+                    .unwrap_err()
+            );
+        }
+
+        // The Lighthouse instruction carries no assertion
+        let policy = FillValidationPolicy {
+            extra_programs: vec![AllowedProgram::lighthouse()],
+            check_lighthouse_assertions: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            FillValidationError::InvalidLighthouseInstruction { index: 3 },
+            validate_fill_sanitized_message(&sanitized_message, order.clone(), &policy)
+                .unwrap_err()
+        );
+
+        // The order expired long ago
+        let policy = FillValidationPolicy {
+            extra_programs: vec![AllowedProgram::lighthouse()],
+            min_seconds_to_expiry: Some(1),
+            ..Default::default()
+        };
+        assert!(matches!(
+            validate_fill_sanitized_message(&sanitized_message, order, &policy),
+            Err(FillValidationError::ExpiresTooSoon {
+                expire_at: 1000,
+                min_seconds_to_expiry: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_default_policy_accepts_baseline_messages() {
+        // A repeated compute unit limit overwrites the first one
+        let (order, fill_ix) = make_order_and_fill_ix(None);
+        let sanitized_message = make_sanitized_transaction(
+            &order.maker,
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(100_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1_000),
+                ComputeBudgetInstruction::set_compute_unit_limit(200_000),
+                fill_ix.clone(),
+            ],
+            Hash::new_unique(),
+        );
+        let validated_fill = validate_fill_sanitized_message(
+            &sanitized_message,
+            order.clone(),
+            &FillValidationPolicy::default(),
+        )
+        .unwrap();
+        assert_eq!(validated_fill.compute_unit_limit, 200_000);
+
+        // but is rejected in a message compared to the original
+        assert_eq!(
+            FillValidationError::DuplicateComputeUnitLimit { index: 2 },
+            validate_similar_fill_sanitized_message(
+                sanitized_message.clone(),
+                sanitized_message,
+                &FillValidationPolicy::default()
+            )
+            .unwrap_err()
+        );
+
+        // Associated token account instructions only have to match the original
+        let ata_ixs = [vec![], vec![0], vec![2], vec![3, 4]].map(|data| Instruction {
+            program_id: associated_token::ID,
+            accounts: vec![AccountMeta::new(order.taker, true)],
+            data,
+        });
+        let original_sanitized_message = make_sanitized_transaction(
+            &order.maker,
+            &[ata_ixs.as_slice(), &[fill_ix.clone()]].concat(),
+            Hash::new_unique(),
+        );
+        assert!(validate_similar_fill_sanitized_message(
+            original_sanitized_message.clone(),
+            original_sanitized_message,
+            &FillValidationPolicy::default()
+        )
+        .is_ok());
+
+        // while the fill validation only allows creating them idempotently
+        for ata_ix in ata_ixs {
+            let sanitized_message = make_sanitized_transaction(
+                &order.maker,
+                &[
+                    ComputeBudgetInstruction::set_compute_unit_limit(100_000),
+                    ComputeBudgetInstruction::set_compute_unit_price(1_000),
+                    ata_ix,
+                    fill_ix.clone(),
+                ],
+                Hash::new_unique(),
+            );
+            assert_eq!(
+                FillValidationError::InvalidAssociatedTokenAccountInstruction { index: 2 },
+                validate_fill_sanitized_message(
+                    &sanitized_message,
+                    order.clone(),
+                    &FillValidationPolicy::default()
+                )
+                .unwrap_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_similar_fill_sanitized_message_policy() {
+        let (order, fill_ix) = make_order_and_fill_ix(None);
+        let ata_ix = Instruction {
+            program_id: associated_token::ID,
+            accounts: vec![AccountMeta::new(order.taker, true)],
+            data: vec![1],
+        };
+        let original_sanitized_message =
+            make_sanitized_transaction(&order.maker, &[ata_ix, fill_ix], Hash::new_unique());
+        assert!(validate_similar_fill_sanitized_message(
+            original_sanitized_message.clone(),
+            original_sanitized_message.clone(),
+            &FillValidationPolicy::default()
+        )
+        .is_ok());
+
+        let policy = FillValidationPolicy {
+            allow_create_associated_token_account: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            FillValidationError::AssociatedTokenAccountNotAllowed { index: 0 },
+            validate_similar_fill_sanitized_message(
+                original_sanitized_message.clone(),
+                original_sanitized_message,
+                &policy
             )
             .unwrap_err()
-            .to_string()
         );
     }
 }
@@ -2,5 +2,16 @@ use anchor_lang::prelude::*;
 
 declare_program!(order_engine);
 
+pub mod builder;
+pub mod decode;
+pub mod economics;
+pub mod error;
 pub mod fill;
+pub mod lighthouse;
+pub mod policy;
+pub mod sign;
+#[cfg(feature = "simulation")]
+pub mod simulation;
+pub mod solvency;
+pub mod trailer;
 pub mod transaction;
@@ -0,0 +1,559 @@
+//! Lighthouse assertions the wallet of the taker appends to a fill
+//!
+//! Only the instructions allowed by [`ALLOWED_LIGHTHOUSE_DISCRIMINATORS`] are decoded, they
+//! assert on the account info or on the token account state of a single target account:
+//!
+//! | Discriminator | Instruction                  |
+//! |---------------|------------------------------|
+//! | 5             | `AssertAccountInfo`          |
+//! | 6             | `AssertAccountInfoMulti`     |
+//! | 9             | `AssertTokenAccount`         |
+//! | 10            | `AssertTokenAccountMulti`    |
+//!
+//! The layouts follow the Lighthouse IDL: the discriminator, a log level, then one borsh encoded
+//! assertion, or a u8 count of assertions for the multi variants.
+//!
+//! [`ALLOWED_LIGHTHOUSE_DISCRIMINATORS`]: crate::policy::ALLOWED_LIGHTHOUSE_DISCRIMINATORS
+use anchor_lang::prelude::*;
+
+use crate::{
+    builder::temporary_wsol_token_account,
+    decode::{DecodedFill, LighthouseInstruction},
+    error::{FillValidationError, Result},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum LogLevel {
+    Silent,
+    PlaintextMessage,
+    EncodedMessage,
+    EncodedNoop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum IntegerOperator {
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    GreaterThanOrEqual,
+    LessThanOrEqual,
+    /// All the bits of the value are set
+    Contains,
+    /// None of the bits of the value are set
+    DoesNotContain,
+}
+
+impl IntegerOperator {
+    /// No integer up to `max` compares to `value` this way
+    fn is_unsatisfiable(&self, value: u64, max: u64) -> bool {
+        match self {
+            Self::GreaterThan => value >= max,
+            Self::LessThan => value == 0,
+            Self::Equal | Self::GreaterThanOrEqual | Self::Contains => value > max,
+            Self::NotEqual | Self::LessThanOrEqual | Self::DoesNotContain => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum EquatableOperator {
+    Equal,
+    NotEqual,
+}
+
+impl EquatableOperator {
+    /// Whether `asserted` compares to the `actual` value this way
+    fn holds<T: PartialEq>(&self, asserted: &T, actual: &T) -> bool {
+        match self {
+            Self::Equal => asserted == actual,
+            Self::NotEqual => asserted != actual,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum KnownProgram {
+    System,
+    Token,
+    Token2022,
+    Rent,
+    Stake,
+    Vote,
+    BpfLoader,
+    UpgradeableLoader,
+    SysvarConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum AccountInfoAssertion {
+    Lamports {
+        value: u64,
+        operator: IntegerOperator,
+    },
+    DataLength {
+        value: u64,
+        operator: IntegerOperator,
+    },
+    Owner {
+        value: Pubkey,
+        operator: EquatableOperator,
+    },
+    KnownOwner {
+        value: KnownProgram,
+        operator: EquatableOperator,
+    },
+    RentEpoch {
+        value: u64,
+        operator: IntegerOperator,
+    },
+    IsSigner {
+        value: bool,
+        operator: EquatableOperator,
+    },
+    IsWritable {
+        value: bool,
+        operator: EquatableOperator,
+    },
+    Executable {
+        value: bool,
+        operator: EquatableOperator,
+    },
+    VerifyDatahash {
+        expected_hash: [u8; 32],
+        start: Option<u16>,
+        length: Option<u16>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum TokenAccountAssertion {
+    Mint {
+        value: Pubkey,
+        operator: EquatableOperator,
+    },
+    Owner {
+        value: Pubkey,
+        operator: EquatableOperator,
+    },
+    Amount {
+        value: u64,
+        operator: IntegerOperator,
+    },
+    Delegate {
+        value: Option<Pubkey>,
+        operator: EquatableOperator,
+    },
+    State {
+        value: u8,
+        operator: IntegerOperator,
+    },
+    IsNative {
+        value: Option<u64>,
+        operator: EquatableOperator,
+    },
+    DelegatedAmount {
+        value: u64,
+        operator: IntegerOperator,
+    },
+    CloseAuthority {
+        value: Option<Pubkey>,
+        operator: EquatableOperator,
+    },
+    TokenAccountOwnerIsDerived,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LighthouseAssertions {
+    AccountInfo(Vec<AccountInfoAssertion>),
+    TokenAccount(Vec<TokenAccountAssertion>),
+}
+
+/// A decoded Lighthouse assertion instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LighthouseAssertion {
+    /// Position of the instruction in the message
+    pub index: usize,
+    pub log_level: LogLevel,
+    pub target_account: Pubkey,
+    pub assertions: LighthouseAssertions,
+}
+
+/// Deserialize a u8 count of assertions followed by the assertions
+fn deserialize_multi<T: AnchorDeserialize>(data: &mut &[u8]) -> std::io::Result<Vec<T>> {
+    let count = u8::deserialize(data)?;
+    (0..count).map(|_| T::deserialize(data)).collect()
+}
+
+impl LighthouseAssertion {
+    /// Decode one of the allowed Lighthouse instructions, anything else is invalid
+    pub fn decode(instruction: &LighthouseInstruction) -> Result<Self> {
+        let index = instruction.index;
+        let invalid = || FillValidationError::InvalidLighthouseInstruction { index };
+        let (discriminator, mut data) = instruction.data.split_first().ok_or_else(invalid)?;
+        let target_account = *instruction.accounts.first().ok_or_else(invalid)?;
+
+        let log_level = LogLevel::deserialize(&mut data).map_err(|_| invalid())?;
+        let assertions = match discriminator {
+            5 => AccountInfoAssertion::deserialize(&mut data)
+                .map(|assertion| LighthouseAssertions::AccountInfo(vec![assertion])),
+            6 => deserialize_multi(&mut data).map(LighthouseAssertions::AccountInfo),
+            9 => TokenAccountAssertion::deserialize(&mut data)
+                .map(|assertion| LighthouseAssertions::TokenAccount(vec![assertion])),
+            10 => deserialize_multi(&mut data).map(LighthouseAssertions::TokenAccount),
+            _ => return Err(invalid()),
+        }
+        .map_err(|_| invalid())?;
+        if !data.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            index,
+            log_level,
+            target_account,
+            assertions,
+        })
+    }
+
+    /// Reject assertions on the accounts of the maker, and assertions the fill can never satisfy
+    ///
+    /// The maker pays the fees of a transaction that fails on an assertion, only the taker should
+    /// be able to protect itself this way. Unsatisfiable assertions are the obvious ones: an
+    /// integer compared out of the range of its type, or a token account of the taker asserted
+    /// to have another mint, owner or token program than the fill uses.
+    pub fn check(&self, decoded_fill: &DecodedFill) -> Result<()> {
+        let accounts = &decoded_fill.accounts;
+        let maker_accounts = [
+            Some(accounts.maker),
+            accounts.maker_input_mint_token_account,
+            accounts.maker_output_mint_token_account,
+            Some(temporary_wsol_token_account(&accounts.maker)),
+        ];
+        if maker_accounts.contains(&Some(self.target_account)) {
+            return Err(FillValidationError::LighthouseAssertionOnMakerAccount {
+                index: self.index,
+                account: self.target_account,
+            });
+        }
+
+        // Mint and token program of the taker token account targeted, if any
+        let taker_token_account =
+            if accounts.taker_input_mint_token_account == Some(self.target_account) {
+                Some((accounts.input_mint, accounts.input_token_program))
+            } else if accounts.taker_output_mint_token_account == Some(self.target_account) {
+                Some((accounts.output_mint, accounts.output_token_program))
+            } else {
+                None
+            };
+
+        let unsatisfiable = match &self.assertions {
+            LighthouseAssertions::AccountInfo(assertions) => {
+                assertions.iter().any(|assertion| match assertion {
+                    AccountInfoAssertion::Lamports { value, operator }
+                    | AccountInfoAssertion::DataLength { value, operator }
+                    | AccountInfoAssertion::RentEpoch { value, operator } => {
+                        operator.is_unsatisfiable(*value, u64::MAX)
+                    }
+                    AccountInfoAssertion::Owner { value, operator } => taker_token_account
+                        .is_some_and(|(_, token_program)| !operator.holds(value, &token_program)),
+                    _ => false,
+                })
+            }
+            LighthouseAssertions::TokenAccount(assertions) => {
+                assertions.iter().any(|assertion| match assertion {
+                    TokenAccountAssertion::Amount { value, operator }
+                    | TokenAccountAssertion::DelegatedAmount { value, operator } => {
+                        operator.is_unsatisfiable(*value, u64::MAX)
+                    }
+                    TokenAccountAssertion::State { value, operator } => {
+                        operator.is_unsatisfiable(u64::from(*value), u64::from(u8::MAX))
+                    }
+                    TokenAccountAssertion::Mint { value, operator } => {
+                        taker_token_account.is_some_and(|(mint, _)| !operator.holds(value, &mint))
+                    }
+                    TokenAccountAssertion::Owner { value, operator } => {
+                        taker_token_account.is_some_and(|_| !operator.holds(value, &accounts.taker))
+                    }
+                    _ => false,
+                })
+            }
+        };
+        if unsatisfiable {
+            return Err(FillValidationError::UnsatisfiableLighthouseAssertion {
+                index: self.index,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::{AccountKind, FillTransactionBuilder},
+        decode::decode_fill_sanitized_message,
+        fill::Order,
+        transaction::{versioned_message_to_sanitized_message, NoAddressLookupTables},
+    };
+
+    fn instruction(
+        discriminator: u8,
+        target_account: Pubkey,
+        assertion: &[u8],
+    ) -> LighthouseInstruction {
+        LighthouseInstruction {
+            index: 3,
+            accounts: vec![target_account],
+            data: [&[discriminator, 0], assertion].concat(),
+        }
+    }
+
+    #[test]
+    fn test_decode_lighthouse_assertion() {
+        let target_account = Pubkey::new_unique();
+        let lamports = AccountInfoAssertion::Lamports {
+            value: 5_000_000,
+            operator: IntegerOperator::GreaterThan,
+        };
+        assert_eq!(
+            LighthouseAssertion::decode(&instruction(
+                5,
+                target_account,
+                &lamports.try_to_vec().unwrap()
+            )),
+            Ok(LighthouseAssertion {
+                index: 3,
+                log_level: LogLevel::Silent,
+                target_account,
+                assertions: LighthouseAssertions::AccountInfo(vec![lamports.clone()]),
+            })
+        );
+
+        let token_assertions = vec![
+            TokenAccountAssertion::Amount {
+                value: 1,
+                operator: IntegerOperator::GreaterThanOrEqual,
+            },
+            TokenAccountAssertion::Delegate {
+                value: None,
+                operator: EquatableOperator::Equal,
+            },
+        ];
+        let data = [
+            vec![2],
+            token_assertions[0].try_to_vec().unwrap(),
+            token_assertions[1].try_to_vec().unwrap(),
+        ]
+        .concat();
+        assert_eq!(
+            LighthouseAssertion::decode(&instruction(10, target_account, &data))
+                .unwrap()
+                .assertions,
+            LighthouseAssertions::TokenAccount(token_assertions)
+        );
+
+        // Memory writes, truncated or trailing data
+        let invalid = Err(FillValidationError::InvalidLighthouseInstruction { index: 3 });
+        assert_eq!(
+            LighthouseAssertion::decode(&instruction(0, target_account, &[])),
+            invalid
+        );
+        assert_eq!(
+            LighthouseAssertion::decode(&instruction(9, target_account, &[2, 1])),
+            invalid
+        );
+        assert_eq!(
+            LighthouseAssertion::decode(&instruction(
+                5,
+                target_account,
+                &[lamports.try_to_vec().unwrap(), vec![0]].concat()
+            )),
+            invalid
+        );
+        assert!(FillValidationError::InvalidLighthouseInstruction { index: 3 }.is_malformed());
+    }
+
+    /// Instruction data as encoded by the Lighthouse generated client, `lighthouse-sdk` 2.x: a u8
+    /// discriminator, the log level, then the borsh assertion or a u8 count of assertions
+    #[test]
+    fn test_decode_lighthouse_fixtures() {
+        let target_account = solana_sdk::pubkey!("5v2Vd71VoJ1wZhz1PkhTY48mrJwS6wF4LfvDbYPnJ3bc");
+        let mint = solana_sdk::pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+        let decode = |data: Vec<u8>| {
+            LighthouseAssertion::decode(&LighthouseInstruction {
+                index: 3,
+                accounts: vec![target_account],
+                data,
+            })
+            .unwrap()
+        };
+
+        // getAssertAccountInfoInstruction of tests/suites/integration/swap.ts: lamports greater
+        // than 5_000_000 with the default silent log level
+        let assertion = decode(vec![5, 0, 0, 0x40, 0x4b, 0x4c, 0, 0, 0, 0, 0, 2]);
+        assert_eq!(assertion.target_account, target_account);
+        assert_eq!(assertion.log_level, LogLevel::Silent);
+        assert_eq!(
+            assertion.assertions,
+            LighthouseAssertions::AccountInfo(vec![AccountInfoAssertion::Lamports {
+                value: 5_000_000,
+                operator: IntegerOperator::GreaterThan,
+            }])
+        );
+
+        // AssertAccountInfoMulti logging a plaintext message: lamports at least 1_000_000 and
+        // owned by the system program
+        let assertion = decode(vec![
+            6, 1, 2, 0, 0x40, 0x42, 0x0f, 0, 0, 0, 0, 0, 4, 3, 0, 0,
+        ]);
+        assert_eq!(assertion.log_level, LogLevel::PlaintextMessage);
+        assert_eq!(
+            assertion.assertions,
+            LighthouseAssertions::AccountInfo(vec![
+                AccountInfoAssertion::Lamports {
+                    value: 1_000_000,
+                    operator: IntegerOperator::GreaterThanOrEqual,
+                },
+                AccountInfoAssertion::KnownOwner {
+                    value: KnownProgram::System,
+                    operator: EquatableOperator::Equal,
+                },
+            ])
+        );
+
+        // AssertTokenAccount: amount at least 5_000_000
+        let assertion = decode(vec![9, 0, 2, 0x40, 0x4b, 0x4c, 0, 0, 0, 0, 0, 4]);
+        assert_eq!(
+            assertion.assertions,
+            LighthouseAssertions::TokenAccount(vec![TokenAccountAssertion::Amount {
+                value: 5_000_000,
+                operator: IntegerOperator::GreaterThanOrEqual,
+            }])
+        );
+
+        // AssertTokenAccountMulti: mint and owner equal, owner derived
+        let assertion = decode(
+            [
+                &[10, 0, 3, 0][..],
+                mint.as_ref(),
+                &[0, 1],
+                target_account.as_ref(),
+                &[0, 8],
+            ]
+            .concat(),
+        );
+        assert_eq!(
+            assertion.assertions,
+            LighthouseAssertions::TokenAccount(vec![
+                TokenAccountAssertion::Mint {
+                    value: mint,
+                    operator: EquatableOperator::Equal,
+                },
+                TokenAccountAssertion::Owner {
+                    value: target_account,
+                    operator: EquatableOperator::Equal,
+                },
+                TokenAccountAssertion::TokenAccountOwnerIsDerived,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_check_lighthouse_assertion() {
+        let order = Order {
+            taker: Pubkey::new_unique(),
+            maker: Pubkey::new_unique(),
+            in_amount: 1_000_000,
+            input_mint: Pubkey::new_unique(),
+            out_amount: 5_000_000,
+            output_mint: Pubkey::new_unique(),
+            expire_at: i64::MAX,
+            expected_trailer: None,
+        };
+        let decoded_fill = decode_fill_sanitized_message(
+            &versioned_message_to_sanitized_message(
+                FillTransactionBuilder::new(order.clone())
+                    .build_message()
+                    .unwrap(),
+                &NoAddressLookupTables,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let taker_output_mint_token_account = AccountKind::Token
+            .token_account(&order.taker, &order.output_mint)
+            .unwrap();
+        let check = |target_account: Pubkey, assertions: LighthouseAssertions| {
+            LighthouseAssertion {
+                index: 3,
+                log_level: LogLevel::Silent,
+                target_account,
+                assertions,
+            }
+            .check(&decoded_fill)
+        };
+
+        // The taker protecting what it receives
+        assert_eq!(
+            check(
+                taker_output_mint_token_account,
+                LighthouseAssertions::TokenAccount(vec![
+                    TokenAccountAssertion::Amount {
+                        value: 5_000_000,
+                        operator: IntegerOperator::GreaterThanOrEqual,
+                    },
+                    TokenAccountAssertion::Mint {
+                        value: order.output_mint,
+                        operator: EquatableOperator::Equal,
+                    },
+                ])
+            ),
+            Ok(())
+        );
+
+        let lamports = LighthouseAssertions::AccountInfo(vec![AccountInfoAssertion::Lamports {
+            value: 5_000_000,
+            operator: IntegerOperator::GreaterThan,
+        }]);
+        assert_eq!(check(order.taker, lamports.clone()), Ok(()));
+        let maker_output_mint_token_account = AccountKind::Token
+            .token_account(&order.maker, &order.output_mint)
+            .unwrap();
+        for maker_account in [order.maker, maker_output_mint_token_account] {
+            assert_eq!(
+                check(maker_account, lamports.clone()),
+                Err(FillValidationError::LighthouseAssertionOnMakerAccount {
+                    index: 3,
+                    account: maker_account,
+                })
+            );
+        }
+
+        let unsatisfiable = Err(FillValidationError::UnsatisfiableLighthouseAssertion { index: 3 });
+        for assertions in [
+            LighthouseAssertions::TokenAccount(vec![TokenAccountAssertion::Mint {
+                value: order.input_mint,
+                operator: EquatableOperator::Equal,
+            }]),
+            LighthouseAssertions::TokenAccount(vec![TokenAccountAssertion::Owner {
+                value: order.taker,
+                operator: EquatableOperator::NotEqual,
+            }]),
+            LighthouseAssertions::TokenAccount(vec![TokenAccountAssertion::Amount {
+                value: 0,
+                operator: IntegerOperator::LessThan,
+            }]),
+            LighthouseAssertions::AccountInfo(vec![AccountInfoAssertion::Owner {
+                value: AccountKind::Token2022.token_program(),
+                operator: EquatableOperator::Equal,
+            }]),
+        ] {
+            assert_eq!(
+                check(taker_output_mint_token_account, assertions),
+                unsatisfiable
+            );
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anchor_lang::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    decode::DecodedFill,
+    error::{FillValidationError, Result},
+    lighthouse::LighthouseAssertion,
+    trailer::FillTrailer,
+};
+
+pub const LIGHTHOUSE_PROGRAM_ID: Pubkey = pubkey!("L2TExMFKdjpN9kozasaurPirfHy9P8sbXoAN1qA3S95");
+
+// We only allow certain instruction from the Lighthouse program.
+//
+// If we allow the MemoryWrite instruction, the hacker can drain the signer.
+// https://github.com/Jac0xb/lighthouse/blob/main/programs/lighthouse/lighthouse.json
+pub const ALLOWED_LIGHTHOUSE_DISCRIMINATORS: &[u8] = &[5, 6, 9, 10];
+
+/// A program whose instructions are tolerated on top of the ones built by the RFQ system
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedProgram {
+    pub program_id: Pubkey,
+    /// Allowed first bytes of the instruction data, `None` allows any instruction
+    pub discriminators: Option<Vec<u8>>,
+}
+
+impl AllowedProgram {
+    /// Lighthouse assertions the taker's wallet may append, anything able to write is excluded
+    pub fn lighthouse() -> Self {
+        Self {
+            program_id: LIGHTHOUSE_PROGRAM_ID,
+            discriminators: Some(ALLOWED_LIGHTHOUSE_DISCRIMINATORS.to_vec()),
+        }
+    }
+
+    fn allows(&self, data: &[u8]) -> bool {
+        match &self.discriminators {
+            Some(discriminators) => data
+                .first()
+                .is_some_and(|discriminator| discriminators.contains(discriminator)),
+            None => true,
+        }
+    }
+}
+
+/// Rules applied by the fill validators on top of checking the transaction against the quote
+///
+/// The default policy is the one used by the RFQ system, makers can tighten it to their own risk
+/// needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FillValidationPolicy {
+    /// Programs accepted by [`validate_fill_sanitized_message`](crate::fill::validate_fill_sanitized_message)
+    /// besides compute budget, associated token account and order engine instructions
+    pub extra_programs: Vec<AllowedProgram>,
+    /// Programs whose instructions may be appended after the original ones, checked by
+    /// [`validate_similar_fill_sanitized_message`](crate::fill::validate_similar_fill_sanitized_message)
+    pub appended_programs: Vec<AllowedProgram>,
+    pub min_compute_unit_price: u64,
+    pub max_compute_unit_price: u64,
+    pub min_compute_unit_limit: u32,
+    pub max_compute_unit_limit: u32,
+    pub max_instructions: usize,
+    /// Whether the taker may create a token account in the fill transaction
+    pub allow_create_associated_token_account: bool,
+    /// Minimum number of seconds left before `expire_at` at validation time
+    pub min_seconds_to_expiry: Option<u64>,
+    /// Decode the Lighthouse instructions and reject assertions on the accounts of the maker or
+    /// that the fill cannot satisfy, see [`LighthouseAssertion::check`]
+    pub check_lighthouse_assertions: bool,
+    /// Reject fills whose trailer is not exactly [`FILL_TRAILER_LEN`](crate::trailer::FILL_TRAILER_LEN) bytes or sets flags unknown
+    /// to this version of the sdk, see [`FillTrailer::decode_strict`]
+    ///
+    /// Off by default: the program ignores these bytes, a field added to the trailer by the RFQ
+    /// system would otherwise make every fill invalid.
+    pub strict_trailer: bool,
+}
+
+impl Default for FillValidationPolicy {
+    fn default() -> Self {
+        Self {
+            extra_programs: vec![],
+            appended_programs: vec![AllowedProgram::lighthouse()],
+            min_compute_unit_price: 0,
+            max_compute_unit_price: u64::MAX,
+            min_compute_unit_limit: 0,
+            max_compute_unit_limit: u32::MAX,
+            max_instructions: usize::MAX,
+            allow_create_associated_token_account: true,
+            min_seconds_to_expiry: None,
+            check_lighthouse_assertions: false,
+            strict_trailer: false,
+        }
+    }
+}
+
+impl FillValidationPolicy {
+    pub(crate) fn check_extra_program(
+        &self,
+        index: usize,
+        program_id: &Pubkey,
+        data: &[u8],
+    ) -> Result<()> {
+        check_allowed_program(&self.extra_programs, index, program_id, data)
+    }
+
+    pub(crate) fn check_appended_program(
+        &self,
+        index: usize,
+        program_id: &Pubkey,
+        data: &[u8],
+    ) -> Result<()> {
+        check_allowed_program(&self.appended_programs, index, program_id, data)
+    }
+
+    pub(crate) fn check_instruction_count(&self, actual: usize) -> Result<()> {
+        if actual > self.max_instructions {
+            return Err(FillValidationError::TooManyInstructions {
+                max: self.max_instructions,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_associated_token_account(&self, index: usize) -> Result<()> {
+        if !self.allow_create_associated_token_account {
+            return Err(FillValidationError::AssociatedTokenAccountNotAllowed { index });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_compute_unit_limit(&self, limit: u32) -> Result<()> {
+        if !(self.min_compute_unit_limit..=self.max_compute_unit_limit).contains(&limit) {
+            return Err(FillValidationError::ComputeUnitLimitOutOfBounds {
+                limit,
+                min: self.min_compute_unit_limit,
+                max: self.max_compute_unit_limit,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_compute_unit_price(&self, price: u64) -> Result<()> {
+        if !(self.min_compute_unit_price..=self.max_compute_unit_price).contains(&price) {
+            return Err(FillValidationError::ComputeUnitPriceOutOfBounds {
+                price,
+                min: self.min_compute_unit_price,
+                max: self.max_compute_unit_price,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_lighthouse_assertions(&self, decoded_fill: &DecodedFill) -> Result<()> {
+        if !self.check_lighthouse_assertions {
+            return Ok(());
+        }
+        decoded_fill
+            .lighthouse_instructions
+            .iter()
+            .try_for_each(|instruction| {
+                LighthouseAssertion::decode(instruction)?.check(decoded_fill)
+            })
+    }
+
+    pub(crate) fn check_trailer(&self, trailer_data: &[u8]) -> Result<()> {
+        if self.strict_trailer && !trailer_data.is_empty() {
+            FillTrailer::decode_strict(trailer_data)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_expiry(&self, expire_at: i64) -> Result<()> {
+        self.check_expiry_at(expire_at, unix_timestamp())
+    }
+
+    fn check_expiry_at(&self, expire_at: i64, now: i64) -> Result<()> {
+        let Some(min_seconds_to_expiry) = self.min_seconds_to_expiry else {
+            return Ok(());
+        };
+        let seconds_to_expiry = expire_at.saturating_sub(now);
+        if seconds_to_expiry < i64::try_from(min_seconds_to_expiry).unwrap_or(i64::MAX) {
+            return Err(FillValidationError::ExpiresTooSoon {
+                expire_at,
+                seconds_to_expiry,
+                min_seconds_to_expiry,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn check_allowed_program(
+    allowed_programs: &[AllowedProgram],
+    index: usize,
+    program_id: &Pubkey,
+    data: &[u8],
+) -> Result<()> {
+    let Some(allowed_program) = allowed_programs
+        .iter()
+        .find(|allowed_program| &allowed_program.program_id == program_id)
+    else {
+        return Err(FillValidationError::UnexpectedProgramId {
+            index,
+            program_id: *program_id,
+        });
+    };
+    if !allowed_program.allows(data) {
+        return Err(FillValidationError::ForbiddenInstruction {
+            index,
+            program_id: *program_id,
+            discriminator: data.first().copied(),
+        });
+    }
+    Ok(())
+}
+
+fn unix_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| i64::try_from(duration.as_secs()).unwrap_or(i64::MAX))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy_bounds() {
+        let policy = FillValidationPolicy::default();
+        assert!(policy.check_compute_unit_price(u64::MAX).is_ok());
+        assert!(policy.check_compute_unit_limit(0).is_ok());
+        assert!(policy.check_instruction_count(usize::MAX).is_ok());
+        assert!(policy.check_associated_token_account(0).is_ok());
+        assert!(policy.check_expiry(0).is_ok());
+        assert_eq!(
+            FillValidationError::UnexpectedProgramId {
+                index: 2,
+                program_id: LIGHTHOUSE_PROGRAM_ID
+            },
+            policy
+                .check_extra_program(2, &LIGHTHOUSE_PROGRAM_ID, &[5])
+                .unwrap_err()
+        );
+        assert!(policy
+            .check_appended_program(2, &LIGHTHOUSE_PROGRAM_ID, &[5])
+            .is_ok());
+        assert_eq!(
+            FillValidationError::ForbiddenInstruction {
+                index: 2,
+                program_id: LIGHTHOUSE_PROGRAM_ID,
+                discriminator: None
+            },
+            policy
+                .check_appended_program(2, &LIGHTHOUSE_PROGRAM_ID, &[])
+                .unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_tightened_policy() {
+        let policy = FillValidationPolicy {
+            min_compute_unit_price: 1,
+            max_compute_unit_price: 1_000,
+            max_compute_unit_limit: 200_000,
+            max_instructions: 4,
+            allow_create_associated_token_account: false,
+            min_seconds_to_expiry: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(
+            FillValidationError::ComputeUnitPriceOutOfBounds {
+                price: 0,
+                min: 1,
+                max: 1_000
+            },
+            policy.check_compute_unit_price(0).unwrap_err()
+        );
+        assert!(policy.check_compute_unit_price(1_000).is_ok());
+        assert!(policy.check_compute_unit_limit(200_001).is_err());
+        assert_eq!(
+            FillValidationError::TooManyInstructions { max: 4, actual: 5 },
+            policy.check_instruction_count(5).unwrap_err()
+        );
+        assert!(policy.check_associated_token_account(1).is_err());
+        assert!(policy.check_expiry_at(1_010, 1_000).is_ok());
+        assert_eq!(
+            FillValidationError::ExpiresTooSoon {
+                expire_at: 1_009,
+                seconds_to_expiry: 9,
+                min_seconds_to_expiry: 10
+            },
+            policy.check_expiry_at(1_009, 1_000).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use solana_sdk::{signature::Signature, signer::Signer, transaction::VersionedTransaction};
+
+use crate::error::SignFillError;
+
+/// Add the maker signature to a fill transaction signed by the other parties
+///
+/// The maker slot is found from the signer pubkey among the required signers. Every other
+/// signature must be present and valid for the message, otherwise nothing is signed: a
+/// transaction missing the taker signature cannot land, and sending it would only burn the
+/// maker's fees.
+pub fn sign_fill_transaction<S: Signer + ?Sized>(
+    transaction: &mut VersionedTransaction,
+    maker_signer: &S,
+) -> Result<Signature, SignFillError> {
+    let maker = maker_signer.try_pubkey()?;
+    let num_required_signatures = usize::from(transaction.message.header().num_required_signatures);
+    let signers = transaction
+        .message
+        .static_account_keys()
+        .get(..num_required_signatures)
+        .unwrap_or_default();
+    if transaction.signatures.len() != num_required_signatures {
+        return Err(SignFillError::SignatureCountMismatch {
+            expected: num_required_signatures,
+            actual: transaction.signatures.len(),
+        });
+    }
+    let maker_index = signers
+        .iter()
+        .position(|signer| signer == &maker)
+        .ok_or(SignFillError::NotASigner { signer: maker })?;
+
+    let message = transaction.message.serialize();
+    for (index, (signer, signature)) in signers.iter().zip(&transaction.signatures).enumerate() {
+        if index == maker_index {
+            continue;
+        }
+        if signature == &Signature::default() {
+            return Err(SignFillError::MissingSignature {
+                index,
+                signer: *signer,
+            });
+        }
+        if !signature.verify(signer.as_ref(), &message) {
+            return Err(SignFillError::InvalidSignature {
+                index,
+                signer: *signer,
+            });
+        }
+    }
+
+    let signature = maker_signer.try_sign_message(&message)?;
+    transaction.signatures[maker_index] = signature;
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        hash::Hash,
+        message::{v0, VersionedMessage},
+        signature::Keypair,
+        system_instruction,
+    };
+    use webhook_api::enums::RejectionReason;
+
+    /// A transaction paid by the maker and signed by the taker
+    fn transaction(maker: &Keypair, taker: &Keypair) -> VersionedTransaction {
+        let message = v0::Message::try_compile(
+            &maker.pubkey(),
+            &[system_instruction::transfer(
+                &taker.pubkey(),
+                &maker.pubkey(),
+                1,
+            )],
+            &[],
+            Hash::new_unique(),
+        )
+        .unwrap();
+        let message = VersionedMessage::V0(message);
+        let taker_signature = taker.sign_message(&message.serialize());
+        VersionedTransaction {
+            signatures: vec![Signature::default(), taker_signature],
+            message,
+        }
+    }
+
+    #[test]
+    fn test_sign_fill_transaction() {
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+
+        let mut signed = transaction(&maker, &taker);
+        let signature = sign_fill_transaction(&mut signed, &maker).unwrap();
+        assert_eq!(signed.signatures[0], signature);
+        assert!(signed.verify_with_results().iter().all(|valid| *valid));
+
+        let mut unsigned = transaction(&maker, &taker);
+        unsigned.signatures[1] = Signature::default();
+        assert_eq!(
+            sign_fill_transaction(&mut unsigned, &maker),
+            Err(SignFillError::MissingSignature {
+                index: 1,
+                signer: taker.pubkey()
+            })
+        );
+        assert_eq!(unsigned.signatures[0], Signature::default());
+
+        let mut forged = transaction(&maker, &taker);
+        forged.signatures[1] = Keypair::new().sign_message(&forged.message.serialize());
+        let error = sign_fill_transaction(&mut forged, &maker).unwrap_err();
+        assert_eq!(
+            error,
+            SignFillError::InvalidSignature {
+                index: 1,
+                signer: taker.pubkey()
+            }
+        );
+        assert_eq!(
+            RejectionReason::from(error),
+            RejectionReason::SignatureVerificationFailed
+        );
+
+        let mut other_maker = transaction(&maker, &taker);
+        let signer = Keypair::new();
+        assert_eq!(
+            sign_fill_transaction(&mut other_maker, &signer),
+            Err(SignFillError::NotASigner {
+                signer: signer.pubkey()
+            })
+        );
+    }
+}
@@ -0,0 +1,461 @@
+//! Offline execution of fill transactions against account snapshots
+//!
+//! The transaction runs in an in-process bank holding only the supplied accounts, the order
+//! engine program and the SPL programs, so the outcome of a fill can be known before signing it.
+//! Signatures are not verified and the recent blockhash is replaced by one of the bank, the
+//! message is otherwise executed as is.
+//!
+//! The order engine runs natively unless `SBF_OUT_DIR` (or `BPF_OUT_DIR`) points to a directory
+//! containing `order_engine.so`, e.g. dumped from mainnet. Only the compiled program is metered,
+//! natively the reported compute units leave out the order engine itself.
+use std::collections::{BTreeSet, HashMap};
+
+use agave_feature_set::bpf_account_data_direct_mapping;
+use anchor_lang::{
+    error::ERROR_CODE_OFFSET,
+    solana_program::{account_info::AccountInfo, entrypoint::ProgramResult},
+};
+use anchor_spl::{
+    token,
+    token_2022::{
+        self,
+        spl_token_2022::{extension::StateWithExtensions, state::Account as TokenAccount},
+    },
+};
+use anyhow::{anyhow, Result};
+use order_engine_program::error::OrderEngineError;
+use solana_program_test::{processor, ProgramTest};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    commitment_config::CommitmentLevel,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    transaction::{TransactionError, VersionedTransaction},
+};
+
+use crate::transaction::{
+    deserialize_address_lookup_table, load_addresses, AddressLookupTableLoader,
+};
+
+/// Errors of the order engine program, in the order of their codes
+const ORDER_ENGINE_ERRORS: [OrderEngineError; 3] = [
+    OrderEngineError::InvalidCalculation,
+    OrderEngineError::MissingTemporaryWrappedSolTokenAccount,
+    OrderEngineError::Token2022MintExtensionNotSupported,
+];
+
+/// Executes fill transactions against the supplied accounts, without any network access
+///
+/// Every account read by the transaction must be supplied: the mints, the token accounts, the
+/// maker paying the fees and the address lookup tables. Missing accounts are treated as empty.
+#[derive(Debug, Clone, Default)]
+pub struct FillSimulator {
+    accounts: HashMap<Pubkey, Account>,
+    unix_timestamp: Option<i64>,
+}
+
+/// Outcome of a simulated fill
+#[derive(Debug, Clone)]
+pub struct FillSimulation {
+    /// Error of the transaction, the fees are still charged to the fee payer
+    pub error: Option<TransactionError>,
+    /// Error of the order engine program, when it is the one that failed
+    pub order_engine_error: Option<OrderEngineError>,
+    pub compute_units_consumed: u64,
+    /// Accounts of the transaction whose lamports or token amount changed
+    pub balance_changes: Vec<BalanceChange>,
+    pub logs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceChange {
+    pub address: Pubkey,
+    pub lamports: i128,
+    /// Change of the amount held, for token accounts before or after the transaction
+    pub token: Option<TokenBalanceChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenBalanceChange {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: i128,
+}
+
+/// Address lookup tables among the supplied accounts
+struct SuppliedAddressLookupTables<'a>(&'a HashMap<Pubkey, Account>);
+
+impl AddressLookupTableLoader for SuppliedAddressLookupTables<'_> {
+    fn load_addresses(&self, address_lookup_table: &Pubkey) -> Result<Vec<Pubkey>> {
+        let account = self
+            .0
+            .get(address_lookup_table)
+            .ok_or_else(|| anyhow!("Missing address lookup table {address_lookup_table}"))?;
+        deserialize_address_lookup_table(address_lookup_table, &account.data)
+    }
+}
+
+/// Workaround from anchor issue https://github.com/coral-xyz/anchor/issues/2738#issuecomment-2230683481
+///
+/// The anchor entrypoint wants the slice to live as long as the accounts it holds. The program
+/// tests leak a copy of the accounts for that, which would grow the memory of a webhook with every
+/// simulated swap, the lifetime of the slice is extended instead.
+fn order_engine_entry<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    // SAFETY: only the lifetime changes, the entrypoint returns before `accounts` is dropped and
+    // keeps no reference to it
+    let accounts =
+        unsafe { std::mem::transmute::<&[AccountInfo<'a>], &'a [AccountInfo<'a>]>(accounts) };
+    order_engine_program::entry(program_id, accounts, instruction_data)
+}
+
+/// Token balance held by an account, `None` if it is not a token account
+fn token_balance(account: &Account) -> Option<(Pubkey, Pubkey, u64)> {
+    if account.owner != token::ID && account.owner != token_2022::ID {
+        return None;
+    }
+    let token_account = StateWithExtensions::<TokenAccount>::unpack(&account.data).ok()?;
+    Some((
+        token_account.base.mint,
+        token_account.base.owner,
+        token_account.base.amount,
+    ))
+}
+
+fn balance_change(
+    address: Pubkey,
+    before: Option<&Account>,
+    after: Option<&Account>,
+) -> Option<BalanceChange> {
+    let lamports = |account: Option<&Account>| account.map_or(0, |account| account.lamports);
+    let token_before = before.and_then(token_balance);
+    let token_after = after.and_then(token_balance);
+    let token = token_after
+        .or(token_before)
+        .map(|(mint, owner, _)| TokenBalanceChange {
+            mint,
+            owner,
+            amount: i128::from(token_after.map_or(0, |(_, _, amount)| amount))
+                - i128::from(token_before.map_or(0, |(_, _, amount)| amount)),
+        });
+    let change = BalanceChange {
+        address,
+        lamports: i128::from(lamports(after)) - i128::from(lamports(before)),
+        token: token.filter(|token| token.amount != 0),
+    };
+    (change.lamports != 0 || change.token.is_some()).then_some(change)
+}
+
+/// The order engine error of a failed transaction, when the program that failed is the order engine
+fn order_engine_error(
+    transaction: &VersionedTransaction,
+    error: &TransactionError,
+) -> Option<OrderEngineError> {
+    let TransactionError::InstructionError(index, InstructionError::Custom(code)) = error else {
+        return None;
+    };
+    let instruction = transaction
+        .message
+        .instructions()
+        .get(usize::from(*index))?;
+    if instruction.program_id(transaction.message.static_account_keys())
+        != &order_engine_program::ID
+    {
+        return None;
+    }
+    let variant = code.checked_sub(ERROR_CODE_OFFSET)?;
+    ORDER_ENGINE_ERRORS
+        .get(usize::try_from(variant).ok()?)
+        .copied()
+}
+
+impl FillSimulator {
+    pub fn new(accounts: impl IntoIterator<Item = (Pubkey, Account)>) -> Self {
+        Self {
+            accounts: accounts.into_iter().collect(),
+            unix_timestamp: None,
+        }
+    }
+
+    pub fn add_account(mut self, address: Pubkey, account: Account) -> Self {
+        self.accounts.insert(address, account);
+        self
+    }
+
+    /// Clock of the simulation, the current time by default
+    pub fn unix_timestamp(mut self, unix_timestamp: i64) -> Self {
+        self.unix_timestamp = Some(unix_timestamp);
+        self
+    }
+
+    pub async fn simulate(&self, transaction: &VersionedTransaction) -> Result<FillSimulation> {
+        let mut program_test = ProgramTest::new(
+            "order_engine",
+            order_engine_program::ID,
+            processor!(order_engine_entry),
+        );
+        // required to run the order engine natively
+        program_test.deactivate_feature(bpf_account_data_direct_mapping::ID);
+        for (address, account) in &self.accounts {
+            program_test.add_account(*address, account.clone());
+        }
+        let context = program_test.start_with_context().await;
+        if let Some(unix_timestamp) = self.unix_timestamp {
+            let clock = context.banks_client.get_sysvar::<Clock>().await?;
+            context.set_sysvar(&Clock {
+                unix_timestamp,
+                ..clock
+            });
+        }
+
+        let mut addresses = transaction
+            .message
+            .static_account_keys()
+            .iter()
+            .copied()
+            .collect::<BTreeSet<_>>();
+        if let Some(address_table_lookups) = transaction.message.address_table_lookups() {
+            let loaded_addresses = load_addresses(
+                address_table_lookups,
+                &SuppliedAddressLookupTables(&self.accounts),
+            )?;
+            addresses.extend(loaded_addresses.writable);
+            addresses.extend(loaded_addresses.readonly);
+        }
+
+        let mut transaction = transaction.clone();
+        transaction
+            .message
+            .set_recent_blockhash(context.last_blockhash);
+        let result = context
+            .banks_client
+            .process_transaction_with_metadata(transaction.clone())
+            .await?;
+        let error = result.result.err();
+        let order_engine_error = error
+            .as_ref()
+            .and_then(|error| order_engine_error(&transaction, error));
+        let (compute_units_consumed, logs) = result
+            .metadata
+            .map(|metadata| (metadata.compute_units_consumed, metadata.log_messages))
+            .unwrap_or_default();
+
+        let mut balance_changes = vec![];
+        for address in addresses {
+            let after = context
+                .banks_client
+                .get_account_with_commitment(address, CommitmentLevel::Processed)
+                .await?;
+            balance_changes.extend(balance_change(
+                address,
+                self.accounts.get(&address),
+                after.as_ref(),
+            ));
+        }
+
+        Ok(FillSimulation {
+            error,
+            order_engine_error,
+            compute_units_consumed,
+            balance_changes,
+            logs,
+        })
+    }
+}
+
+impl FillSimulation {
+    pub fn balance_change(&self, address: &Pubkey) -> Option<&BalanceChange> {
+        self.balance_changes
+            .iter()
+            .find(|change| &change.address == address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::{AccountKind, FillTransactionBuilder},
+        fill::Order,
+    };
+    use anchor_spl::{
+        associated_token::get_associated_token_address_with_program_id,
+        token::spl_token,
+        token_2022::spl_token_2022::{
+            extension::{
+                transfer_fee::{TransferFee, TransferFeeConfig},
+                BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+            },
+            state::{AccountState, Mint},
+        },
+    };
+    use solana_sdk::{native_token::LAMPORTS_PER_SOL, program_pack::Pack, rent::Rent};
+
+    fn account(owner: Pubkey, data: Vec<u8>) -> Account {
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn mint(token_program: Pubkey, transfer_fee_basis_points: Option<u16>) -> Account {
+        let mint = Mint {
+            decimals: 6,
+            is_initialized: true,
+            supply: u64::MAX,
+            ..Default::default()
+        };
+        let Some(transfer_fee_basis_points) = transfer_fee_basis_points else {
+            let mut data = vec![0; Mint::LEN];
+            mint.pack_into_slice(&mut data);
+            return account(token_program, data);
+        };
+        let len =
+            ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferFeeConfig])
+                .unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: transfer_fee_basis_points.into(),
+        };
+        let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        config.older_transfer_fee = transfer_fee;
+        config.newer_transfer_fee = transfer_fee;
+        state.base = mint;
+        state.pack_base();
+        state.init_account_type().unwrap();
+        account(token_program, data)
+    }
+
+    fn token_account(token_program: Pubkey, mint: Pubkey, owner: Pubkey, amount: u64) -> Account {
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        TokenAccount {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        account(token_program, data)
+    }
+
+    /// A fill of 100 input tokens for 200 output tokens, the taker output account is created
+    fn simulator(order: &Order, input_token_program: Pubkey) -> FillSimulator {
+        let wallet = |lamports| Account {
+            lamports,
+            ..Default::default()
+        };
+        let ata = |owner, mint, token_program| {
+            get_associated_token_address_with_program_id(owner, mint, &token_program)
+        };
+        FillSimulator::new([
+            (order.maker, wallet(LAMPORTS_PER_SOL)),
+            (order.taker, wallet(LAMPORTS_PER_SOL)),
+            (
+                ata(&order.taker, &order.input_mint, input_token_program),
+                token_account(input_token_program, order.input_mint, order.taker, 1_000),
+            ),
+            (
+                ata(&order.maker, &order.input_mint, input_token_program),
+                token_account(input_token_program, order.input_mint, order.maker, 0),
+            ),
+            (
+                ata(&order.maker, &order.output_mint, token::ID),
+                token_account(token::ID, order.output_mint, order.maker, 1_000),
+            ),
+            (order.output_mint, mint(token::ID, None)),
+        ])
+    }
+
+    fn order() -> Order {
+        Order {
+            taker: Pubkey::new_unique(),
+            maker: Pubkey::new_unique(),
+            in_amount: 100,
+            input_mint: Pubkey::new_unique(),
+            out_amount: 200,
+            output_mint: Pubkey::new_unique(),
+            expire_at: 1_000,
+            expected_trailer: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_simulate_fill() {
+        let order = order();
+        let transaction = FillTransactionBuilder::new(order.clone())
+            .compute_unit_price(1_000_000)
+            .build_transaction()
+            .unwrap();
+        let simulator = simulator(&order, token::ID)
+            .add_account(order.input_mint, mint(token::ID, None))
+            .unix_timestamp(order.expire_at);
+
+        let simulation = simulator.simulate(&transaction).await.unwrap();
+        assert_eq!(simulation.error, None, "{:#?}", simulation.logs);
+        assert!(simulation.compute_units_consumed > 0);
+        let token_change = |owner, mint| {
+            let address = get_associated_token_address_with_program_id(owner, mint, &token::ID);
+            simulation
+                .balance_change(&address)
+                .and_then(|change| change.token.as_ref())
+                .map(|token| token.amount)
+        };
+        assert_eq!(token_change(&order.taker, &order.input_mint), Some(-100));
+        assert_eq!(token_change(&order.maker, &order.input_mint), Some(100));
+        assert_eq!(token_change(&order.maker, &order.output_mint), Some(-200));
+        assert_eq!(token_change(&order.taker, &order.output_mint), Some(200));
+        // the maker pays the signatures and the priority fee, the taker the rent of its account
+        assert_eq!(
+            simulation.balance_change(&order.maker).unwrap().lamports,
+            -(2 * 5_000 + 100_000)
+        );
+        assert_eq!(
+            simulation.balance_change(&order.taker).unwrap().lamports,
+            -i128::from(Rent::default().minimum_balance(TokenAccount::LEN))
+        );
+
+        // past the expiry the program fails, without an order engine error
+        let expired = simulator
+            .unix_timestamp(order.expire_at + 1)
+            .simulate(&transaction)
+            .await
+            .unwrap();
+        assert!(expired.error.is_some());
+        assert!(expired.order_engine_error.is_none());
+        assert_eq!(
+            expired.balance_change(&order.maker).unwrap().lamports,
+            -(2 * 5_000 + 100_000)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_simulate_order_engine_error() {
+        let order = order();
+        let transaction = FillTransactionBuilder::new(order.clone())
+            .taker_accounts(AccountKind::Token2022, AccountKind::Token)
+            .maker_accounts(AccountKind::Token2022, AccountKind::Token)
+            .build_transaction()
+            .unwrap();
+        let simulation = simulator(&order, token_2022::ID)
+            .add_account(order.input_mint, mint(token_2022::ID, Some(100)))
+            .unix_timestamp(order.expire_at)
+            .simulate(&transaction)
+            .await
+            .unwrap();
+        assert!(matches!(
+            simulation.order_engine_error,
+            Some(OrderEngineError::Token2022MintExtensionNotSupported)
+        ));
+    }
+}
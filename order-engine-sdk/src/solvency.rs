@@ -0,0 +1,293 @@
+//! Whether the maker holds enough to settle a fill, checked before signing it
+//!
+//! A maker that signs a fill it cannot settle pays the fees of a failed transaction and counts
+//! against its fulfilment rate, it should rather reject the swap with the matching
+//! [`RejectionReason`](webhook_api::enums::RejectionReason).
+use std::collections::HashMap;
+
+use anchor_spl::token::spl_token;
+use solana_sdk::{message::SanitizedMessage, program_pack::Pack, pubkey::Pubkey, rent::Rent};
+
+use crate::{
+    decode::{decode_fill_sanitized_message, FillAccounts},
+    error::SolvencyError,
+    fill::{Order, ValidatedFill},
+};
+
+/// Fee of each signature of a transaction
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// Balances of the maker, as fetched by the caller
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MakerBalances {
+    /// Lamports of the maker wallet
+    pub lamports: u64,
+    /// Amounts held by the token accounts of the maker, WSOL accounts included
+    pub token_accounts: HashMap<Pubkey, u64>,
+}
+
+/// What settling a fill costs the maker, or requires it to hold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MakerRequirements {
+    /// Signature fees, paid by the maker as the fee payer
+    pub transaction_fee: u64,
+    pub priority_fee: u64,
+    /// Lamports sent from the maker wallet, when it pays the output in native SOL
+    pub native_output: u64,
+    /// Token account debited of `output_amount`, `None` when the output is paid in native SOL
+    pub output_token_account: Option<Pubkey>,
+    pub output_amount: u64,
+    /// Rent of the temporary WSOL account, paid upfront and refunded when it is closed
+    pub temporary_wsol_rent: u64,
+    /// Lamports the maker wallet keeps to stay rent exempt
+    pub rent_exempt_reserve: u64,
+}
+
+impl MakerRequirements {
+    /// Fees and native output, that leave the maker wallet
+    pub fn lamports_spent(&self) -> u64 {
+        self.transaction_fee
+            .saturating_add(self.priority_fee)
+            .saturating_add(self.native_output)
+    }
+
+    /// Lamports the maker wallet must hold before the fill
+    ///
+    /// The temporary WSOL account is refunded before the transaction ends, only the fees have
+    /// been paid at that point.
+    pub fn lamports(&self) -> u64 {
+        let after_fill = self
+            .lamports_spent()
+            .saturating_add(self.rent_exempt_reserve);
+        let during_fill = self
+            .transaction_fee
+            .saturating_add(self.priority_fee)
+            .saturating_add(self.temporary_wsol_rent);
+        after_fill.max(during_fill)
+    }
+}
+
+/// Check the maker can settle a validated fill with its current balances
+///
+/// `validated_fill` is the result of validating `sanitized_message` against `order`, the fill
+/// instruction is only read here for the accounts used by each side of the trade.
+pub fn check_maker_solvency(
+    sanitized_message: &SanitizedMessage,
+    order: &Order,
+    validated_fill: &ValidatedFill,
+    balances: &MakerBalances,
+) -> Result<MakerRequirements, SolvencyError> {
+    let FillAccounts {
+        taker_input_mint_token_account,
+        maker_input_mint_token_account,
+        taker_output_mint_token_account,
+        maker_output_mint_token_account,
+        ..
+    } = decode_fill_sanitized_message(sanitized_message)?.accounts;
+
+    // The program unwraps WSOL through a temporary account whenever one side holds WSOL and the
+    // other expects native SOL
+    let unwraps_input =
+        taker_input_mint_token_account.is_some() && maker_input_mint_token_account.is_none();
+    let unwraps_output =
+        maker_output_mint_token_account.is_some() && taker_output_mint_token_account.is_none();
+
+    let rent = Rent::default();
+    let requirements = MakerRequirements {
+        transaction_fee: sanitized_message
+            .get_signature_details()
+            .num_transaction_signatures()
+            .saturating_mul(LAMPORTS_PER_SIGNATURE),
+        priority_fee: validated_fill.priority_fee(),
+        native_output: if maker_output_mint_token_account.is_none() {
+            order.out_amount
+        } else {
+            0
+        },
+        output_token_account: maker_output_mint_token_account,
+        output_amount: order.out_amount,
+        temporary_wsol_rent: if unwraps_input || unwraps_output {
+            rent.minimum_balance(spl_token::state::Account::LEN)
+        } else {
+            0
+        },
+        rent_exempt_reserve: rent.minimum_balance(0),
+    };
+
+    let required = requirements
+        .lamports_spent()
+        .saturating_add(requirements.rent_exempt_reserve);
+    if balances.lamports < required {
+        return Err(SolvencyError::InsufficientLamports {
+            required,
+            available: balances.lamports,
+        });
+    }
+    if let Some(token_account) = requirements.output_token_account {
+        let available = balances
+            .token_accounts
+            .get(&token_account)
+            .copied()
+            .unwrap_or_default();
+        if available < requirements.output_amount {
+            return Err(SolvencyError::InsufficientTokenBalance {
+                token_account,
+                required: requirements.output_amount,
+                available,
+            });
+        }
+    }
+    if balances.lamports < requirements.lamports() {
+        return Err(SolvencyError::InsufficientLamportsForTemporaryWsolAccount {
+            required: requirements.lamports(),
+            available: balances.lamports,
+        });
+    }
+    Ok(requirements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::{AccountKind, FillTransactionBuilder},
+        fill::validate_fill_sanitized_message,
+        policy::FillValidationPolicy,
+        transaction::{versioned_message_to_sanitized_message, NoAddressLookupTables},
+    };
+    use anchor_spl::token::spl_token::native_mint;
+    use webhook_api::enums::RejectionReason;
+
+    fn check(
+        builder: FillTransactionBuilder,
+        order: &Order,
+        balances: &MakerBalances,
+    ) -> Result<MakerRequirements, SolvencyError> {
+        let sanitized_message = versioned_message_to_sanitized_message(
+            builder.build_message().unwrap(),
+            &NoAddressLookupTables,
+        )
+        .unwrap();
+        let validated_fill = validate_fill_sanitized_message(
+            &sanitized_message,
+            order.clone(),
+            &FillValidationPolicy::default(),
+        )
+        .unwrap();
+        check_maker_solvency(&sanitized_message, order, &validated_fill, balances)
+    }
+
+    #[test]
+    fn test_check_maker_solvency() {
+        let order = Order {
+            taker: Pubkey::new_unique(),
+            maker: Pubkey::new_unique(),
+            in_amount: 1_000_000,
+            input_mint: Pubkey::new_unique(),
+            out_amount: 5_000_000,
+            output_mint: Pubkey::new_unique(),
+            expire_at: i64::MAX,
+            expected_trailer: None,
+        };
+        let maker_output_mint_token_account = AccountKind::Token
+            .token_account(&order.maker, &order.output_mint)
+            .unwrap();
+        let builder = FillTransactionBuilder::new(order.clone())
+            .compute_unit_limit(100_000)
+            .compute_unit_price(1_500);
+        let mut balances = MakerBalances {
+            lamports: 1_000_000,
+            token_accounts: HashMap::from([(maker_output_mint_token_account, 5_000_000)]),
+        };
+
+        let requirements = check(builder, &order, &balances).unwrap();
+        assert_eq!(requirements.transaction_fee, 10_000);
+        assert_eq!(requirements.priority_fee, 150);
+        assert_eq!(requirements.native_output, 0);
+        assert_eq!(
+            requirements.output_token_account,
+            Some(maker_output_mint_token_account)
+        );
+        assert_eq!(requirements.temporary_wsol_rent, 0);
+        assert_eq!(requirements.lamports(), 10_150 + 890_880);
+
+        balances
+            .token_accounts
+            .insert(maker_output_mint_token_account, 4_999_999);
+        let builder = FillTransactionBuilder::new(order.clone());
+        let error = check(builder, &order, &balances).unwrap_err();
+        assert_eq!(
+            error,
+            SolvencyError::InsufficientTokenBalance {
+                token_account: maker_output_mint_token_account,
+                required: 5_000_000,
+                available: 4_999_999,
+            }
+        );
+        assert_eq!(
+            RejectionReason::from(error),
+            RejectionReason::InsufficientBalance
+        );
+    }
+
+    #[test]
+    fn test_check_maker_solvency_native_output() {
+        let order = Order {
+            taker: Pubkey::new_unique(),
+            maker: Pubkey::new_unique(),
+            in_amount: 1_000_000,
+            input_mint: Pubkey::new_unique(),
+            out_amount: 5_000_000,
+            output_mint: native_mint::ID,
+            expire_at: i64::MAX,
+            expected_trailer: None,
+        };
+        let maker_wsol_token_account = AccountKind::Wsol
+            .token_account(&order.maker, &order.output_mint)
+            .unwrap();
+
+        // native SOL paid from the maker wallet
+        let builder = FillTransactionBuilder::new(order.clone())
+            .taker_accounts(AccountKind::Token, AccountKind::NativeSol)
+            .maker_accounts(AccountKind::Token, AccountKind::NativeSol);
+        let balances = MakerBalances {
+            lamports: 5_000_000,
+            token_accounts: HashMap::new(),
+        };
+        assert_eq!(
+            check(builder, &order, &balances),
+            Err(SolvencyError::InsufficientLamports {
+                required: 10_000 + 5_000_000 + 890_880,
+                available: 5_000_000,
+            })
+        );
+
+        // WSOL unwrapped for the taker through the temporary account
+        let builder = || {
+            FillTransactionBuilder::new(order.clone())
+                .taker_accounts(AccountKind::Token, AccountKind::NativeSol)
+                .maker_accounts(AccountKind::Token, AccountKind::Wsol)
+        };
+        let mut balances = MakerBalances {
+            lamports: 1_000_000,
+            token_accounts: HashMap::from([(maker_wsol_token_account, 5_000_000)]),
+        };
+        let error = check(builder(), &order, &balances).unwrap_err();
+        assert_eq!(
+            error,
+            SolvencyError::InsufficientLamportsForTemporaryWsolAccount {
+                required: 10_000 + 2_039_280,
+                available: 1_000_000,
+            }
+        );
+        assert_eq!(
+            RejectionReason::from(error),
+            RejectionReason::InsufficientBalanceForAtaCreation
+        );
+
+        balances.lamports = 10_000 + 2_039_280;
+        let requirements = check(builder(), &order, &balances).unwrap();
+        assert_eq!(requirements.native_output, 0);
+        assert_eq!(requirements.lamports_spent(), 10_000);
+    }
+}
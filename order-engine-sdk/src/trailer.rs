@@ -0,0 +1,131 @@
+use crate::error::{FillValidationError, Result};
+
+/// Number of bytes appended by the RFQ system after the anchor arguments of the fill instruction
+pub const FILL_TRAILER_LEN: usize = 3;
+
+/// Least significant bit of the flags byte, set when the swap is exact-out
+pub const EXACT_OUT_FLAG: u8 = 0b0000_0001;
+
+/// Informational bytes appended to the fill instruction data, ignored by the program
+///
+/// Layout: `[fee_bps: u16 little endian][flags: u8]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FillTrailer {
+    pub fee_bps: u16,
+    /// Raw flags byte, bits other than [`EXACT_OUT_FLAG`] are kept as is so they can be re-encoded
+    pub flags: u8,
+}
+
+impl FillTrailer {
+    pub fn new(fee_bps: u16, exact_out: bool) -> Self {
+        Self {
+            fee_bps,
+            flags: if exact_out { EXACT_OUT_FLAG } else { 0 },
+        }
+    }
+
+    pub fn is_exact_out(&self) -> bool {
+        self.flags & EXACT_OUT_FLAG != 0
+    }
+
+    /// Flags this version of the sdk does not know about
+    pub fn unknown_flags(&self) -> u8 {
+        self.flags & !EXACT_OUT_FLAG
+    }
+
+    pub fn encode(&self) -> [u8; FILL_TRAILER_LEN] {
+        let [fee_bps_low, fee_bps_high] = self.fee_bps.to_le_bytes();
+        [fee_bps_low, fee_bps_high, self.flags]
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let [fee_bps_low, fee_bps_high, flags] = data else {
+            return Err(FillValidationError::InvalidTrailerLength { len: data.len() });
+        };
+        Ok(Self {
+            fee_bps: u16::from_le_bytes([*fee_bps_low, *fee_bps_high]),
+            flags: *flags,
+        })
+    }
+
+    /// Decode the bytes left after the anchor arguments, fewer bytes than a trailer means it is
+    /// absent
+    ///
+    /// Bytes past the trailer are ignored like the program does, so that a field appended by a
+    /// later version of the RFQ system does not turn every fill invalid.
+    pub fn decode_remaining(remaining: &[u8]) -> Option<Self> {
+        remaining
+            .get(..FILL_TRAILER_LEN)
+            .and_then(|data| Self::decode(data).ok())
+    }
+
+    /// Decode and reject any flag bit this version of the sdk does not know about
+    pub fn decode_strict(data: &[u8]) -> Result<Self> {
+        let trailer = Self::decode(data)?;
+        if trailer.unknown_flags() != 0 {
+            return Err(FillValidationError::UnknownTrailerFlags {
+                flags: trailer.unknown_flags(),
+            });
+        }
+        Ok(trailer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_trailer_round_trip() {
+        let trailer = FillTrailer::new(20, true);
+        assert_eq!(trailer.encode(), [20, 0, 1]);
+        assert_eq!(FillTrailer::decode(&trailer.encode()).unwrap(), trailer);
+        assert!(trailer.is_exact_out());
+
+        let trailer = FillTrailer::new(10_000, false);
+        assert_eq!(trailer.encode(), [0x10, 0x27, 0]);
+        assert!(!FillTrailer::decode(&trailer.encode())
+            .unwrap()
+            .is_exact_out());
+    }
+
+    #[test]
+    fn test_fill_trailer_unknown_flags() {
+        let trailer = FillTrailer::decode(&[2, 0, 0b101]).unwrap();
+        assert!(trailer.is_exact_out());
+        assert_eq!(trailer.unknown_flags(), 0b100);
+        // Unknown flags survive re-encoding
+        assert_eq!(trailer.encode(), [2, 0, 0b101]);
+
+        assert_eq!(
+            FillValidationError::UnknownTrailerFlags { flags: 0b100 },
+            FillTrailer::decode_strict(&[2, 0, 0b101]).unwrap_err()
+        );
+        assert_eq!(
+            "Unknown fill trailer flags 0b00000100",
+            FillValidationError::UnknownTrailerFlags { flags: 0b100 }.to_string()
+        );
+    }
+
+    #[test]
+    fn test_fill_trailer_invalid_length() {
+        assert_eq!(FillTrailer::decode_remaining(&[]), None);
+        assert_eq!(FillTrailer::decode_remaining(&[1, 0]), None);
+        assert_eq!(
+            "Invalid fill trailer length 2, expected 3",
+            FillTrailer::decode(&[1, 0]).unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Invalid fill trailer length 4, expected 3",
+            FillTrailer::decode_strict(&[20, 0, 1, 7])
+                .unwrap_err()
+                .to_string()
+        );
+
+        // A longer trailer is decoded from its first bytes
+        assert_eq!(
+            FillTrailer::decode_remaining(&[20, 0, 1, 7]),
+            Some(FillTrailer::new(20, true))
+        );
+    }
+}
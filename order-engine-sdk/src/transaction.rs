@@ -1,12 +1,18 @@
+use std::collections::HashMap;
+
 use agave_reserved_account_keys::ReservedAccountKeys;
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use base64::prelude::*;
 use bincode;
+use solana_address_lookup_table_interface::state::AddressLookupTable;
+use solana_rpc_client::{nonblocking, rpc_client::RpcClient};
 use solana_sdk::{
     message::{
-        v0::LoadedAddresses, SanitizedMessage, SanitizedVersionedMessage, SimpleAddressLoader,
-        VersionedMessage,
+        v0::{LoadedAddresses, MessageAddressTableLookup},
+        AddressLookupTableAccount, SanitizedMessage, SanitizedVersionedMessage,
+        SimpleAddressLoader, VersionedMessage,
     },
+    pubkey::Pubkey,
     transaction::VersionedTransaction,
 };
 
@@ -15,18 +21,144 @@ pub struct TransactionDetails {
     pub sanitized_message: SanitizedMessage,
 }
 
-pub fn deserialize_transaction_base64_into_transaction_details(
+/// Source of address lookup tables used to decompress v0 messages
+///
+/// Implementations only need to return the addresses stored in a table, resolving the lookups
+/// of a message against them is shared. Deactivation of a table is not taken into account.
+pub trait AddressLookupTableLoader {
+    fn load_addresses(&self, address_lookup_table: &Pubkey) -> Result<Vec<Pubkey>>;
+}
+
+/// Rejects any message that uses address lookup tables
+pub struct NoAddressLookupTables;
+
+impl AddressLookupTableLoader for NoAddressLookupTables {
+    fn load_addresses(&self, address_lookup_table: &Pubkey) -> Result<Vec<Pubkey>> {
+        Err(anyhow!(
+            "Address lookup table {address_lookup_table} is not supported"
+        ))
+    }
+}
+
+impl AddressLookupTableLoader for [AddressLookupTableAccount] {
+    fn load_addresses(&self, address_lookup_table: &Pubkey) -> Result<Vec<Pubkey>> {
+        self.iter()
+            .find(|account| &account.key == address_lookup_table)
+            .map(|account| account.addresses.clone())
+            .with_context(|| format!("Unknown address lookup table {address_lookup_table}"))
+    }
+}
+
+impl AddressLookupTableLoader for Vec<AddressLookupTableAccount> {
+    fn load_addresses(&self, address_lookup_table: &Pubkey) -> Result<Vec<Pubkey>> {
+        self.as_slice().load_addresses(address_lookup_table)
+    }
+}
+
+impl AddressLookupTableLoader for HashMap<Pubkey, Vec<Pubkey>> {
+    fn load_addresses(&self, address_lookup_table: &Pubkey) -> Result<Vec<Pubkey>> {
+        self.get(address_lookup_table)
+            .cloned()
+            .with_context(|| format!("Unknown address lookup table {address_lookup_table}"))
+    }
+}
+
+/// Fetches every address lookup table from the cluster with a blocking rpc client
+impl AddressLookupTableLoader for RpcClient {
+    fn load_addresses(&self, address_lookup_table: &Pubkey) -> Result<Vec<Pubkey>> {
+        let account = self
+            .get_account(address_lookup_table)
+            .map_err(|error| anyhow!("Failed to fetch {address_lookup_table}: {error}"))?;
+        deserialize_address_lookup_table(address_lookup_table, &account.data)
+    }
+}
+
+pub(crate) fn deserialize_address_lookup_table(
+    address_lookup_table: &Pubkey,
+    data: &[u8],
+) -> Result<Vec<Pubkey>> {
+    let table = AddressLookupTable::deserialize(data)
+        .map_err(|error| anyhow!("Invalid address lookup table {address_lookup_table}: {error}"))?;
+    Ok(table.addresses.into_owned())
+}
+
+/// Fetch the address lookup tables used by a message, for use in async contexts
+///
+/// The returned accounts can be passed as the loader of [`versioned_message_to_sanitized_message`]
+pub async fn fetch_address_lookup_table_accounts(
+    rpc_client: &nonblocking::rpc_client::RpcClient,
+    versioned_message: &VersionedMessage,
+) -> Result<Vec<AddressLookupTableAccount>> {
+    let Some(address_table_lookups) = versioned_message.address_table_lookups() else {
+        return Ok(vec![]);
+    };
+    if address_table_lookups.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let keys = address_table_lookups
+        .iter()
+        .map(|lookup| lookup.account_key)
+        .collect::<Vec<_>>();
+    let accounts = rpc_client
+        .get_multiple_accounts(&keys)
+        .await
+        .map_err(|error| anyhow!("Failed to fetch address lookup tables: {error}"))?;
+
+    keys.into_iter()
+        .zip(accounts)
+        .map(|(key, account)| {
+            let account = account.with_context(|| format!("Missing address lookup table {key}"))?;
+            Ok(AddressLookupTableAccount {
+                key,
+                addresses: deserialize_address_lookup_table(&key, &account.data)?,
+            })
+        })
+        .collect()
+}
+
+/// Resolve the writable and readonly addresses referenced by the lookups of a message
+pub fn load_addresses<L: AddressLookupTableLoader + ?Sized>(
+    address_table_lookups: &[MessageAddressTableLookup],
+    loader: &L,
+) -> Result<LoadedAddresses> {
+    let mut loaded_addresses = LoadedAddresses::default();
+    for MessageAddressTableLookup {
+        account_key,
+        writable_indexes,
+        readonly_indexes,
+    } in address_table_lookups
+    {
+        let addresses = loader.load_addresses(account_key)?;
+        let lookup = |index: &u8| {
+            addresses
+                .get(usize::from(*index))
+                .copied()
+                .with_context(|| {
+                    format!("Invalid transaction: index {index} out of bounds of {account_key}")
+                })
+        };
+        for index in writable_indexes {
+            loaded_addresses.writable.push(lookup(index)?);
+        }
+        for index in readonly_indexes {
+            loaded_addresses.readonly.push(lookup(index)?);
+        }
+    }
+    Ok(loaded_addresses)
+}
+
+pub fn deserialize_transaction_base64_into_transaction_details<
+    L: AddressLookupTableLoader + ?Sized,
+>(
     transaction: &str,
+    loader: &L,
 ) -> Result<TransactionDetails> {
-    let base64_decoded_tx = BASE64_STANDARD
-        .decode(transaction)
-        .map_err(|error| anyhow!("Invalid transaction: {error}"))?;
-    let versioned_transaction = bincode::deserialize::<VersionedTransaction>(&base64_decoded_tx)
-        .map_err(|error| anyhow!("Invalid transaction: {error}"))?;
+    let versioned_transaction = deserialize_transaction_base64(transaction)?;
 
     // Check the instructions
     let sanitized_message =
-        versioned_message_to_sanitized_message(versioned_transaction.message.clone())?;
+        versioned_message_to_sanitized_message(versioned_transaction.message.clone(), loader)?;
 
     Ok(TransactionDetails {
         versioned_transaction,
@@ -34,18 +166,184 @@ pub fn deserialize_transaction_base64_into_transaction_details(
     })
 }
 
-pub fn versioned_message_to_sanitized_message(
+pub fn deserialize_transaction_base64(transaction: &str) -> Result<VersionedTransaction> {
+    let base64_decoded_tx = BASE64_STANDARD
+        .decode(transaction)
+        .map_err(|error| anyhow!("Invalid transaction: {error}"))?;
+    bincode::deserialize::<VersionedTransaction>(&base64_decoded_tx)
+        .map_err(|error| anyhow!("Invalid transaction: {error}"))
+}
+
+pub fn versioned_message_to_sanitized_message<L: AddressLookupTableLoader + ?Sized>(
     versioned_message: VersionedMessage,
+    loader: &L,
 ) -> Result<SanitizedMessage> {
+    let loaded_addresses = match versioned_message.address_table_lookups() {
+        Some(address_table_lookups) => load_addresses(address_table_lookups, loader)?,
+        None => LoadedAddresses::default(),
+    };
+
     let sanitized_versioned_message = SanitizedVersionedMessage::try_new(versioned_message)
         .map_err(|error| anyhow!("Invalid transaction: {error}"))?;
 
     let sanitized_message = SanitizedMessage::try_new(
         sanitized_versioned_message,
-        SimpleAddressLoader::Enabled(LoadedAddresses::default()), // We do not support any compression for now
+        SimpleAddressLoader::Enabled(loaded_addresses),
         &ReservedAccountKeys::empty_key_set(),
     )
     .map_err(|error| anyhow!("Invalid transaction: {error}"))?;
 
     Ok(sanitized_message)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fill::{validate_fill_sanitized_message, validate_similar_fill_sanitized_message, Order},
+        order_engine,
+        policy::FillValidationPolicy,
+    };
+    use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+    use solana_sdk::{
+        compute_budget::ComputeBudgetInstruction, hash::Hash, instruction::Instruction,
+        message::v0, signature::Signature,
+    };
+
+    fn compressed_fill_transaction(
+        order: &Order,
+        address_lookup_table_accounts: &[AddressLookupTableAccount],
+    ) -> String {
+        let fill_ix = Instruction {
+            program_id: order_engine::ID,
+            accounts: order_engine::client::accounts::Fill {
+                taker: order.taker,
+                maker: order.maker,
+                taker_input_mint_token_account: Some(address_lookup_table_accounts[0].addresses[0]),
+                maker_input_mint_token_account: Some(address_lookup_table_accounts[0].addresses[1]),
+                taker_output_mint_token_account: Some(Pubkey::new_unique()),
+                maker_output_mint_token_account: Some(Pubkey::new_unique()),
+                input_mint: order.input_mint,
+                input_token_program: anchor_spl::token::ID,
+                output_mint: order.output_mint,
+                output_token_program: anchor_spl::token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: order_engine::client::args::Fill {
+                input_amount: order.in_amount,
+                output_amount: order.out_amount,
+                expire_at: order.expire_at,
+            }
+            .data(),
+        };
+        let message = v0::Message::try_compile(
+            &order.maker,
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(100_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1_000),
+                fill_ix,
+            ],
+            address_lookup_table_accounts,
+            Hash::new_unique(),
+        )
+        .unwrap();
+        assert!(!message.address_table_lookups.is_empty());
+
+        let versioned_transaction = VersionedTransaction {
+            signatures: vec![Signature::default(); 2],
+            message: VersionedMessage::V0(message),
+        };
+        BASE64_STANDARD.encode(bincode::serialize(&versioned_transaction).unwrap())
+    }
+
+    #[test]
+    fn test_validate_fill_with_address_lookup_tables() {
+        let order = Order {
+            taker: Pubkey::new_unique(),
+            maker: Pubkey::new_unique(),
+            in_amount: 100,
+            input_mint: Pubkey::new_unique(),
+            out_amount: 200,
+            output_mint: Pubkey::new_unique(),
+            expire_at: 1000,
+            expected_trailer: None,
+        };
+        let address_lookup_table_accounts = vec![AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                order.input_mint,
+                order.output_mint,
+            ],
+        }];
+        let transaction = compressed_fill_transaction(&order, &address_lookup_table_accounts);
+
+        // Without the tables the message cannot be decompressed
+        assert!(deserialize_transaction_base64_into_transaction_details(
+            &transaction,
+            &NoAddressLookupTables
+        )
+        .is_err());
+        assert!(deserialize_transaction_base64_into_transaction_details(
+            &transaction,
+            &HashMap::<Pubkey, Vec<Pubkey>>::new()
+        )
+        .is_err());
+
+        let TransactionDetails {
+            sanitized_message, ..
+        } = deserialize_transaction_base64_into_transaction_details(
+            &transaction,
+            &address_lookup_table_accounts,
+        )
+        .unwrap();
+        assert_eq!(
+            sanitized_message.account_keys().iter().count(),
+            sanitized_message.static_account_keys().len() + 4
+        );
+
+        let validated_similar_fill = validate_similar_fill_sanitized_message(
+            sanitized_message.clone(),
+            sanitized_message.clone(),
+            &FillValidationPolicy::default(),
+        )
+        .unwrap();
+        assert_eq!(validated_similar_fill.input_mint, order.input_mint);
+        assert_eq!(
+            validated_similar_fill.taker_input_mint_token_account,
+            address_lookup_table_accounts[0].addresses[0]
+        );
+
+        let validated_fill = validate_fill_sanitized_message(
+            &sanitized_message,
+            order,
+            &FillValidationPolicy::default(),
+        )
+        .unwrap();
+        assert_eq!(validated_fill.compute_unit_limit, 100_000);
+    }
+
+    #[test]
+    fn test_load_addresses_out_of_bounds() {
+        let key = Pubkey::new_unique();
+        let tables = HashMap::from([(key, vec![Pubkey::new_unique()])]);
+        let lookups = [MessageAddressTableLookup {
+            account_key: key,
+            writable_indexes: vec![0],
+            readonly_indexes: vec![1],
+        }];
+        assert!(load_addresses(&lookups, &tables).is_err());
+
+        let lookups = [MessageAddressTableLookup {
+            account_key: key,
+            writable_indexes: vec![0],
+            readonly_indexes: vec![],
+        }];
+        assert_eq!(
+            load_addresses(&lookups, &tables).unwrap().writable,
+            tables[&key]
+        );
+    }
+}
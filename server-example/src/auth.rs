@@ -0,0 +1,116 @@
+//! `X-API-KEY` authentication
+//!
+//! Several keys can be valid at once so that a key can be rotated without downtime: add the new
+//! key, update the RFQ registration, then remove the old key.
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use crate::server::ApiError;
+
+pub const API_KEY_HEADER: &str = "X-API-KEY";
+
+pub struct ApiKeys {
+    /// Digests of the keys, compared instead of the keys so that their lengths do not leak
+    key_digests: Vec<[u8; 32]>,
+    exempt_paths: Vec<String>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: &[String], exempt_paths: &[String]) -> Self {
+        Self {
+            key_digests: keys.iter().map(|key| digest(key.as_bytes())).collect(),
+            exempt_paths: exempt_paths
+                .iter()
+                .map(|path| path.trim_end_matches('/').to_string())
+                .collect(),
+        }
+    }
+
+    /// No key configured, every request is accepted
+    pub fn is_disabled(&self) -> bool {
+        self.key_digests.is_empty()
+    }
+
+    /// An exempt path and the paths below it, `/health` exempts `/health/live` but not `/healthz`
+    fn is_exempt(&self, path: &str) -> bool {
+        self.exempt_paths.iter().any(|exempt_path| {
+            path.strip_prefix(exempt_path.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+
+    /// Compare against every key so the time taken does not depend on which key matched
+    fn is_valid(&self, api_key: &[u8]) -> bool {
+        let api_key_digest = digest(api_key);
+        self.key_digests.iter().fold(false, |valid, key_digest| {
+            constant_time_eq(key_digest, &api_key_digest) | valid
+        })
+    }
+}
+
+fn digest(key: &[u8]) -> [u8; 32] {
+    Sha256::digest(key).into()
+}
+
+/// Compare two digests in a time that does not depend on their content
+fn constant_time_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter().zip(b).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+pub async fn require_api_key(
+    State(api_keys): State<Arc<ApiKeys>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if api_keys.is_disabled() || api_keys.is_exempt(request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let is_valid = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .is_some_and(|api_key| api_keys.is_valid(api_key.as_bytes()));
+    if !is_valid {
+        return ApiError::Unauthorized().into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_keys() {
+        let api_keys = ApiKeys::new(
+            &["old-key".to_string(), "new-key".to_string()],
+            &["/health".to_string(), "/swagger-ui".to_string()],
+        );
+        assert!(!api_keys.is_disabled());
+        assert!(api_keys.is_valid(b"old-key"));
+        assert!(api_keys.is_valid(b"new-key"));
+        assert!(!api_keys.is_valid(b"new-kez"));
+        assert!(!api_keys.is_valid(b"new-key "));
+        assert!(!api_keys.is_valid(b""));
+
+        assert!(api_keys.is_exempt("/health"));
+        assert!(api_keys.is_exempt("/swagger-ui/index.html"));
+        assert!(!api_keys.is_exempt("/quote"));
+        assert!(!api_keys.is_exempt("/healthz"));
+        assert!(!api_keys.is_exempt("/health-admin"));
+        assert!(!api_keys.is_exempt("/swagger-ui-private"));
+
+        // exempting /admin/ does not exempt its siblings
+        let api_keys = ApiKeys::new(&["key".to_string()], &["/admin/".to_string()]);
+        assert!(api_keys.is_exempt("/admin/fulfilment"));
+        assert!(!api_keys.is_exempt("/administration"));
+
+        assert!(ApiKeys::new(&[], &[]).is_disabled());
+    }
+}
@@ -1,4 +1,20 @@
-use clap::Parser;
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+
+use crate::pricing::PairRate;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum PricerKind {
+    /// Quote a hardcoded amount for any pair, useful to test the integration
+    Static,
+    /// Quote the pairs listed in `pair_rates`, or the rates of `pairs_config` when set, at their
+    /// fixed rate minus their spread
+    FixedRate,
+    /// Quote the mid price minus `mid_price_bps`, the price cache is seeded from `pair_rates` and
+    /// the rates of `pairs_config`
+    MidPrice,
+}
 
 #[derive(Clone, Parser)]
 pub struct Config {
@@ -23,6 +39,58 @@ pub struct Config {
         "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN",
     ])]
     pub supported_tokens: Vec<String>,
+
+    /// TOML or JSON file of the quoted pairs with their direction, sizes, quote types and rates,
+    /// see pairs.example.toml. When set, it replaces `supported_tokens`
+    #[clap(env, long)]
+    pub pairs_config: Option<PathBuf>,
+
+    /// Seconds between two checks of `pairs_config` for changes
+    #[clap(env, long, default_value_t = 5)]
+    pub pairs_config_poll_secs: u64,
+
+    /// Keys accepted in the X-API-KEY header, list several keys to rotate them
+    /// no key disables the authentication
+    #[clap(env, long, value_delimiter = ',')]
+    pub api_keys: Vec<String>,
+
+    /// Path prefixes that do not require an API key
+    #[clap(env, long, value_delimiter = ',', default_values = &[
+        "/health",
+        "/swagger-ui",
+        "/api-doc",
+    ])]
+    pub api_key_exempt_paths: Vec<String>,
+
+    /// Milliseconds kept from the x-request-timeout budget to send the quote back
+    #[clap(env, long, default_value_t = 20)]
+    pub deadline_safety_margin_ms: u64,
+
+    /// How quotes are priced
+    #[clap(env, long, value_enum, default_value_t = PricerKind::Static)]
+    pub pricer: PricerKind,
+
+    /// Rates of the quoted pairs, as TOKEN_IN:TOKEN_OUT:RATE:SPREAD_BPS where the rate is in
+    /// atomic units of the output token per atomic unit of the input token
+    #[clap(env, long, value_delimiter = ',')]
+    pub pair_rates: Vec<PairRate>,
+
+    /// Basis points taken from the mid price by the mid-price pricer
+    #[clap(env, long, default_value_t = 10)]
+    pub mid_price_bps: u16,
+
+    /// Mid prices older than this are not quoted
+    #[clap(env, long)]
+    pub max_price_age_secs: Option<u64>,
+
+    /// Send the accepted swaps to `rpc_url`, otherwise they land on an in-memory mock cluster
+    #[clap(env, long)]
+    pub broadcast: bool,
+
+    /// Most lamports of signature and priority fees the maker pays for a swap, wallets may raise
+    /// the compute unit price of the transaction after the quote
+    #[clap(env, long, default_value_t = 1_000_000)]
+    pub max_fill_network_fee: u64,
 }
 
 // Separating this so we can reuse it in tests
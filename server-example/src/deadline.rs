@@ -0,0 +1,49 @@
+//! Stop working on quotes the RFQ system will not wait for
+//!
+//! Quote requests carry their time budget in the `x-request-start` and `x-request-timeout`
+//! headers. Once the budget minus a safety margin, covering the time to send the response back,
+//! is used up the pricing is cancelled and the request is answered with a 404.
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use webhook_api::deadline::RfqDeadline;
+
+use crate::{server::ApiError, telemetry::record_deadline_missed};
+
+pub async fn enforce_deadline(
+    State(safety_margin): State<Duration>,
+    deadline: Option<RfqDeadline>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // not sent by the RFQ system, nothing to enforce
+    let Some(deadline) = deadline else {
+        return next.run(request).await;
+    };
+
+    let budget = deadline.remaining().saturating_sub(safety_margin);
+    if budget.is_zero() {
+        tracing::warn!("Quote request received after its deadline {deadline:?}");
+        record_deadline_missed("budget_exhausted");
+        return ApiError::NotFound().into_response();
+    }
+
+    // dropping the handler future cancels the pricing
+    match async_std::future::timeout(budget, next.run(request)).await {
+        Ok(response) => {
+            if deadline.is_expired() {
+                record_deadline_missed("late_response");
+            }
+            response
+        }
+        Err(_) => {
+            tracing::warn!("Quote cancelled after {budget:?}, the deadline is too close");
+            record_deadline_missed("cancelled");
+            ApiError::NotFound().into_response()
+        }
+    }
+}
@@ -0,0 +1,403 @@
+//! Broadcast of the signed fill transactions
+//!
+//! The [`FillSender`] keeps sending an accepted transaction until it is confirmed or its
+//! blockhash expires, and records the outcome per quote id for [`OUTCOME_TTL`]. The cluster is
+//! reached through an [`RpcBackend`], [`MockRpcBackend`] runs the whole path in memory.
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use async_std::task::JoinHandle;
+use futures::future::{self, BoxFuture, FutureExt};
+use serde::{Serialize, Serializer};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    signature::Signature,
+    transaction::{self, TransactionError, VersionedTransaction},
+};
+
+use crate::telemetry::record_fill_outcome;
+
+/// Time between two sends of the same transaction
+pub const DEFAULT_RESEND_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest a blockhash stays valid, a transaction is no longer sent past this time after its
+/// first send even when the validity of its blockhash cannot be checked
+pub const BLOCKHASH_LIFETIME: Duration = Duration::from_secs(90);
+
+/// Time an outcome is kept after it was last updated
+pub const OUTCOME_TTL: Duration = Duration::from_secs(10 * 60);
+
+pub trait RpcBackend: Send + Sync {
+    fn send_transaction<'a>(
+        &'a self,
+        transaction: &'a VersionedTransaction,
+    ) -> BoxFuture<'a, Result<Signature>>;
+
+    /// `None` while the transaction has not been processed
+    fn get_signature_status<'a>(
+        &'a self,
+        signature: &'a Signature,
+    ) -> BoxFuture<'a, Result<Option<transaction::Result<()>>>>;
+
+    fn is_blockhash_valid<'a>(&'a self, blockhash: &'a Hash) -> BoxFuture<'a, Result<bool>>;
+}
+
+impl RpcBackend for RpcClient {
+    fn send_transaction<'a>(
+        &'a self,
+        transaction: &'a VersionedTransaction,
+    ) -> BoxFuture<'a, Result<Signature>> {
+        async move { Ok(RpcClient::send_transaction(self, transaction).await?) }.boxed()
+    }
+
+    fn get_signature_status<'a>(
+        &'a self,
+        signature: &'a Signature,
+    ) -> BoxFuture<'a, Result<Option<transaction::Result<()>>>> {
+        async move { Ok(RpcClient::get_signature_status(self, signature).await?) }.boxed()
+    }
+
+    fn is_blockhash_valid<'a>(&'a self, blockhash: &'a Hash) -> BoxFuture<'a, Result<bool>> {
+        async move {
+            Ok(
+                RpcClient::is_blockhash_valid(self, blockhash, CommitmentConfig::processed())
+                    .await?,
+            )
+        }
+        .boxed()
+    }
+}
+
+/// In-memory cluster where every transaction lands after a number of sends
+pub struct MockRpcBackend {
+    /// Result of the transaction once landed, `None` never lands it
+    pub outcome: Option<transaction::Result<()>>,
+    pub land_after_sends: usize,
+    /// Number of blockhash checks answered as valid before the blockhash expires
+    pub valid_blockhash_checks: usize,
+    /// Answer every blockhash check with an error, as an unreachable RPC would
+    pub fail_blockhash_checks: bool,
+    sends: AtomicUsize,
+    blockhash_checks: AtomicUsize,
+    statuses: Mutex<HashMap<Signature, transaction::Result<()>>>,
+}
+
+impl MockRpcBackend {
+    pub fn new(
+        outcome: Option<transaction::Result<()>>,
+        land_after_sends: usize,
+        valid_blockhash_checks: usize,
+    ) -> Self {
+        Self {
+            outcome,
+            land_after_sends,
+            valid_blockhash_checks,
+            fail_blockhash_checks: false,
+            sends: AtomicUsize::new(0),
+            blockhash_checks: AtomicUsize::new(0),
+            statuses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Lands every transaction successfully on the first send
+    pub fn landing() -> Self {
+        Self::new(Some(Ok(())), 1, usize::MAX)
+    }
+}
+
+impl RpcBackend for MockRpcBackend {
+    fn send_transaction<'a>(
+        &'a self,
+        transaction: &'a VersionedTransaction,
+    ) -> BoxFuture<'a, Result<Signature>> {
+        let signature = transaction.signatures.first().copied().unwrap_or_default();
+        let sends = self.sends.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some(outcome) = &self.outcome {
+            if sends >= self.land_after_sends {
+                self.statuses
+                    .lock()
+                    .unwrap()
+                    .entry(signature)
+                    .or_insert_with(|| outcome.clone());
+            }
+        }
+        future::ready(Ok(signature)).boxed()
+    }
+
+    fn get_signature_status<'a>(
+        &'a self,
+        signature: &'a Signature,
+    ) -> BoxFuture<'a, Result<Option<transaction::Result<()>>>> {
+        let status = self.statuses.lock().unwrap().get(signature).cloned();
+        future::ready(Ok(status)).boxed()
+    }
+
+    fn is_blockhash_valid<'a>(&'a self, _blockhash: &'a Hash) -> BoxFuture<'a, Result<bool>> {
+        let checks = self.blockhash_checks.fetch_add(1, Ordering::SeqCst);
+        if self.fail_blockhash_checks {
+            return future::ready(Err(anyhow!("Blockhash check failed"))).boxed();
+        }
+        future::ready(Ok(checks < self.valid_blockhash_checks)).boxed()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum FillOutcome {
+    /// Still being sent
+    Pending {
+        #[serde(serialize_with = "serialize_signature")]
+        signature: Signature,
+    },
+    Landed {
+        #[serde(serialize_with = "serialize_signature")]
+        signature: Signature,
+    },
+    /// Landed but the transaction failed
+    Failed {
+        #[serde(serialize_with = "serialize_signature")]
+        signature: Signature,
+        error: TransactionError,
+    },
+    /// The blockhash expired before the transaction landed
+    Expired {
+        #[serde(serialize_with = "serialize_signature")]
+        signature: Signature,
+    },
+}
+
+fn serialize_signature<S: Serializer>(
+    signature: &Signature,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_str(signature)
+}
+
+pub struct FillSender {
+    backend: Arc<dyn RpcBackend>,
+    resend_interval: Duration,
+    /// Time after the first send past which the transaction is considered expired
+    send_deadline: Duration,
+    /// Outcome of each quote id, with the time it was last updated
+    outcomes: Mutex<HashMap<String, (FillOutcome, Instant)>>,
+}
+
+impl FillSender {
+    pub fn new(
+        backend: Arc<dyn RpcBackend>,
+        resend_interval: Duration,
+        send_deadline: Duration,
+    ) -> Self {
+        Self {
+            backend,
+            resend_interval,
+            send_deadline,
+            outcomes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Send the transaction in the background
+    pub fn spawn(
+        self: &Arc<Self>,
+        quote_id: String,
+        transaction: VersionedTransaction,
+    ) -> JoinHandle<FillOutcome> {
+        let fill_sender = self.clone();
+        async_std::task::spawn(async move { fill_sender.send(quote_id, transaction).await })
+    }
+
+    /// Send the transaction until it is confirmed or its blockhash expires
+    pub async fn send(&self, quote_id: String, transaction: VersionedTransaction) -> FillOutcome {
+        let signature = transaction.signatures.first().copied().unwrap_or_default();
+        let blockhash = *transaction.message.recent_blockhash();
+        let first_send = Instant::now();
+        self.record(&quote_id, FillOutcome::Pending { signature });
+
+        let outcome = loop {
+            if let Err(error) = self.backend.send_transaction(&transaction).await {
+                tracing::debug!("Failed to send {signature} for quote {quote_id}: {error:?}");
+            }
+            async_std::task::sleep(self.resend_interval).await;
+
+            if let Some(outcome) = self.confirmed_outcome(&signature).await {
+                break outcome;
+            }
+            let is_blockhash_valid = match self.backend.is_blockhash_valid(&blockhash).await {
+                Ok(is_blockhash_valid) => is_blockhash_valid,
+                Err(error) => {
+                    tracing::warn!("Failed to check blockhash {blockhash}: {error:?}");
+                    true
+                }
+            };
+            if !is_blockhash_valid || first_send.elapsed() >= self.send_deadline {
+                // It might have landed just before the blockhash expired
+                break self
+                    .confirmed_outcome(&signature)
+                    .await
+                    .unwrap_or(FillOutcome::Expired { signature });
+            }
+        };
+
+        tracing::info!("Fill of quote {quote_id}: {outcome:?}");
+        record_fill_outcome(&outcome);
+        self.record(&quote_id, outcome.clone());
+        outcome
+    }
+
+    pub fn outcome(&self, quote_id: &str) -> Option<FillOutcome> {
+        self.outcomes
+            .lock()
+            .unwrap()
+            .get(quote_id)
+            .map(|(outcome, _)| outcome.clone())
+    }
+
+    async fn confirmed_outcome(&self, signature: &Signature) -> Option<FillOutcome> {
+        match self.backend.get_signature_status(signature).await {
+            Ok(Some(Ok(()))) => Some(FillOutcome::Landed {
+                signature: *signature,
+            }),
+            Ok(Some(Err(error))) => Some(FillOutcome::Failed {
+                signature: *signature,
+                error,
+            }),
+            Ok(None) => None,
+            Err(error) => {
+                tracing::warn!("Failed to get the status of {signature}: {error:?}");
+                None
+            }
+        }
+    }
+
+    /// Record the outcome of a quote, evicting the outcomes older than [`OUTCOME_TTL`]
+    fn record(&self, quote_id: &str, outcome: FillOutcome) {
+        self.record_at(quote_id, outcome, Instant::now())
+    }
+
+    fn record_at(&self, quote_id: &str, outcome: FillOutcome, now: Instant) {
+        let mut outcomes = self.outcomes.lock().unwrap();
+        outcomes.retain(|_, (_, recorded_at)| {
+            now.saturating_duration_since(*recorded_at) <= OUTCOME_TTL
+        });
+        outcomes.insert(quote_id.to_string(), (outcome, now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        instruction::InstructionError,
+        message::{Message, VersionedMessage},
+    };
+
+    fn transaction() -> VersionedTransaction {
+        VersionedTransaction {
+            signatures: vec![Signature::new_unique()],
+            message: VersionedMessage::Legacy(Message::new_with_blockhash(
+                &[],
+                None,
+                &Hash::new_unique(),
+            )),
+        }
+    }
+
+    fn fill_sender(backend: MockRpcBackend) -> (Arc<MockRpcBackend>, FillSender) {
+        let backend = Arc::new(backend);
+        let fill_sender = FillSender::new(backend.clone(), Duration::ZERO, BLOCKHASH_LIFETIME);
+        (backend, fill_sender)
+    }
+
+    #[async_std::test]
+    async fn test_fill_lands_after_resends() {
+        let (backend, fill_sender) = fill_sender(MockRpcBackend::new(Some(Ok(())), 3, usize::MAX));
+        let transaction = transaction();
+        let outcome = fill_sender
+            .send("quote".to_string(), transaction.clone())
+            .await;
+        assert_eq!(
+            outcome,
+            FillOutcome::Landed {
+                signature: transaction.signatures[0]
+            }
+        );
+        assert_eq!(backend.sends.load(Ordering::SeqCst), 3);
+        assert_eq!(fill_sender.outcome("quote"), Some(outcome));
+        assert_eq!(fill_sender.outcome("other"), None);
+    }
+
+    #[async_std::test]
+    async fn test_fill_fails() {
+        let error = TransactionError::InstructionError(0, InstructionError::Custom(1));
+        let (_, fill_sender) = fill_sender(MockRpcBackend::new(Some(Err(error.clone())), 1, 0));
+        let transaction = transaction();
+        assert_eq!(
+            fill_sender
+                .send("quote".to_string(), transaction.clone())
+                .await,
+            FillOutcome::Failed {
+                signature: transaction.signatures[0],
+                error
+            }
+        );
+    }
+
+    #[async_std::test]
+    async fn test_fill_expires() {
+        let (backend, fill_sender) = fill_sender(MockRpcBackend::new(None, 0, 4));
+        let transaction = transaction();
+        assert_eq!(
+            fill_sender
+                .send("quote".to_string(), transaction.clone())
+                .await,
+            FillOutcome::Expired {
+                signature: transaction.signatures[0]
+            }
+        );
+        assert_eq!(backend.sends.load(Ordering::SeqCst), 5);
+    }
+
+    #[async_std::test]
+    async fn test_fill_expires_when_blockhash_checks_fail() {
+        let mut backend = MockRpcBackend::new(None, 0, usize::MAX);
+        backend.fail_blockhash_checks = true;
+        let backend = Arc::new(backend);
+        let fill_sender = FillSender::new(backend.clone(), Duration::ZERO, Duration::ZERO);
+        let transaction = transaction();
+        assert_eq!(
+            fill_sender
+                .send("quote".to_string(), transaction.clone())
+                .await,
+            FillOutcome::Expired {
+                signature: transaction.signatures[0]
+            }
+        );
+        assert_eq!(backend.sends.load(Ordering::SeqCst), 1);
+        assert_eq!(backend.blockhash_checks.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_outcomes_are_evicted() {
+        let (_, fill_sender) = fill_sender(MockRpcBackend::landing());
+        let now = Instant::now();
+        let outcome = FillOutcome::Pending {
+            signature: Signature::new_unique(),
+        };
+        fill_sender.record_at("first", outcome.clone(), now);
+        fill_sender.record_at("second", outcome.clone(), now + OUTCOME_TTL);
+        assert_eq!(fill_sender.outcome("first"), Some(outcome.clone()));
+
+        fill_sender.record_at("third", outcome, now + OUTCOME_TTL + Duration::from_secs(1));
+        assert_eq!(fill_sender.outcome("first"), None);
+        assert_eq!(fill_sender.outcomes.lock().unwrap().len(), 2);
+    }
+}
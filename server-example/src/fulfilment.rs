@@ -0,0 +1,313 @@
+//! Where the webhook stands against the fulfilment rules of the RFQ system
+//!
+//! The rules, as documented in the README:
+//! - the fulfilment rate over the last hour must stay at or above 95%, below it the webhook is
+//!   turned off until Jupiter re-enables it
+//! - a failure suspends the webhook for 5, 10, 20 then 30 minutes as failures accumulate
+//! - each confirmed transaction lowers the accumulated failures by 20%
+//! - swaps held by the taker for more than 20 seconds are not counted
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::fill_sender::FillOutcome;
+
+pub struct FulfilmentRules {
+    pub window: Duration,
+    pub min_fulfilment_rate: f64,
+    /// Suspension applied on a failure, indexed by the accumulated failures
+    pub suspensions: Vec<Duration>,
+    /// Share of the accumulated failures forgiven by a confirmed transaction
+    pub confirmation_discount: f64,
+    pub max_hold: Duration,
+}
+
+impl Default for FulfilmentRules {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(3600),
+            min_fulfilment_rate: 0.95,
+            suspensions: [5, 10, 20, 30]
+                .into_iter()
+                .map(|minutes| Duration::from_secs(minutes * 60))
+                .collect(),
+            confirmation_discount: 0.2,
+            max_hold: Duration::from_secs(20),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapOutcome {
+    /// The fill landed successfully
+    Confirmed,
+    /// The swap was rejected or the fill did not land
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Counted(SwapOutcome),
+    /// Held by the taker for too long
+    Excluded,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "state")]
+pub enum SuspensionState {
+    Active,
+    Suspended {
+        suspension_secs: u64,
+        reenabled_in_secs: u64,
+    },
+    /// Below the minimum fulfilment rate, only re-enabled by Jupiter
+    TurnedOff,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FulfilmentStatus {
+    /// Over the window, 1 without any counted swap
+    pub fulfilment_rate: f64,
+    pub confirmed: usize,
+    pub failed: usize,
+    pub excluded: usize,
+    /// Accumulated failures, lowered by each confirmation
+    pub failure_rate: f64,
+    #[serde(flatten)]
+    pub suspension: SuspensionState,
+}
+
+#[derive(Default)]
+struct TrackerState {
+    events: VecDeque<(Instant, Event)>,
+    failure_rate: f64,
+    /// Last suspension and its end
+    suspension: Option<(Duration, Instant)>,
+    /// Fell below the minimum fulfilment rate, until [`FulfilmentTracker::reset`]
+    turned_off: bool,
+}
+
+impl TrackerState {
+    /// Drop the events that left the window
+    fn prune(&mut self, window: Duration, now: Instant) {
+        while let Some((at, _)) = self.events.front() {
+            if now.saturating_duration_since(*at) <= window {
+                break;
+            }
+            self.events.pop_front();
+        }
+    }
+
+    fn count(&self, event: Event) -> usize {
+        self.events.iter().filter(|(_, e)| *e == event).count()
+    }
+
+    /// 1 without any counted swap
+    fn fulfilment_rate(&self) -> f64 {
+        let confirmed = self.count(Event::Counted(SwapOutcome::Confirmed));
+        match confirmed + self.count(Event::Counted(SwapOutcome::Failed)) {
+            0 => 1.0,
+            total => confirmed as f64 / total as f64,
+        }
+    }
+}
+
+pub struct FulfilmentTracker {
+    rules: FulfilmentRules,
+    state: Mutex<TrackerState>,
+}
+
+impl Default for FulfilmentTracker {
+    fn default() -> Self {
+        Self::new(FulfilmentRules::default())
+    }
+}
+
+impl FulfilmentTracker {
+    pub fn new(rules: FulfilmentRules) -> Self {
+        Self {
+            rules,
+            state: Mutex::new(TrackerState::default()),
+        }
+    }
+
+    /// `held` is the time between the quote and the swap request
+    pub fn record(&self, outcome: SwapOutcome, held: Duration) {
+        self.record_at(outcome, held, Instant::now())
+    }
+
+    /// Record the resolution of an accepted swap, pending fills are ignored
+    pub fn record_fill(&self, outcome: &FillOutcome, held: Duration) {
+        match outcome {
+            FillOutcome::Pending { .. } => {}
+            FillOutcome::Landed { .. } => self.record(SwapOutcome::Confirmed, held),
+            FillOutcome::Failed { .. } | FillOutcome::Expired { .. } => {
+                self.record(SwapOutcome::Failed, held)
+            }
+        }
+    }
+
+    fn record_at(&self, outcome: SwapOutcome, held: Duration, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.prune(self.rules.window, now);
+        if held > self.rules.max_hold {
+            state.events.push_back((now, Event::Excluded));
+            return;
+        }
+        state.events.push_back((now, Event::Counted(outcome)));
+
+        match outcome {
+            SwapOutcome::Confirmed => {
+                state.failure_rate *= 1.0 - self.rules.confirmation_discount;
+            }
+            SwapOutcome::Failed => {
+                state.failure_rate += 1.0;
+                let step =
+                    (state.failure_rate.ceil() as usize).clamp(1, self.rules.suspensions.len());
+                let suspension = self.rules.suspensions[step - 1];
+                // a failure resolved while suspended does not shorten the suspension
+                let until = state
+                    .suspension
+                    .map_or(now + suspension, |(_, until)| until.max(now + suspension));
+                state.suspension = Some((suspension, until));
+            }
+        }
+        if state.fulfilment_rate() < self.rules.min_fulfilment_rate {
+            state.turned_off = true;
+        }
+    }
+
+    /// Start over once Jupiter re-enabled the webhook, the webhook stays turned off until then
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = TrackerState::default();
+    }
+
+    pub fn status(&self) -> FulfilmentStatus {
+        self.status_at(Instant::now())
+    }
+
+    fn status_at(&self, now: Instant) -> FulfilmentStatus {
+        let mut state = self.state.lock().unwrap();
+        state.prune(self.rules.window, now);
+        let fulfilment_rate = state.fulfilment_rate();
+        if fulfilment_rate < self.rules.min_fulfilment_rate {
+            state.turned_off = true;
+        }
+
+        let suspension = match state.suspension {
+            _ if state.turned_off => SuspensionState::TurnedOff,
+            Some((suspension, until)) if until > now => SuspensionState::Suspended {
+                suspension_secs: suspension.as_secs(),
+                reenabled_in_secs: until.duration_since(now).as_secs(),
+            },
+            _ => SuspensionState::Active,
+        };
+
+        FulfilmentStatus {
+            fulfilment_rate,
+            confirmed: state.count(Event::Counted(SwapOutcome::Confirmed)),
+            failed: state.count(Event::Counted(SwapOutcome::Failed)),
+            excluded: state.count(Event::Excluded),
+            failure_rate: state.failure_rate,
+            suspension,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_fulfilment_tracker() {
+        let tracker = FulfilmentTracker::default();
+        let start = Instant::now();
+        for _ in 0..19 {
+            tracker.record_at(SwapOutcome::Confirmed, Duration::ZERO, start);
+        }
+        // held for too long, does not count
+        tracker.record_at(SwapOutcome::Failed, Duration::from_secs(21), start);
+        assert_eq!(tracker.status_at(start).suspension, SuspensionState::Active);
+
+        // 19 out of 20 is still 95%
+        let now = start + MINUTE;
+        tracker.record_at(SwapOutcome::Failed, Duration::from_secs(20), now);
+        let status = tracker.status_at(now);
+        assert_eq!(status.fulfilment_rate, 0.95);
+        assert_eq!(
+            (status.confirmed, status.failed, status.excluded),
+            (19, 1, 1)
+        );
+        assert_eq!(
+            status.suspension,
+            SuspensionState::Suspended {
+                suspension_secs: 300,
+                reenabled_in_secs: 300
+            }
+        );
+        assert_eq!(
+            tracker.status_at(now + 5 * MINUTE).suspension,
+            SuspensionState::Active
+        );
+
+        // confirmations lower the failure rate, a second failure still goes one step further
+        let now = now + 6 * MINUTE;
+        for _ in 0..3 {
+            tracker.record_at(SwapOutcome::Confirmed, Duration::ZERO, now);
+        }
+        assert!((tracker.status_at(now).failure_rate - 0.512).abs() < 1e-9);
+        for _ in 0..17 {
+            tracker.record_at(SwapOutcome::Confirmed, Duration::ZERO, now);
+        }
+        tracker.record_at(SwapOutcome::Failed, Duration::ZERO, now);
+        assert_eq!(
+            tracker.status_at(now).suspension,
+            SuspensionState::Suspended {
+                suspension_secs: 600,
+                reenabled_in_secs: 600
+            }
+        );
+
+        // 39 out of 42 is below 95%
+        tracker.record_at(SwapOutcome::Failed, Duration::ZERO, now);
+        assert_eq!(
+            tracker.status_at(now).suspension,
+            SuspensionState::TurnedOff
+        );
+
+        // the failures leave the window, the webhook stays turned off until re-enabled
+        let status = tracker.status_at(now + 61 * MINUTE);
+        assert_eq!(
+            (status.confirmed, status.failed, status.excluded),
+            (0, 0, 0)
+        );
+        assert_eq!(status.suspension, SuspensionState::TurnedOff);
+
+        tracker.reset();
+        let status = tracker.status_at(now + 61 * MINUTE);
+        assert_eq!(status.suspension, SuspensionState::Active);
+        assert_eq!(status.failure_rate, 0.0);
+    }
+
+    #[test]
+    fn test_fulfilment_tracker_prunes_on_record() {
+        let tracker = FulfilmentTracker::default();
+        let start = Instant::now();
+        for minutes in 0..120 {
+            tracker.record_at(
+                SwapOutcome::Confirmed,
+                Duration::ZERO,
+                start + minutes * MINUTE,
+            );
+        }
+        // only the last hour is kept, without the status ever being read
+        assert_eq!(tracker.state.lock().unwrap().events.len(), 61);
+    }
+}
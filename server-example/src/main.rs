@@ -1,5 +1,13 @@
+mod auth;
 mod config;
+mod deadline;
+mod fill_sender;
+mod fulfilment;
+mod pairs;
+mod pricing;
+mod quote_book;
 mod server;
+mod telemetry;
 
 use config::get_app_config;
 use tracing::level_filters::LevelFilter;
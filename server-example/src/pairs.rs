@@ -0,0 +1,391 @@
+//! Pairs quoted by the maker, loaded from a TOML or JSON file
+//!
+//! Each entry of the file describes an ordered pair: the quoted direction, the size bounds, the
+//! allowed quote types and the pricing parameters, see `pairs.example.toml`. Requests outside of
+//! the configured pairs are answered with a 404 and `/tokens` advertises them, along with the
+//! decimals listed in the file.
+//!
+//! [`LivePairs`] polls the file and reloads it when it changes, a file that fails to load keeps
+//! the previous pairs in place.
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use futures::future::{self, BoxFuture};
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use webhook_api::{
+    enums::QuoteType,
+    tokens::{PairInfo, TokenInfo, TokensResponse},
+    validated::ValidatedQuoteRequest,
+};
+
+use crate::pricing::{QuotePricer, Rate};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+    /// Only from `token_in` to `token_out`
+    #[default]
+    OneWay,
+    /// The reverse pair is quoted as well, at the inverse rate and with the bounds swapped
+    TwoWay,
+}
+
+/// An entry of the pairs file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PairEntry {
+    pub token_in: String,
+    pub token_out: String,
+    #[serde(default)]
+    pub direction: Direction,
+    pub min_amount_in: Option<u64>,
+    pub max_amount_in: Option<u64>,
+    pub min_amount_out: Option<u64>,
+    pub max_amount_out: Option<u64>,
+    #[serde(default = "all_quote_types")]
+    pub quote_types: Vec<QuoteType>,
+    /// Atomic units of `token_out` per atomic unit of `token_in`, as a decimal string
+    pub rate: Option<String>,
+    #[serde(default)]
+    pub spread_bps: u16,
+}
+
+fn all_quote_types() -> Vec<QuoteType> {
+    vec![QuoteType::ExactIn, QuoteType::ExactOut]
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PairsFile {
+    pairs: Vec<PairEntry>,
+    /// Decimals of the tokens, by mint
+    #[serde(default)]
+    decimals: HashMap<String, u8>,
+}
+
+/// Inclusive bounds on an amount, in atomic units
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Bounds {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+impl Bounds {
+    fn new(min: Option<u64>, max: Option<u64>) -> Result<Self> {
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                bail!("Minimum amount {min} is above the maximum amount {max}");
+            }
+        }
+        Ok(Self { min, max })
+    }
+
+    pub fn contains(&self, amount: u64) -> bool {
+        self.min.is_none_or(|min| amount >= min) && self.max.is_none_or(|max| amount <= max)
+    }
+}
+
+/// Quoting parameters of an ordered pair
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pair {
+    pub amount_in: Bounds,
+    pub amount_out: Bounds,
+    pub quote_types: Vec<QuoteType>,
+    /// Pairs without a rate are left to the configured pricer
+    pub rate: Option<Rate>,
+    pub spread_bps: u16,
+}
+
+impl Pair {
+    pub fn allows(&self, quote_type: QuoteType) -> bool {
+        self.quote_types.contains(&quote_type)
+    }
+
+    pub fn within_bounds(&self, amount_in: u64, amount_out: u64) -> bool {
+        self.amount_in.contains(amount_in) && self.amount_out.contains(amount_out)
+    }
+
+    fn reverse(&self) -> Self {
+        Self {
+            amount_in: self.amount_out,
+            amount_out: self.amount_in,
+            quote_types: self.quote_types.clone(),
+            rate: self.rate.map(|rate| rate.inverse()),
+            spread_bps: self.spread_bps,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PairsConfig {
+    pairs: HashMap<(Pubkey, Pubkey), Pair>,
+    decimals: HashMap<Pubkey, u8>,
+}
+
+impl PairsConfig {
+    /// Parse the content of `path`, as JSON for a `.json` file and TOML otherwise
+    pub fn parse(path: &Path, contents: &str) -> Result<Self> {
+        let file: PairsFile = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            serde_json::from_str(contents)?
+        } else {
+            toml::from_str(contents)?
+        };
+
+        let mut pairs = HashMap::new();
+        for entry in file.pairs {
+            let token_in = entry
+                .token_in
+                .parse::<Pubkey>()
+                .with_context(|| format!("Invalid token {}", entry.token_in))?;
+            let token_out = entry
+                .token_out
+                .parse::<Pubkey>()
+                .with_context(|| format!("Invalid token {}", entry.token_out))?;
+            let pair = Pair {
+                amount_in: Bounds::new(entry.min_amount_in, entry.max_amount_in)?,
+                amount_out: Bounds::new(entry.min_amount_out, entry.max_amount_out)?,
+                quote_types: entry.quote_types,
+                rate: entry.rate.as_deref().map(str::parse).transpose()?,
+                spread_bps: entry.spread_bps,
+            };
+            if entry.direction == Direction::TwoWay
+                && pairs
+                    .insert((token_out, token_in), pair.reverse())
+                    .is_some()
+            {
+                bail!("Pair {token_out}/{token_in} is configured twice");
+            }
+            if pairs.insert((token_in, token_out), pair).is_some() {
+                bail!("Pair {token_in}/{token_out} is configured twice");
+            }
+        }
+        let decimals = file
+            .decimals
+            .into_iter()
+            .map(|(mint, decimals)| {
+                let mint = mint
+                    .parse::<Pubkey>()
+                    .with_context(|| format!("Invalid token {mint}"))?;
+                Ok((mint, decimals))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { pairs, decimals })
+    }
+
+    pub fn get(&self, token_in: &Pubkey, token_out: &Pubkey) -> Option<&Pair> {
+        self.pairs.get(&(*token_in, *token_out))
+    }
+
+    /// The configured pairs and their tokens, as advertised on `/tokens`
+    pub fn advertisement(&self) -> TokensResponse {
+        let tokens = self
+            .pairs
+            .keys()
+            .flat_map(|(token_in, token_out)| [*token_in, *token_out])
+            .map(|mint| (mint.to_string(), self.decimals.get(&mint).copied()))
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .map(|(mint, decimals)| TokenInfo { mint, decimals })
+            .collect();
+        let to_string = |amount: Option<u64>| amount.map(|amount| amount.to_string());
+        let mut pairs = self
+            .pairs
+            .iter()
+            .map(|((token_in, token_out), pair)| PairInfo {
+                token_in: token_in.to_string(),
+                token_out: token_out.to_string(),
+                quote_types: pair.quote_types.clone(),
+                min_amount_in: to_string(pair.amount_in.min),
+                max_amount_in: to_string(pair.amount_in.max),
+                min_amount_out: to_string(pair.amount_out.min),
+                max_amount_out: to_string(pair.amount_out.max),
+            })
+            .collect::<Vec<_>>();
+        // the ETag of the advertisement must not depend on the order of the map
+        pairs.sort_by(|a, b| (&a.token_in, &a.token_out).cmp(&(&b.token_in, &b.token_out)));
+        TokensResponse::new(tokens, pairs)
+    }
+
+    /// Configured rates, by token in and token out
+    pub fn rates(&self) -> impl Iterator<Item = (Pubkey, Pubkey, Rate)> + '_ {
+        self.pairs
+            .iter()
+            .filter_map(|((token_in, token_out), pair)| Some((*token_in, *token_out, pair.rate?)))
+    }
+}
+
+/// The pairs of a file, reloaded when the file changes
+pub struct LivePairs {
+    path: PathBuf,
+    contents: Mutex<String>,
+    config: RwLock<Arc<PairsConfig>>,
+}
+
+impl LivePairs {
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config = PairsConfig::parse(&path, &contents)
+            .with_context(|| format!("Invalid pairs config {}", path.display()))?;
+        Ok(Self {
+            path,
+            contents: Mutex::new(contents),
+            config: RwLock::new(Arc::new(config)),
+        })
+    }
+
+    pub fn current(&self) -> Arc<PairsConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Load the file again if its content changed, `true` when new pairs are in place
+    pub fn reload(&self) -> Result<bool> {
+        let contents = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}", self.path.display()))?;
+        let mut current_contents = self.contents.lock().unwrap();
+        if *current_contents == contents {
+            return Ok(false);
+        }
+        // do not parse the same broken file on every poll
+        let config = PairsConfig::parse(&self.path, &contents);
+        *current_contents = contents;
+        let config =
+            config.with_context(|| format!("Invalid pairs config {}", self.path.display()))?;
+        *self.config.write().unwrap() = Arc::new(config);
+        Ok(true)
+    }
+
+    /// Check the file for changes every `interval`
+    pub fn spawn_reload(self: &Arc<Self>, interval: Duration) {
+        let pairs = self.clone();
+        async_std::task::spawn(async move {
+            loop {
+                async_std::task::sleep(interval).await;
+                match pairs.reload() {
+                    Ok(true) => tracing::info!("Reloaded pairs from {}", pairs.path.display()),
+                    Ok(false) => {}
+                    Err(error) => tracing::warn!("Keeping the previous pairs: {error:#}"),
+                }
+            }
+        });
+    }
+}
+
+/// Quotes the configured pairs at their rate minus their spread, pairs without a rate are not
+/// quoted
+pub struct PairsPricer {
+    pub pairs: Arc<LivePairs>,
+}
+
+impl QuotePricer for PairsPricer {
+    fn price<'a>(
+        &'a self,
+        request: &'a ValidatedQuoteRequest,
+    ) -> BoxFuture<'a, Result<Option<u64>>> {
+        let amount = self
+            .pairs
+            .current()
+            .get(&request.token_in, &request.token_out)
+            .and_then(|pair| {
+                pair.rate?
+                    .quote(request.quote_type, request.amount, pair.spread_bps)
+            });
+        Box::pin(future::ready(Ok(amount)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const WSOL: &str = "So11111111111111111111111111111111111111112";
+
+    fn pairs_toml(max_amount_in: u64) -> String {
+        format!(
+            r#"
+            [[pairs]]
+            token_in = "{USDC}"
+            token_out = "{WSOL}"
+            min_amount_in = 100_000_000
+            max_amount_in = {max_amount_in}
+            quote_types = ["exactIn"]
+            rate = "5"
+            spread_bps = 10
+            "#
+        )
+    }
+
+    #[test]
+    fn test_pairs_config() {
+        let (usdc, wsol) = (USDC.parse().unwrap(), WSOL.parse().unwrap());
+        let toml = format!("{}\n[decimals]\n{USDC} = 6", pairs_toml(1_000_000_000));
+        let config = PairsConfig::parse(Path::new("pairs.toml"), &toml).unwrap();
+        let pair = config.get(&usdc, &wsol).unwrap();
+        assert!(pair.allows(QuoteType::ExactIn));
+        assert!(!pair.allows(QuoteType::ExactOut));
+        assert!(pair.within_bounds(100_000_000, 1));
+        assert!(!pair.within_bounds(10_000_000, 1));
+        assert!(!pair.within_bounds(1_000_000_001, 1));
+        // one way only
+        assert!(config.get(&wsol, &usdc).is_none());
+        let advertisement = config.advertisement();
+        assert_eq!(
+            advertisement.mints(),
+            vec![USDC.to_string(), WSOL.to_string()]
+        );
+        assert_eq!(advertisement.tokens[0].decimals, Some(6));
+        assert_eq!(advertisement.tokens[1].decimals, None);
+        assert_eq!(advertisement.pairs.len(), 1);
+        assert_eq!(
+            advertisement.pairs[0].max_amount_in.as_deref(),
+            Some("1000000000")
+        );
+
+        let json = format!(
+            r#"{{"pairs": [{{"token_in": "{USDC}", "token_out": "{WSOL}", "direction": "twoWay", "max_amount_out": 10, "rate": "0.5"}}]}}"#
+        );
+        let config = PairsConfig::parse(Path::new("pairs.json"), &json).unwrap();
+        let reverse = config.get(&wsol, &usdc).unwrap();
+        assert_eq!(reverse.amount_in.max, Some(10));
+        assert_eq!(reverse.rate, Some(Rate::new(10, 5).unwrap()));
+        assert!(reverse.allows(QuoteType::ExactOut));
+
+        let inverted_bounds = pairs_toml(1).replace("100_000_000", "2");
+        assert!(PairsConfig::parse(Path::new("pairs.toml"), &inverted_bounds).is_err());
+        let duplicated = pairs_toml(1_000_000_000).repeat(2);
+        assert!(PairsConfig::parse(Path::new("pairs.toml"), &duplicated).is_err());
+    }
+
+    #[test]
+    fn test_live_pairs_reload() {
+        let (usdc, wsol) = (USDC.parse().unwrap(), WSOL.parse().unwrap());
+        let path = std::env::temp_dir().join(format!("pairs-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, pairs_toml(1_000_000_000)).unwrap();
+        let pairs = LivePairs::load(&path).unwrap();
+        assert!(!pairs.reload().unwrap());
+
+        std::fs::write(&path, pairs_toml(200_000_000)).unwrap();
+        assert!(pairs.reload().unwrap());
+        let max_amount_in =
+            |pairs: &LivePairs| pairs.current().get(&usdc, &wsol).unwrap().amount_in.max;
+        assert_eq!(max_amount_in(&pairs), Some(200_000_000));
+
+        // a broken file keeps the previous pairs
+        std::fs::write(&path, "pairs = 1").unwrap();
+        assert!(pairs.reload().is_err());
+        assert!(!pairs.reload().unwrap());
+        assert_eq!(max_amount_in(&pairs), Some(200_000_000));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,344 @@
+//! Pricing of quote requests
+//!
+//! A [`QuotePricer`] computes the amount the maker is willing to trade for a quote request, the
+//! rest of the quote handler (validation, response, bookkeeping) does not depend on how the price
+//! is obtained.
+//!
+//! Amounts are always rounded in favour of the maker: the output amount of an exact-in quote is
+//! rounded down and the input amount of an exact-out quote is rounded up.
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use futures::future::{self, BoxFuture};
+use solana_sdk::pubkey::Pubkey;
+use webhook_api::{enums::QuoteType, validated::ValidatedQuoteRequest};
+
+const BPS_DENOMINATOR: u128 = 10_000;
+
+pub trait QuotePricer: Send + Sync {
+    /// The output amount of an exact-in request, or the input amount of an exact-out request
+    ///
+    /// `None` means the maker does not quote the request, which is answered with a 404
+    fn price<'a>(
+        &'a self,
+        request: &'a ValidatedQuoteRequest,
+    ) -> BoxFuture<'a, Result<Option<u64>>>;
+}
+
+/// Exchange rate in atomic units of the output token per atomic unit of the input token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    numerator: u128,
+    denominator: u128,
+}
+
+impl Rate {
+    pub fn new(numerator: u128, denominator: u128) -> Result<Self> {
+        if numerator == 0 || denominator == 0 {
+            bail!("Rate must be strictly positive");
+        }
+        Ok(Self {
+            numerator,
+            denominator,
+        })
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            numerator: self.denominator,
+            denominator: self.numerator,
+        }
+    }
+
+    /// Quote `amount` at this rate, worsened by `spread_bps` for the taker
+    ///
+    /// `None` when the quoted amount rounds to zero or does not fit in a u64
+    pub fn quote(&self, quote_type: QuoteType, amount: u64, spread_bps: u16) -> Option<u64> {
+        let kept_bps = BPS_DENOMINATOR.checked_sub(u128::from(spread_bps))?;
+        let amount = u128::from(amount);
+        let quoted_amount = match quote_type {
+            // amount out = amount in * rate * (1 - spread), rounded down
+            QuoteType::ExactIn => amount
+                .checked_mul(self.numerator)?
+                .checked_mul(kept_bps)?
+                .checked_div(self.denominator.checked_mul(BPS_DENOMINATOR)?)?,
+            // amount in = amount out / (rate * (1 - spread)), rounded up
+            QuoteType::ExactOut => amount
+                .checked_mul(self.denominator)?
+                .checked_mul(BPS_DENOMINATOR)?
+                .div_ceil(self.numerator.checked_mul(kept_bps)?),
+        };
+        u64::try_from(quoted_amount)
+            .ok()
+            .filter(|quoted_amount| *quoted_amount > 0)
+    }
+}
+
+/// Parse a decimal such as `25.0006` without losing precision
+impl FromStr for Rate {
+    type Err = anyhow::Error;
+
+    fn from_str(rate: &str) -> Result<Self> {
+        let (integer, fraction) = rate.split_once('.').unwrap_or((rate, ""));
+        if fraction.len() > 18 {
+            bail!("Rate {rate} has more than 18 decimals");
+        }
+        let numerator = format!("{integer}{fraction}")
+            .parse::<u128>()
+            .with_context(|| format!("Invalid rate {rate}"))?;
+        Self::new(numerator, 10u128.pow(fraction.len() as u32))
+    }
+}
+
+/// Fixed rate and spread for a directed pair, parsed from `TOKEN_IN:TOKEN_OUT:RATE:SPREAD_BPS`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairRate {
+    pub token_in: Pubkey,
+    pub token_out: Pubkey,
+    pub rate: Rate,
+    pub spread_bps: u16,
+}
+
+impl FromStr for PairRate {
+    type Err = anyhow::Error;
+
+    fn from_str(pair_rate: &str) -> Result<Self> {
+        let [token_in, token_out, rate, spread_bps] = pair_rate
+            .split(':')
+            .collect::<Vec<_>>()
+            .try_into()
+            .map_err(|_| anyhow!("Expected TOKEN_IN:TOKEN_OUT:RATE:SPREAD_BPS, got {pair_rate}"))?;
+        Ok(Self {
+            token_in: token_in.parse()?,
+            token_out: token_out.parse()?,
+            rate: rate.parse()?,
+            spread_bps: spread_bps.parse()?,
+        })
+    }
+}
+
+/// Always quotes the same amount, whatever the pair and size
+pub struct StaticPricer {
+    pub amount: u64,
+}
+
+impl QuotePricer for StaticPricer {
+    fn price<'a>(
+        &'a self,
+        _request: &'a ValidatedQuoteRequest,
+    ) -> BoxFuture<'a, Result<Option<u64>>> {
+        Box::pin(future::ready(Ok(Some(self.amount))))
+    }
+}
+
+/// Quotes configured pairs at a fixed rate minus a spread, other pairs are not quoted
+pub struct FixedRatePricer {
+    pairs: HashMap<(Pubkey, Pubkey), PairRate>,
+}
+
+impl FixedRatePricer {
+    pub fn new(pair_rates: impl IntoIterator<Item = PairRate>) -> Self {
+        Self {
+            pairs: pair_rates
+                .into_iter()
+                .map(|pair_rate| ((pair_rate.token_in, pair_rate.token_out), pair_rate))
+                .collect(),
+        }
+    }
+}
+
+impl QuotePricer for FixedRatePricer {
+    fn price<'a>(
+        &'a self,
+        request: &'a ValidatedQuoteRequest,
+    ) -> BoxFuture<'a, Result<Option<u64>>> {
+        let amount = self
+            .pairs
+            .get(&(request.token_in, request.token_out))
+            .and_then(|pair_rate| {
+                pair_rate
+                    .rate
+                    .quote(request.quote_type, request.amount, pair_rate.spread_bps)
+            });
+        Box::pin(future::ready(Ok(amount)))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedPrice {
+    rate: Rate,
+    updated_at: Instant,
+}
+
+/// Latest mid prices, updated by whatever feed the maker runs in process
+#[derive(Default)]
+pub struct PriceCache {
+    prices: RwLock<HashMap<(Pubkey, Pubkey), CachedPrice>>,
+}
+
+impl PriceCache {
+    /// Set the mid price of `base` in `quote` tokens, the inverse pair is updated as well
+    pub fn update(&self, base: Pubkey, quote: Pubkey, rate: Rate) {
+        let updated_at = Instant::now();
+        let mut prices = self.prices.write().unwrap();
+        prices.insert((base, quote), CachedPrice { rate, updated_at });
+        prices.insert(
+            (quote, base),
+            CachedPrice {
+                rate: rate.inverse(),
+                updated_at,
+            },
+        );
+    }
+
+    /// The mid price of `base` in `quote` tokens, if updated within `max_age`
+    pub fn get(&self, base: &Pubkey, quote: &Pubkey, max_age: Option<Duration>) -> Option<Rate> {
+        let prices = self.prices.read().unwrap();
+        let cached_price = prices.get(&(*base, *quote))?;
+        if max_age.is_some_and(|max_age| cached_price.updated_at.elapsed() > max_age) {
+            return None;
+        }
+        Some(cached_price.rate)
+    }
+}
+
+/// Quotes the mid price from the cache minus a fixed number of basis points
+pub struct MidPricePricer {
+    pub cache: Arc<PriceCache>,
+    pub bps: u16,
+    /// Prices older than this are considered stale and not quoted
+    pub max_age: Option<Duration>,
+}
+
+impl QuotePricer for MidPricePricer {
+    fn price<'a>(
+        &'a self,
+        request: &'a ValidatedQuoteRequest,
+    ) -> BoxFuture<'a, Result<Option<u64>>> {
+        let amount = self
+            .cache
+            .get(&request.token_in, &request.token_out, self.max_age)
+            .and_then(|rate| rate.quote(request.quote_type, request.amount, self.bps));
+        Box::pin(future::ready(Ok(amount)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webhook_api::enums::Protocol;
+
+    fn quote_request(
+        token_in: Pubkey,
+        token_out: Pubkey,
+        quote_type: QuoteType,
+        amount: u64,
+    ) -> ValidatedQuoteRequest {
+        ValidatedQuoteRequest {
+            request_id: "request".to_string(),
+            quote_id: "quote".to_string(),
+            token_in,
+            amount,
+            token_out,
+            quote_type,
+            protocol: Protocol::V1,
+            taker: None,
+            suggested_prioritization_fees: None,
+            fee_bps: 0,
+            is_wsol: None,
+            receiver: None,
+        }
+    }
+
+    #[test]
+    fn test_rate_rounding() {
+        let rate = "0.3".parse::<Rate>().unwrap();
+        assert_eq!(rate, Rate::new(3, 10).unwrap());
+
+        // 10 * 0.3 * 0.99 = 2.97
+        assert_eq!(rate.quote(QuoteType::ExactIn, 10, 100), Some(2));
+        // 10 / (0.3 * 0.99) = 33.67
+        assert_eq!(rate.quote(QuoteType::ExactOut, 10, 100), Some(34));
+        // Exact results are not rounded
+        assert_eq!(rate.quote(QuoteType::ExactIn, 100, 0), Some(30));
+        assert_eq!(rate.quote(QuoteType::ExactOut, 30, 0), Some(100));
+
+        assert_eq!(rate.quote(QuoteType::ExactIn, 3, 0), None);
+        assert_eq!(rate.quote(QuoteType::ExactIn, 10, 10_000), None);
+        assert_eq!(
+            "1000"
+                .parse::<Rate>()
+                .unwrap()
+                .quote(QuoteType::ExactIn, u64::MAX, 0),
+            None
+        );
+        assert!("0".parse::<Rate>().is_err());
+        assert!("1.5.2".parse::<Rate>().is_err());
+    }
+
+    #[test]
+    fn test_fixed_rate_pricer() {
+        let (jup, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let pair_rate = format!("{jup}:{usdc}:0.025:10")
+            .parse::<PairRate>()
+            .unwrap();
+        let pricer = FixedRatePricer::new([pair_rate]);
+
+        let request = quote_request(jup, usdc, QuoteType::ExactIn, 1_000_000_000);
+        assert_eq!(
+            futures::executor::block_on(pricer.price(&request)).unwrap(),
+            Some(24_975_000)
+        );
+        let request = quote_request(jup, usdc, QuoteType::ExactOut, 24_975_000);
+        assert_eq!(
+            futures::executor::block_on(pricer.price(&request)).unwrap(),
+            Some(1_000_000_000)
+        );
+
+        // Only the configured direction is quoted
+        let request = quote_request(usdc, jup, QuoteType::ExactIn, 1_000_000);
+        assert_eq!(
+            futures::executor::block_on(pricer.price(&request)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_mid_price_pricer() {
+        let (sol, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let cache = Arc::new(PriceCache::default());
+        let pricer = MidPricePricer {
+            cache: cache.clone(),
+            bps: 50,
+            max_age: None,
+        };
+        let request = quote_request(usdc, sol, QuoteType::ExactIn, 200_000_000);
+        assert_eq!(
+            futures::executor::block_on(pricer.price(&request)).unwrap(),
+            None
+        );
+
+        // 1 SOL = 200 USDC
+        cache.update(sol, usdc, "0.2".parse().unwrap());
+        assert_eq!(
+            futures::executor::block_on(pricer.price(&request)).unwrap(),
+            Some(995_000_000)
+        );
+
+        let stale_pricer = MidPricePricer {
+            cache,
+            bps: 50,
+            max_age: Some(Duration::ZERO),
+        };
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(
+            futures::executor::block_on(stale_pricer.price(&request)).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! Quotes issued by the webhook, so that swap requests can be checked against them
+//!
+//! The RFQ system lets the taker sign the transaction for at most 55 seconds after the quote, an
+//! entry is evicted once that time has elapsed. A quote is consumed by its first accepted swap,
+//! until then the maker would co-sign a second fill of the same quote. Quoting again under the
+//! ids of an issued quote replaces it, under the ids of a swapped one is refused.
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use order_engine_sdk::{fill::Order, trailer::FillTrailer};
+use solana_sdk::pubkey::Pubkey;
+use webhook_api::{enums::QuoteType, validated::ValidatedQuoteResponse};
+
+/// Time to live of the transaction built by the RFQ system from a quote
+pub const QUOTE_TTL: Duration = Duration::from_secs(55);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QuoteKey {
+    pub request_id: String,
+    pub quote_id: String,
+}
+
+impl QuoteKey {
+    pub fn new(request_id: impl Into<String>, quote_id: impl Into<String>) -> Self {
+        Self {
+            request_id: request_id.into(),
+            quote_id: quote_id.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IssuedQuote {
+    pub quote: ValidatedQuoteResponse,
+    pub fee_bps: u16,
+    pub issued_at: Instant,
+}
+
+impl IssuedQuote {
+    /// The order the fill transaction must match
+    ///
+    /// The expiry is chosen by the RFQ system when building the transaction, it is taken from the
+    /// transaction and checked separately
+    pub fn order(&self, taker: Pubkey, expire_at: i64) -> Order {
+        Order {
+            taker: self.quote.taker.unwrap_or(taker),
+            maker: self.quote.maker,
+            in_amount: self.quote.amount_in,
+            input_mint: self.quote.token_in,
+            out_amount: self.quote.amount_out,
+            output_mint: self.quote.token_out,
+            expire_at,
+            expected_trailer: Some(FillTrailer::new(
+                self.fee_bps,
+                self.quote.quote_type == QuoteType::ExactOut,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum QuoteLookupError {
+    NotFound,
+    Expired,
+    /// A swap of the quote was already accepted
+    AlreadySwapped,
+}
+
+/// Once swapped, only the issue time of a quote is kept to recognise a repeated swap
+#[derive(Debug)]
+enum BookEntry {
+    Issued(Box<IssuedQuote>),
+    Swapped { issued_at: Instant },
+}
+
+impl BookEntry {
+    fn issued_at(&self) -> Instant {
+        match self {
+            Self::Issued(issued_quote) => issued_quote.issued_at,
+            Self::Swapped { issued_at } => *issued_at,
+        }
+    }
+}
+
+pub struct QuoteBook {
+    ttl: Duration,
+    quotes: Mutex<HashMap<QuoteKey, BookEntry>>,
+}
+
+impl Default for QuoteBook {
+    fn default() -> Self {
+        Self::new(QUOTE_TTL)
+    }
+}
+
+impl QuoteBook {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            quotes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record an issued quote, evicting the ones that can no longer be swapped
+    ///
+    /// Returns `false` and leaves the book unchanged when a quote with the same ids was swapped
+    /// and has not expired yet, replacing it would let it be swapped again.
+    #[must_use]
+    pub fn insert(&self, quote: ValidatedQuoteResponse, fee_bps: u16) -> bool {
+        self.insert_at(quote, fee_bps, Instant::now())
+    }
+
+    fn insert_at(&self, quote: ValidatedQuoteResponse, fee_bps: u16, now: Instant) -> bool {
+        let mut quotes = self.quotes.lock().unwrap();
+        quotes.retain(|_, entry| !self.is_expired(entry, now));
+        let key = QuoteKey::new(&quote.request_id, &quote.quote_id);
+        if let Some(BookEntry::Swapped { .. }) = quotes.get(&key) {
+            return false;
+        }
+        quotes.insert(
+            key,
+            BookEntry::Issued(Box::new(IssuedQuote {
+                quote,
+                fee_bps,
+                issued_at: now,
+            })),
+        );
+        true
+    }
+
+    pub fn get(&self, key: &QuoteKey) -> Result<IssuedQuote, QuoteLookupError> {
+        self.get_at(key, Instant::now())
+    }
+
+    fn get_at(&self, key: &QuoteKey, now: Instant) -> Result<IssuedQuote, QuoteLookupError> {
+        let mut quotes = self.quotes.lock().unwrap();
+        match self.entry(&mut quotes, key, now)? {
+            BookEntry::Issued(issued_quote) => Ok(issued_quote.as_ref().clone()),
+            BookEntry::Swapped { .. } => Err(QuoteLookupError::AlreadySwapped),
+        }
+    }
+
+    /// Mark the quote as swapped, once the swap of its fill is accepted
+    ///
+    /// Only one swap of a quote can consume it, a repeated one fails with
+    /// [`QuoteLookupError::AlreadySwapped`] until the quote would have expired.
+    pub fn consume(&self, key: &QuoteKey) -> Result<(), QuoteLookupError> {
+        self.consume_at(key, Instant::now())
+    }
+
+    fn consume_at(&self, key: &QuoteKey, now: Instant) -> Result<(), QuoteLookupError> {
+        let mut quotes = self.quotes.lock().unwrap();
+        let entry = self.entry(&mut quotes, key, now)?;
+        if let BookEntry::Swapped { .. } = entry {
+            return Err(QuoteLookupError::AlreadySwapped);
+        }
+        *entry = BookEntry::Swapped {
+            issued_at: entry.issued_at(),
+        };
+        Ok(())
+    }
+
+    /// Time since the quote was issued, `None` for a quote not in the book
+    pub fn held(&self, key: &QuoteKey) -> Option<Duration> {
+        let quotes = self.quotes.lock().unwrap();
+        quotes.get(key).map(|entry| entry.issued_at().elapsed())
+    }
+
+    /// The entry of a quote that is still valid, an expired one is evicted
+    fn entry<'a>(
+        &self,
+        quotes: &'a mut HashMap<QuoteKey, BookEntry>,
+        key: &QuoteKey,
+        now: Instant,
+    ) -> Result<&'a mut BookEntry, QuoteLookupError> {
+        let entry = quotes.get(key).ok_or(QuoteLookupError::NotFound)?;
+        if self.is_expired(entry, now) {
+            quotes.remove(key);
+            return Err(QuoteLookupError::Expired);
+        }
+        quotes.get_mut(key).ok_or(QuoteLookupError::NotFound)
+    }
+
+    fn is_expired(&self, entry: &BookEntry, now: Instant) -> bool {
+        now.saturating_duration_since(entry.issued_at()) > self.ttl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webhook_api::enums::Protocol;
+
+    fn quote(request_id: &str) -> ValidatedQuoteResponse {
+        ValidatedQuoteResponse {
+            request_id: request_id.to_string(),
+            quote_id: "quote".to_string(),
+            token_in: Pubkey::new_unique(),
+            amount_in: 100,
+            token_out: Pubkey::new_unique(),
+            quote_type: QuoteType::ExactOut,
+            protocol: Protocol::V1,
+            amount_out: 200,
+            maker: Pubkey::new_unique(),
+            prioritization_fee_to_use: None,
+            taker: None,
+            receiver: None,
+        }
+    }
+
+    #[test]
+    fn test_quote_book_ttl() {
+        let quote_book = QuoteBook::default();
+        let now = Instant::now();
+        assert!(quote_book.insert_at(quote("first"), 2, now));
+
+        let key = QuoteKey::new("first", "quote");
+        let issued_quote = quote_book.get_at(&key, now + QUOTE_TTL).unwrap();
+        let taker = Pubkey::new_unique();
+        let order = issued_quote.order(taker, 1000);
+        assert_eq!(order.taker, taker);
+        assert_eq!(order.in_amount, 100);
+        assert_eq!(order.expected_trailer, Some(FillTrailer::new(2, true)));
+
+        assert_eq!(
+            QuoteLookupError::NotFound,
+            quote_book
+                .get_at(&QuoteKey::new("first", "other"), now)
+                .unwrap_err()
+        );
+        assert_eq!(
+            QuoteLookupError::Expired,
+            quote_book
+                .get_at(&key, now + QUOTE_TTL + Duration::from_secs(1))
+                .unwrap_err()
+        );
+        assert_eq!(quote_book.quotes.lock().unwrap().len(), 0);
+
+        // Inserting evicts the expired quotes
+        assert!(quote_book.insert_at(quote("first"), 2, now));
+        assert!(quote_book.insert_at(quote("second"), 2, now + QUOTE_TTL * 2));
+        assert_eq!(quote_book.quotes.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_quote_book_consume() {
+        let quote_book = QuoteBook::default();
+        let now = Instant::now();
+        assert!(quote_book.insert_at(quote("first"), 2, now));
+
+        let key = QuoteKey::new("first", "quote");
+        assert_eq!(quote_book.consume_at(&key, now), Ok(()));
+        assert_eq!(
+            QuoteLookupError::AlreadySwapped,
+            quote_book.get_at(&key, now).unwrap_err()
+        );
+        assert_eq!(
+            Err(QuoteLookupError::AlreadySwapped),
+            quote_book.consume_at(&key, now + QUOTE_TTL)
+        );
+        assert!(quote_book.held(&key).is_some());
+
+        // The swapped quote is evicted like any other
+        assert_eq!(
+            Err(QuoteLookupError::Expired),
+            quote_book.consume_at(&key, now + QUOTE_TTL + Duration::from_secs(1))
+        );
+        assert_eq!(
+            Err(QuoteLookupError::NotFound),
+            quote_book.consume_at(&QuoteKey::new("second", "quote"), now)
+        );
+    }
+
+    #[test]
+    fn test_quote_book_requote() {
+        let quote_book = QuoteBook::default();
+        let now = Instant::now();
+        let key = QuoteKey::new("first", "quote");
+        assert!(quote_book.insert_at(quote("first"), 2, now));
+        assert_eq!(quote_book.consume_at(&key, now), Ok(()));
+
+        // Quoting again under the same ids does not revive the swapped quote
+        assert!(!quote_book.insert_at(quote("first"), 2, now + Duration::from_secs(1)));
+        assert_eq!(
+            Err(QuoteLookupError::AlreadySwapped),
+            quote_book.consume_at(&key, now + Duration::from_secs(1))
+        );
+
+        // while an issued quote is replaced, and still swapped once
+        assert!(quote_book.insert_at(quote("second"), 2, now));
+        assert!(quote_book.insert_at(quote("second"), 5, now));
+        let key = QuoteKey::new("second", "quote");
+        assert_eq!(quote_book.get_at(&key, now).unwrap().fee_bps, 5);
+        assert_eq!(quote_book.consume_at(&key, now), Ok(()));
+        assert!(!quote_book.insert_at(quote("second"), 2, now));
+        assert_eq!(
+            Err(QuoteLookupError::AlreadySwapped),
+            quote_book.consume_at(&key, now)
+        );
+
+        // The ids can be quoted again once the quote expired
+        assert!(quote_book.insert_at(quote("first"), 2, now + QUOTE_TTL + Duration::from_secs(1)));
+    }
+}
@@ -7,10 +7,19 @@
 ///
 ///
 use anyhow::Result;
-use order_engine_sdk::transaction::{
-    deserialize_transaction_base64_into_transaction_details, TransactionDetails,
+use metrics_exporter_prometheus::PrometheusHandle;
+use order_engine_sdk::{
+    decode::decode_fill_sanitized_message,
+    economics::{FillCostLimits, FillEconomics},
+    fill::validate_fill_sanitized_message,
+    policy::FillValidationPolicy,
+    sign::sign_fill_transaction,
+    transaction::{
+        deserialize_transaction_base64, fetch_address_lookup_table_accounts,
+        versioned_message_to_sanitized_message,
+    },
 };
-use solana_rpc_client::rpc_client::SerializableTransaction;
+use solana_rpc_client::{nonblocking::rpc_client::RpcClient, rpc_client::SerializableTransaction};
 use solana_sdk::{
     signature::Keypair,
     signer::{EncodableKey, Signer},
@@ -20,12 +29,17 @@ use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 use utoipauto::utoipauto;
 
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
 
 use axum::{
-    extract::{rejection::JsonRejection, Query, State},
+    extract::{rejection::JsonRejection, Path, Query, State},
     http::{HeaderMap, StatusCode},
-    response::IntoResponse,
+    middleware,
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
@@ -39,19 +53,39 @@ use tower_http::{
 use tracing::Level as TraceLevel;
 
 use webhook_api::{
-    enums::{QuoteType, RejectionReason, SwapState},
+    enums::{ErrorCode, QuoteType, RejectionReason, SwapState, SUPPORTED_PROTOCOLS},
     requests::*,
     responses::*,
+    tokens::{TokenInfo, TokensRequest, TokensResponse},
+    validated::{ValidatedQuoteRequest, ValidatedQuoteResponse, ValidationError},
 };
 
-use crate::config::Config;
+use crate::{
+    auth::{require_api_key, ApiKeys},
+    config::{Config, PricerKind},
+    deadline::enforce_deadline,
+    fill_sender::{
+        FillOutcome, FillSender, MockRpcBackend, RpcBackend, BLOCKHASH_LIFETIME,
+        DEFAULT_RESEND_INTERVAL,
+    },
+    fulfilment::{FulfilmentStatus, FulfilmentTracker, SwapOutcome},
+    pairs::{LivePairs, PairsPricer},
+    pricing::{FixedRatePricer, MidPricePricer, PriceCache, QuotePricer, StaticPricer},
+    quote_book::{QuoteBook, QuoteKey, QuoteLookupError},
+    telemetry::{self, record_swap_outcome, track_quote_responses, QuoteTimer},
+};
 
 #[derive(Error, Debug)]
 pub enum ApiError {
     #[error("API method not found")]
     NotFound(),
+    #[error("Invalid or missing API key")]
+    Unauthorized(),
     #[error("{0}")]
     BadRequest(String),
+    // handle requests that are well-formed json but carry invalid values
+    #[error(transparent)]
+    ValidationError(#[from] ValidationError),
     // handle errors for incoming invalid json
     #[error(transparent)]
     JsonExtractorRejection(#[from] JsonRejection),
@@ -62,23 +96,44 @@ pub enum ApiError {
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> axum::response::Response {
-        let (status, message) = match self {
+        let (status, code, message) = match self {
             Self::JsonExtractorRejection(json_rejection) => {
                 tracing::error!("JsonExtractorRejection: {:?}", json_rejection);
-                (json_rejection.status(), json_rejection.body_text())
+                (
+                    json_rejection.status(),
+                    ErrorCode::InvalidRequest,
+                    json_rejection.body_text(),
+                )
             }
-            Self::NotFound() => (StatusCode::NOT_FOUND, self.to_string()),
+            Self::NotFound() => (StatusCode::NOT_FOUND, ErrorCode::NotFound, self.to_string()),
+            Self::Unauthorized() => (
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::Unauthorized,
+                self.to_string(),
+            ),
             Self::BadRequest(error) => {
                 tracing::error!("BadRequest: {:?}", error);
-                (StatusCode::BAD_REQUEST, error)
+                (StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest, error)
+            }
+            Self::ValidationError(error) => {
+                tracing::error!("ValidationError: {:?}", error);
+                (
+                    StatusCode::BAD_REQUEST,
+                    ErrorCode::InvalidRequest,
+                    error.to_string(),
+                )
             }
             Self::GenericError(error) => {
                 tracing::error!("GenericError: {:?}", error);
-                (StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::InternalError,
+                    error.to_string(),
+                )
             }
         };
 
-        (status, Json(ErrorResponse::from(message))).into_response()
+        (status, Json(ErrorResponse::new(code, message))).into_response()
     }
 }
 
@@ -130,71 +185,99 @@ async fn example_quote(
         headers
     );
 
-    let is_input_mint_supported = state
-        .config
-        .supported_tokens
-        .contains(&quote_request.token_in);
+    // Parse the request, malformed mints or amounts are rejected with a 400
+    let quote_request = ValidatedQuoteRequest::try_from(quote_request)?;
+
+    // if the token pair or the quote type is not supported, return 404
+    let pair = match &state.pairs {
+        Some(pairs) => {
+            let pair = pairs
+                .current()
+                .get(&quote_request.token_in, &quote_request.token_out)
+                .filter(|pair| pair.allows(quote_request.quote_type))
+                .cloned()
+                .ok_or(ApiError::NotFound())?;
+            Some(pair)
+        }
+        None => {
+            let is_input_mint_supported = state
+                .config
+                .supported_tokens
+                .contains(&quote_request.token_in.to_string());
+
+            let is_output_mint_supported = state
+                .config
+                .supported_tokens
+                .contains(&quote_request.token_out.to_string());
+
+            if !is_input_mint_supported || !is_output_mint_supported {
+                return Err(ApiError::NotFound());
+            }
+            None
+        }
+    };
 
-    let is_output_mint_supported = state
-        .config
-        .supported_tokens
-        .contains(&quote_request.token_out);
+    // answer with the newest protocol we support that the RFQ system accepts
+    let protocol = quote_request
+        .protocol
+        .negotiate(&SUPPORTED_PROTOCOLS)
+        .ok_or(ApiError::NotFound())?;
 
-    // if the  the token pair is not supported, return 404
-    if !is_input_mint_supported || !is_output_mint_supported {
-        return Err(ApiError::NotFound());
-    }
+    let _quote_timer = QuoteTimer::start(quote_request.token_in, quote_request.token_out);
 
     // The normal flow of a quote request would be:
     // Step 1: Parse the request
     // Step 2: Compute the quote
     // Step 3: Build the quote response
 
-    let maker_pubkey = state.keypair.pubkey().to_string();
+    let maker = state.keypair.pubkey();
 
-    let example_quoted_amount = "123123123".to_string();
+    // no quote for this request, return 404
+    let Some(quoted_amount) = state.pricer.price(&quote_request).await? else {
+        return Err(ApiError::NotFound());
+    };
 
     // different logic between ExactIn and ExactOut
+    let (amount_in, amount_out) = match quote_request.quote_type {
+        // the pricer computed the amount out based on the amount in
+        QuoteType::ExactIn => (quote_request.amount, quoted_amount),
+        // the pricer computed the amount in based on the amount out
+        QuoteType::ExactOut => (quoted_amount, quote_request.amount),
+    };
 
-    let quote = match quote_request.quote_type {
-        QuoteType::ExactIn => {
-            // compute the amount out based on the amount in
-            QuoteResponse {
-                request_id: quote_request.request_id,
-                quote_id: quote_request.quote_id,
-                taker: quote_request.taker,
-                token_in: quote_request.token_in,
-                amount_in: quote_request.amount.clone(),
-                token_out: quote_request.token_out,
-                quote_type: quote_request.quote_type,
-                protocol: quote_request.protocol,
-                amount_out: example_quoted_amount,
-                maker: maker_pubkey,
-                prioritization_fee_to_use: quote_request.suggested_prioritization_fees,
-                receiver: quote_request.receiver.clone(),
-            }
-        }
-        QuoteType::ExactOut => {
-            // compute the amount in based on the amount out
-            QuoteResponse {
-                request_id: quote_request.request_id,
-                quote_id: quote_request.quote_id,
-                taker: quote_request.taker,
-                token_in: quote_request.token_in,
-                amount_in: example_quoted_amount,
-                token_out: quote_request.token_out,
-                quote_type: quote_request.quote_type,
-                protocol: quote_request.protocol,
-                amount_out: quote_request.amount.clone(),
-                maker: maker_pubkey,
-                prioritization_fee_to_use: quote_request.suggested_prioritization_fees,
-                receiver: quote_request.receiver.clone(),
-            }
-        }
+    // outside of the size bounds of the pair, return 404
+    if pair.is_some_and(|pair| !pair.within_bounds(amount_in, amount_out)) {
+        return Err(ApiError::NotFound());
+    }
+
+    let quote = ValidatedQuoteResponse {
+        request_id: quote_request.request_id,
+        quote_id: quote_request.quote_id,
+        taker: quote_request.taker,
+        token_in: quote_request.token_in,
+        amount_in,
+        token_out: quote_request.token_out,
+        quote_type: quote_request.quote_type,
+        protocol,
+        amount_out,
+        maker,
+        prioritization_fee_to_use: quote_request.suggested_prioritization_fees,
+        receiver: quote_request.receiver,
     };
 
+    // Keep the quote around to check the swap request against it, a swapped quote is not re-issued
+    if !state
+        .quote_book
+        .insert(quote.clone(), quote_request.fee_bps)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Quote {} of request {} was already swapped",
+            quote.quote_id, quote.request_id
+        )));
+    }
+
     // Build jupiter quote request
-    Ok(Json(quote))
+    Ok(Json(quote.into()))
 }
 
 /// Example swap handler
@@ -216,6 +299,32 @@ async fn example_swap(
     State(state): State<Arc<AppState>>,
     Query(_queries): Query<HashMap<String, String>>,
     WithRejection(Json(quote_request), _): WithRejection<Json<SwapRequest>, ApiError>,
+) -> Result<Json<SwapResponse>, ApiError> {
+    // quotes we no longer know of count as promptly swapped
+    let held = state
+        .quote_book
+        .held(&QuoteKey::new(
+            &quote_request.request_id,
+            &quote_request.quote_id,
+        ))
+        .unwrap_or_default();
+    let response = swap(&state, quote_request, held).await;
+    let swap_state = response
+        .as_ref()
+        .ok()
+        .map(|Json(swap_response)| &swap_response.state);
+    record_swap_outcome(swap_state);
+    // accepted swaps are recorded once the fill outcome is known
+    if swap_state != Some(&SwapState::Accepted) {
+        state.fulfilment.record(SwapOutcome::Failed, held);
+    }
+    response
+}
+
+async fn swap(
+    state: &AppState,
+    quote_request: SwapRequest,
+    held: Duration,
 ) -> Result<Json<SwapResponse>, ApiError> {
     // Step 1: Parse the request
     // Step 2: Sign the transaction
@@ -234,71 +343,140 @@ async fn example_swap(
             quote_id: quote_request.quote_id.clone(),
             state: SwapState::Rejected,
             rejection_reason: Some("<rejection reason>".to_string()),
+            unknown_fields: BTreeMap::new(),
         })),
         SIMULATE_INSUFFICIENT_BALANCE => Ok(Json(SwapResponse {
             tx_signature: None,
             quote_id: quote_request.quote_id.clone(),
             state: SwapState::RejectedWithReason(RejectionReason::InsufficientBalance),
             rejection_reason: None,
+            unknown_fields: BTreeMap::new(),
         })),
         SIMULATE_SIGNATURE_VERIFICATION_FAILED => Ok(Json(SwapResponse {
             tx_signature: None,
             quote_id: quote_request.quote_id.clone(),
             state: SwapState::RejectedWithReason(RejectionReason::SignatureVerificationFailed),
             rejection_reason: None,
+            unknown_fields: BTreeMap::new(),
         })),
         SIMULATE_MALFORMED => Err(ApiError::BadRequest("Malformed request".to_string())),
         _ => {
             // ========================================
             // extract the message
             // ========================================
-            let TransactionDetails {
-                mut versioned_transaction,
-                sanitized_message: _,
-            } = deserialize_transaction_base64_into_transaction_details(
-                &quote_request.transaction,
+            let mut versioned_transaction =
+                deserialize_transaction_base64(&quote_request.transaction)?;
+
+            // resolve the address lookup tables, if the message uses any
+            let address_lookup_table_accounts = fetch_address_lookup_table_accounts(
+                &state.rpc_client,
+                &versioned_transaction.message,
+            )
+            .await?;
+            let sanitized_message = versioned_message_to_sanitized_message(
+                versioned_transaction.message.clone(),
+                &address_lookup_table_accounts,
             )?;
 
             // ========================================
             // validate the message
             // ========================================
-            // add the validation logic here
+            let rejected = |swap_state, rejection_reason: String| {
+                tracing::warn!(
+                    "Rejecting swap of quote {}: {}",
+                    quote_request.quote_id,
+                    rejection_reason
+                );
+                Ok(Json(SwapResponse {
+                    tx_signature: None,
+                    quote_id: quote_request.quote_id.clone(),
+                    state: swap_state,
+                    rejection_reason: Some(rejection_reason),
+                    unknown_fields: BTreeMap::new(),
+                }))
+            };
+            // the transaction must fill a quote we issued and that is still valid
+            let quote_key = QuoteKey::new(&quote_request.request_id, &quote_request.quote_id);
+            let issued_quote = match state.quote_book.get(&quote_key) {
+                Ok(issued_quote) => issued_quote,
+                Err(error) => {
+                    let (swap_state, rejection_reason) = quote_lookup_rejection(error);
+                    return rejected(swap_state, rejection_reason);
+                }
+            };
+
+            // the expiry is set by the RFQ system, it only needs to be in the future
+            let validation =
+                decode_fill_sanitized_message(&sanitized_message).and_then(|decoded_fill| {
+                    tracing::debug!(
+                        "Swap of quote {}: {:?}",
+                        quote_request.quote_id,
+                        decoded_fill
+                    );
+                    let order =
+                        issued_quote.order(decoded_fill.accounts.taker, decoded_fill.expire_at);
+                    validate_fill_sanitized_message(
+                        &sanitized_message,
+                        order.clone(),
+                        &FillValidationPolicy {
+                            min_seconds_to_expiry: Some(0),
+                            ..Default::default()
+                        },
+                    )
+                    .map(|validated_fill| (order, validated_fill))
+                });
+            let (order, validated_fill) = match validation {
+                Ok(validation) => validation,
+                Err(error) => {
+                    return rejected(
+                        SwapState::RejectedWithReason(RejectionReason::from(&error)),
+                        error.to_string(),
+                    )
+                }
+            };
+
+            // wallets may have raised the compute unit price since the quote
+            let limits = FillCostLimits {
+                max_network_fee: Some(state.config.max_fill_network_fee),
+                ..Default::default()
+            };
+            if let Err(error) =
+                FillEconomics::new(&sanitized_message, &order, &validated_fill).check(&limits, None)
+            {
+                return rejected(
+                    SwapState::RejectedWithReason(RejectionReason::from(&error)),
+                    error.to_string(),
+                );
+            }
 
             // ========================================
             // add the maker signature to the transaction
             // ========================================
-            match versioned_transaction.signatures.get_mut(0) {
-                Some(signature_slot) => {
-                    let signature = state
-                        .keypair
-                        .sign_message(&versioned_transaction.message.serialize());
-                    *signature_slot = signature;
-                }
-                None => {
-                    return Err(ApiError::BadRequest(
-                        "Partial sign signature to replace not found".to_string(),
-                    ));
-                }
+            if let Err(error) = sign_fill_transaction(&mut versioned_transaction, &state.keypair) {
+                return rejected(
+                    SwapState::RejectedWithReason(RejectionReason::from(&error)),
+                    error.to_string(),
+                );
+            }
+
+            // the first accepted swap consumes the quote, a repeated one would fill it twice
+            if let Err(error) = state.quote_book.consume(&quote_key) {
+                let (swap_state, rejection_reason) = quote_lookup_rejection(error);
+                return rejected(swap_state, rejection_reason);
             }
             let signature = versioned_transaction.get_signature().to_string();
 
             // ========================================
             // broadcast the transaction
             // ========================================
-
-            let _rpc_client_url = state.config.rpc_url.clone();
-            /*
-            tokio::spawn(async move {
-                let client = nonblocking::rpc_client::RpcClient::new(rpc_client_url);
-                // We're using send_and_confirm_transaction to keep sending until the outcome is known on-chain, or the blockhash expires
-                if let Err(error) = client
-                    .send_and_confirm_transaction(&versioned_transaction)
-                    .await
-                {
-                    tracing::error!("Failed to send and confirm transaction: {error:?}");
-                };
+            // keep sending until the outcome is known on-chain, or the blockhash expires
+            let fill = state
+                .fill_sender
+                .spawn(quote_request.quote_id.clone(), versioned_transaction);
+            let fulfilment = state.fulfilment.clone();
+            async_std::task::spawn(async move {
+                fulfilment.record_fill(&fill.await, held);
             });
-             */
 
             // return the response
             Ok(Json(SwapResponse {
@@ -306,29 +484,94 @@ async fn example_swap(
                 quote_id: quote_request.quote_id.clone(),
                 state: SwapState::Accepted,
                 rejection_reason: None,
+                unknown_fields: BTreeMap::new(),
             }))
         }
     }
 }
 
+/// Rejection of a swap whose quote is not, or no longer, in the quote book
+fn quote_lookup_rejection(error: QuoteLookupError) -> (SwapState, String) {
+    let (reason, message) = match error {
+        QuoteLookupError::NotFound => (RejectionReason::QuoteNotFound, "Unknown quote"),
+        QuoteLookupError::Expired => (RejectionReason::QuoteExpired, "Quote expired"),
+        QuoteLookupError::AlreadySwapped => {
+            (RejectionReason::DuplicateSwap, "Quote already swapped")
+        }
+    };
+    (SwapState::RejectedWithReason(reason), message.to_string())
+}
+
+/// Tokens and pairs advertised to the RFQ system
+///
+/// Consumers asking for `application/vnd.jup.rfq.tokens.v2+json` get the pairs with their sizes,
+/// others get the plain array of mints
+
 #[utoipa::path(get, path = "/tokens",
 params(
     ("X-API-KEY" = Option<String>, Header, description = "Optional API Key (if required by the webhook)"),
+    ("If-None-Match" = Option<String>, Header, description = "ETag of the advertisement already known"),
 ),
 responses(
-    (status = 200, body= Vec<String>),
+    (status = 200, content(
+        (Vec<String> = "application/json"),
+        (TokensResponse = "application/vnd.jup.rfq.tokens.v2+json"),
+    )),
+    (status = 304, description = "The advertisement did not change"),
     (status = 400, body= ErrorResponse),
+    (status = 401, body= ErrorResponse),
 ))]
 async fn example_tokens_list(
     State(state): State<Arc<AppState>>,
-) -> Result<Json<Vec<String>>, ApiError> {
-    Ok(Json(state.config.supported_tokens.clone()))
+    tokens_request: TokensRequest,
+) -> Response {
+    let tokens = match &state.pairs {
+        Some(pairs) => pairs.current().advertisement(),
+        None => TokensResponse::new(
+            state
+                .config
+                .supported_tokens
+                .iter()
+                .map(|mint| TokenInfo {
+                    mint: mint.clone(),
+                    decimals: None,
+                })
+                .collect(),
+            vec![],
+        ),
+    };
+    tokens_request.respond(&tokens)
 }
 
 async fn get_health() -> Result<(), ApiError> {
     Ok(())
 }
 
+async fn get_metrics(State(state): State<Arc<AppState>>) -> String {
+    telemetry::render(&state.metrics)
+}
+
+async fn get_fill_outcome(
+    State(state): State<Arc<AppState>>,
+    Path(quote_id): Path<String>,
+) -> Result<Json<FillOutcome>, ApiError> {
+    state
+        .fill_sender
+        .outcome(&quote_id)
+        .map(Json)
+        .ok_or(ApiError::NotFound())
+}
+
+async fn get_fulfilment_status(State(state): State<Arc<AppState>>) -> Json<FulfilmentStatus> {
+    Json(state.fulfilment.status())
+}
+
+/// To call once Jupiter re-enabled a webhook that was turned off
+async fn reset_fulfilment_status(State(state): State<Arc<AppState>>) -> Json<FulfilmentStatus> {
+    state.fulfilment.reset();
+    Json(state.fulfilment.status())
+}
+
 async fn not_found_handler() -> ApiError {
     ApiError::NotFound()
 }
@@ -342,20 +585,48 @@ const MAX_AGE: Duration = Duration::from_secs(86400);
 struct AppState {
     config: Config,
     keypair: Keypair,
+    rpc_client: RpcClient,
+    quote_book: QuoteBook,
+    pairs: Option<Arc<LivePairs>>,
+    pricer: Box<dyn QuotePricer>,
+    fill_sender: Arc<FillSender>,
+    fulfilment: Arc<FulfilmentTracker>,
+    metrics: PrometheusHandle,
 }
 
 fn app(state: Arc<AppState>) -> Router {
+    let api_keys = Arc::new(ApiKeys::new(
+        &state.config.api_keys,
+        &state.config.api_key_exempt_paths,
+    ));
+    if api_keys.is_disabled() {
+        tracing::warn!("No API key configured, requests are not authenticated");
+    }
+
     let router = Router::new()
-        .route("/quote", post(example_quote))
+        .route(
+            "/quote",
+            post(example_quote)
+                .layer(middleware::from_fn_with_state(
+                    Duration::from_millis(state.config.deadline_safety_margin_ms),
+                    enforce_deadline,
+                ))
+                .layer(middleware::from_fn(track_quote_responses)),
+        )
         .route("/swap", post(example_swap))
         .route("/tokens", get(example_tokens_list))
         // not part of RFQ spec, but useful for debugging
         .route("/health", get(get_health))
+        .route("/fills/{quote_id}", get(get_fill_outcome))
+        .route("/metrics", get(get_metrics))
+        .route("/admin/fulfilment", get(get_fulfilment_status))
+        .route("/admin/fulfilment/reset", post(reset_fulfilment_status))
         .merge(SwaggerUi::new("/swagger-ui").url("/api-doc/openapi.json", ApiDoc::openapi()))
         .fallback(not_found_handler)
         .with_state(state);
 
     router
+        .layer(middleware::from_fn_with_state(api_keys, require_api_key))
         .layer(CorsLayer::permissive().max_age(MAX_AGE))
         //.layer(TimeoutLayer::new(Duration::from_secs(10)))
         .layer(
@@ -365,7 +636,7 @@ fn app(state: Arc<AppState>) -> Router {
         )
 }
 
-pub async fn serve(config: Config) {
+fn app_state(config: Config, metrics: PrometheusHandle) -> Arc<AppState> {
     // generate a keypair if not provided
     let keypair = match &config.maker_keypair {
         Some(private_key_file) => {
@@ -380,12 +651,66 @@ pub async fn serve(config: Config) {
 
     tracing::info!("maker pubkey: {}", keypair.pubkey());
 
-    // create the shared state
-    let app_state = Arc::new(AppState {
-        config: config.clone(),
-        keypair,
+    let pairs = config.pairs_config.as_ref().map(|path| {
+        let pairs = Arc::new(LivePairs::load(path).expect("Invalid pairs config"));
+        pairs.spawn_reload(Duration::from_secs(config.pairs_config_poll_secs));
+        pairs
     });
 
+    let pricer: Box<dyn QuotePricer> = match (config.pricer, &pairs) {
+        (PricerKind::Static, _) => Box::new(StaticPricer { amount: 123123123 }),
+        (PricerKind::FixedRate, Some(pairs)) => Box::new(PairsPricer {
+            pairs: pairs.clone(),
+        }),
+        (PricerKind::FixedRate, None) => Box::new(FixedRatePricer::new(config.pair_rates.clone())),
+        (PricerKind::MidPrice, _) => {
+            // replace the seeding with the price feed of your choice
+            let cache = Arc::new(PriceCache::default());
+            for pair_rate in &config.pair_rates {
+                cache.update(pair_rate.token_in, pair_rate.token_out, pair_rate.rate);
+            }
+            if let Some(pairs) = &pairs {
+                for (token_in, token_out, rate) in pairs.current().rates() {
+                    cache.update(token_in, token_out, rate);
+                }
+            }
+            Box::new(MidPricePricer {
+                cache,
+                bps: config.mid_price_bps,
+                max_age: config.max_price_age_secs.map(Duration::from_secs),
+            })
+        }
+    };
+
+    let rpc_backend: Arc<dyn RpcBackend> = if config.broadcast {
+        Arc::new(RpcClient::new(config.rpc_url.clone()))
+    } else {
+        tracing::info!("Broadcast disabled, accepted swaps land on a mock cluster");
+        Arc::new(MockRpcBackend::landing())
+    };
+
+    Arc::new(AppState {
+        rpc_client: RpcClient::new(config.rpc_url.clone()),
+        config,
+        keypair,
+        quote_book: QuoteBook::default(),
+        pairs,
+        pricer,
+        fill_sender: Arc::new(FillSender::new(
+            rpc_backend,
+            DEFAULT_RESEND_INTERVAL,
+            BLOCKHASH_LIFETIME,
+        )),
+        fulfilment: Arc::new(FulfilmentTracker::default()),
+        metrics,
+    })
+}
+
+pub async fn serve(config: Config) {
+    // create the shared state
+    let metrics = telemetry::install_recorder().expect("Failed to install the metrics recorder");
+    let app_state = app_state(config.clone(), metrics);
+
     // build the axum router
     let app = app(app_state);
     // start the server
@@ -395,3 +720,126 @@ pub async fn serve(config: Config) {
     tracing::info!("Starting server at {:?}", config.listen_addr);
     axum::serve(listener, app).await.unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use mock_rfq::{
+        orchestrator::DEFAULT_QUOTE_TIMEOUT, webhook::WebhookClient, Orchestrator, RfqRequest,
+    };
+    use solana_sdk::{pubkey, pubkey::Pubkey};
+    use webhook_api::validated::ValidatedSwapRequest;
+    use webhook_conformance::ConformanceConfig;
+
+    /// Serve the example server on a random port, returns its url
+    async fn spawn_server(config: Config) -> String {
+        let metrics = PrometheusBuilder::new().build_recorder().handle();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let webhook_url = format!("http://{}", listener.local_addr().unwrap());
+        let app = app(app_state(config, metrics));
+        async_std::task::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        webhook_url
+    }
+
+    #[async_std::test]
+    async fn test_conformance() {
+        let mut config = Config::parse_from(["server-example"]);
+        config.api_keys = vec!["conformance".to_string()];
+        let webhook_url = spawn_server(config).await;
+
+        let report = webhook_conformance::run(&ConformanceConfig {
+            api_key: Some("conformance".to_string()),
+            ..ConformanceConfig::new(webhook_url)
+        })
+        .await;
+        assert!(report.is_success(), "{report}");
+        assert_eq!(report.skipped(), 0);
+    }
+
+    #[async_std::test]
+    async fn test_mock_rfq() {
+        let webhook_url = spawn_server(Config::parse_from(["server-example"])).await;
+        let orchestrator = Orchestrator::new(
+            vec![webhook_url.parse().unwrap()],
+            DEFAULT_QUOTE_TIMEOUT,
+            None,
+        );
+        orchestrator.refresh_tokens().await;
+
+        let request = RfqRequest {
+            token_in: pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"),
+            token_out: pubkey!("So11111111111111111111111111111111111111112"),
+            amount: 1_000_000,
+            quote_type: QuoteType::ExactIn,
+            fee_bps: 2,
+            suggested_prioritization_fees: Some(10_000),
+        };
+        // the swap goes through the quote book and the fill validation
+        for quote_type in [QuoteType::ExactIn, QuoteType::ExactOut] {
+            let request = RfqRequest {
+                quote_type,
+                ..request.clone()
+            };
+            let outcome = orchestrator
+                .rfq(&request, &Keypair::new())
+                .await
+                .unwrap()
+                .expect("the example server quotes every supported pair");
+            assert_eq!(outcome.swap.state, SwapState::Accepted);
+        }
+
+        // the example server does not list this token
+        let request = RfqRequest {
+            token_out: Pubkey::new_unique(),
+            ..request
+        };
+        assert!(orchestrator
+            .rfq(&request, &Keypair::new())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[async_std::test]
+    async fn test_duplicate_swap() {
+        let webhook_url = spawn_server(Config::parse_from(["server-example"])).await;
+        let orchestrator = Orchestrator::new(
+            vec![webhook_url.parse().unwrap()],
+            DEFAULT_QUOTE_TIMEOUT,
+            None,
+        );
+        orchestrator.refresh_tokens().await;
+
+        let request = RfqRequest {
+            token_in: pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"),
+            token_out: pubkey!("So11111111111111111111111111111111111111112"),
+            amount: 1_000_000,
+            quote_type: QuoteType::ExactIn,
+            fee_bps: 2,
+            suggested_prioritization_fees: Some(10_000),
+        };
+        let outcome = orchestrator
+            .rfq(&request, &Keypair::new())
+            .await
+            .unwrap()
+            .expect("the example server quotes every supported pair");
+        assert_eq!(outcome.swap.state, SwapState::Accepted);
+
+        // the same quote swapped again must not be co-signed a second time
+        let swap = WebhookClient::new(outcome.webhook)
+            .swap(ValidatedSwapRequest {
+                request_id: outcome.quote.request_id,
+                quote_id: outcome.quote.quote_id,
+                transaction: outcome.transaction,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            swap.state,
+            SwapState::RejectedWithReason(RejectionReason::DuplicateSwap)
+        );
+        assert_eq!(swap.tx_signature, None);
+    }
+}
@@ -0,0 +1,109 @@
+//! Prometheus metrics of the webhook, rendered on `/metrics`
+//!
+//! They cover what the RFQ system judges a webhook on: quotes answered within the deadline and
+//! accepted swaps that land.
+use std::time::Instant;
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+use solana_sdk::pubkey::Pubkey;
+use webhook_api::enums::SwapState;
+
+use crate::fill_sender::FillOutcome;
+
+pub const QUOTE_DURATION_METRIC: &str = "webhook_quote_duration_seconds";
+pub const QUOTE_RESPONSES_METRIC: &str = "webhook_quote_responses_total";
+pub const DEADLINE_MISSED_METRIC: &str = "webhook_quote_deadline_missed_total";
+pub const SWAP_OUTCOMES_METRIC: &str = "webhook_swap_outcomes_total";
+pub const FILL_OUTCOMES_METRIC: &str = "webhook_fill_outcomes_total";
+
+/// Buckets around the 250ms quote timeout of the RFQ system
+const QUOTE_DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.15, 0.2, 0.25, 0.3, 0.5, 1.0,
+];
+
+pub fn install_recorder() -> Result<PrometheusHandle, BuildError> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(QUOTE_DURATION_METRIC.to_string()),
+            QUOTE_DURATION_BUCKETS,
+        )?
+        .install_recorder()
+}
+
+/// Records the time spent quoting a pair when dropped, including when the quote is cancelled
+pub struct QuoteTimer {
+    token_in: Pubkey,
+    token_out: Pubkey,
+    start: Instant,
+}
+
+impl QuoteTimer {
+    pub fn start(token_in: Pubkey, token_out: Pubkey) -> Self {
+        Self {
+            token_in,
+            token_out,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for QuoteTimer {
+    fn drop(&mut self) {
+        metrics::histogram!(
+            QUOTE_DURATION_METRIC,
+            "token_in" => self.token_in.to_string(),
+            "token_out" => self.token_out.to_string(),
+        )
+        .record(self.start.elapsed());
+    }
+}
+
+/// Count quote responses by status: 200, 404, other 4xx and 5xx
+pub async fn track_quote_responses(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    let status = match status.as_u16() {
+        200 => "200",
+        404 => "404",
+        _ if status.is_client_error() => "4xx",
+        _ if status.is_server_error() => "5xx",
+        _ => "other",
+    };
+    metrics::counter!(QUOTE_RESPONSES_METRIC, "status" => status).increment(1);
+    response
+}
+
+pub fn record_deadline_missed(reason: &'static str) {
+    metrics::counter!(DEADLINE_MISSED_METRIC, "reason" => reason).increment(1);
+}
+
+pub fn record_swap_outcome(swap_state: Option<&SwapState>) {
+    let (state, reason) = match swap_state {
+        Some(SwapState::Accepted) => ("accepted", "none".to_string()),
+        Some(SwapState::Rejected) => ("rejected", "none".to_string()),
+        Some(SwapState::RejectedWithReason(rejection_reason)) => {
+            ("rejectedWithReason", rejection_reason.to_string())
+        }
+        Some(SwapState::Unknown(swap_state)) => ("unknown", swap_state.clone()),
+        // the request could not be processed
+        None => ("error", "none".to_string()),
+    };
+    metrics::counter!(SWAP_OUTCOMES_METRIC, "state" => state, "reason" => reason).increment(1);
+}
+
+pub fn record_fill_outcome(outcome: &FillOutcome) {
+    let outcome = match outcome {
+        FillOutcome::Pending { .. } => return,
+        FillOutcome::Landed { .. } => "landed",
+        FillOutcome::Failed { .. } => "failed",
+        FillOutcome::Expired { .. } => "expired",
+    };
+    metrics::counter!(FILL_OUTCOMES_METRIC, "outcome" => outcome).increment(1);
+}
+
+/// Render the metrics, trimming the histograms on the way
+pub fn render(handle: &PrometheusHandle) -> String {
+    handle.run_upkeep();
+    handle.render()
+}
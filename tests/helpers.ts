@@ -1,8 +1,22 @@
 
 import {
+    AccountRole,
+    address,
+    appendTransactionMessageInstructions,
+    compileTransaction,
     createKeyPairSignerFromBytes,
+    createTransactionMessage,
     generateKeyPair,
+    getAddressEncoder,
     getAddressFromPublicKey,
+    getBase64EncodedWireTransaction,
+    getProgramDerivedAddress,
+    partiallySignTransaction,
+    pipe,
+    setTransactionMessageFeePayer,
+    setTransactionMessageLifetimeUsingBlockhash,
+    type Address,
+    type Blockhash,
     type KeyPairSigner,
   } from "@solana/kit";
   import fs from "fs";
@@ -39,4 +53,102 @@ import {
     return await loadKeypairFromFile("~/.config/solana/id.json");
   }
   
-  
\ No newline at end of file
+  
+const ORDER_ENGINE_PROGRAM = address("61DFfeTKM7trxYcPQCM78bJ794ddZprZpAwAnLiwTpYH");
+const COMPUTE_BUDGET_PROGRAM = address("ComputeBudget111111111111111111111111111111");
+const ASSOCIATED_TOKEN_PROGRAM = address("ATokenGPvbdbxxfMN6QVxGiujE6hG6z6y8Vx1WiJb5mb");
+const TOKEN_PROGRAM = address("TokenkegQfeZyiNwAJbNbGsPcCRR2e9B6kgNAbfhbeUu9");
+const SYSTEM_PROGRAM = address("11111111111111111111111111111111");
+// anchor discriminator of the fill instruction, see idls/order_engine.json
+const FILL_DISCRIMINATOR = [168, 96, 183, 163, 92, 10, 40, 160];
+
+export type FillOrder = {
+  taker: KeyPairSigner;
+  maker: Address;
+  inAmount: bigint;
+  inputMint: Address;
+  outAmount: bigint;
+  outputMint: Address;
+  expireAt: bigint;
+  feeBps: number;
+  exactOut: boolean;
+};
+
+function littleEndian(bytes: number, write: (view: DataView) => void): number[] {
+  const view = new DataView(new ArrayBuffer(bytes));
+  write(view);
+  return Array.from(new Uint8Array(view.buffer));
+}
+
+async function associatedTokenAccount(owner: Address, mint: Address): Promise<Address> {
+  const encoder = getAddressEncoder();
+  const [tokenAccount] = await getProgramDerivedAddress({
+    programAddress: ASSOCIATED_TOKEN_PROGRAM,
+    seeds: [encoder.encode(owner), encoder.encode(TOKEN_PROGRAM), encoder.encode(mint)],
+  });
+  return tokenAccount;
+}
+
+// Build the fill transaction of a quote the way the RFQ system does, signed by the taker only
+//
+// Both sides use token accounts of the token program, the maker signs when it accepts the swap.
+export async function buildFillTransaction(order: FillOrder): Promise<string> {
+  const taker = order.taker.address;
+  const writable = async (owner: Address, mint: Address) =>
+    ({ address: await associatedTokenAccount(owner, mint), role: AccountRole.WRITABLE });
+  const readonly = (account: Address) => ({ address: account, role: AccountRole.READONLY });
+
+  const fillData = [
+    ...FILL_DISCRIMINATOR,
+    ...littleEndian(8, (view) => view.setBigUint64(0, order.inAmount, true)),
+    ...littleEndian(8, (view) => view.setBigUint64(0, order.outAmount, true)),
+    ...littleEndian(8, (view) => view.setBigInt64(0, order.expireAt, true)),
+    // trailer: the fee in bps and the direction flag
+    ...littleEndian(2, (view) => view.setUint16(0, order.feeBps, true)),
+    order.exactOut ? 1 : 0,
+  ];
+  const fillInstruction = {
+    programAddress: ORDER_ENGINE_PROGRAM,
+    accounts: [
+      { address: taker, role: AccountRole.WRITABLE_SIGNER },
+      { address: order.maker, role: AccountRole.WRITABLE_SIGNER },
+      await writable(taker, order.inputMint),
+      await writable(order.maker, order.inputMint),
+      await writable(taker, order.outputMint),
+      await writable(order.maker, order.outputMint),
+      readonly(order.inputMint),
+      readonly(TOKEN_PROGRAM),
+      readonly(order.outputMint),
+      readonly(TOKEN_PROGRAM),
+      readonly(SYSTEM_PROGRAM),
+    ],
+    data: new Uint8Array(fillData),
+  };
+  const computeUnitPrice = {
+    programAddress: COMPUTE_BUDGET_PROGRAM,
+    data: new Uint8Array([3, ...littleEndian(8, (view) => view.setBigUint64(0, 0n, true))]),
+  };
+  const computeUnitLimit = {
+    programAddress: COMPUTE_BUDGET_PROGRAM,
+    data: new Uint8Array([2, ...littleEndian(4, (view) => view.setUint32(0, 200_000, true))]),
+  };
+
+  const message = pipe(
+    createTransactionMessage({ version: 0 }),
+    (message) => setTransactionMessageFeePayer(order.maker, message),
+    (message) => setTransactionMessageLifetimeUsingBlockhash(
+      // the webhook co-signs the fill, it does not need a recent blockhash
+      { blockhash: "11111111111111111111111111111111" as Blockhash, lastValidBlockHeight: 0n },
+      message,
+    ),
+    (message) => appendTransactionMessageInstructions(
+      [computeUnitPrice, computeUnitLimit, fillInstruction],
+      message,
+    ),
+  );
+  const transaction = await partiallySignTransaction(
+    [order.taker.keyPair],
+    compileTransaction(message),
+  );
+  return getBase64EncodedWireTransaction(transaction);
+}
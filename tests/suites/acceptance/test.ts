@@ -2,10 +2,10 @@ import axios from 'axios';
 import { assert } from 'chai';
 import { describe, expect, it } from 'vitest';
 import * as params from '../../params';
-import { loadKeypairFromFile } from '../../helpers';
+import { buildFillTransaction, loadKeypairFromFile } from '../../helpers';
 import { BN } from 'bn.js';
 import { parse } from 'path';
-import { getPublicKeyFromAddress } from '@solana/kit';
+import { address, generateKeyPairSigner, getPublicKeyFromAddress } from '@solana/kit';
 
 
 // Base API URL, load from environment variable or use default
@@ -136,7 +136,7 @@ describe('Webhook API Quote', () => {
       taker: "5v2Vd71VoJ1wZhz1PkhTY48mrJwS6wF4LfvDbYPnJ3bc",
       tokenIn: params.MINT_A,
       // this token does not exists so it cannot be supported and the response should be 404
-      tokenOut: "fake3KUxqvJ5erXobKTYFtL2BpTgGzy7B9AcRcXeCwWvFM",
+      tokenOut: "FakeUnsupportedMint111111111111111111111111",
     }
 
     await axios.post(url, payload, { headers: HEADERS }).then((response) => {
@@ -156,17 +156,81 @@ describe('Webhook API Quote', () => {
     });
   });
 
-  it('should return a successful swap response', async () => {
-    const url = `${WEBHOOK_URL}/swap`;
+  it('should return a 400 for a malformed amount', async () => {
+    const url = `${WEBHOOK_URL}/quote`;
     console.log('request url: ', url);
 
     const payload = {
+      // amounts are integers in the smallest unit of the token
+      amount: "1.5",
+      feeBps: params.FEE_BPS,
+      protocol: "v1",
       quoteId: "59db3e19-c7b0-4753-a8aa-206701004498",
+      quoteType: "exactIn",
       requestId: "629bddf3-0038-43a6-8956-f5433d6b1191",
-      transaction: "AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAgAIABw1+jAiHYL/eHd3PMsF/IJuCQu5SqvEx+s2I0OosbQsG8kjAG1BZAFRV2dywxrzs3LT7Wy6rwamoK1c5K6qkDwTmwoAL86DDaPJrpECH4O7FIcjNK8aXLr8U+vEPOkKqMIbT6oz1rKyozQUgdRIXXEPO9Upd2Z7eIKFrVSU3OPOX3N7E3kRk8Ll8XsOf5Ir4ISzHf+0ZUtqBSXSNVE5iS+sA4iF2IlhNfbkvqPIGGddbql5WIVIAOvUkFwCrBoXw04EAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAMGRm/lIRcy/+ytunLDm+e8jOW7xfcSayxDmzpAAAAABHnZx8wQNd5yEfmetIwJ1wsr31vfni5WuKH7taLqMycG3fbh12Whk9nL4UbO63msHLSF7V9bN5E6jPWFfv8AqUpYSftyo7vpH9xbDmpX9jxaHLRbIGem7Qys02OVyKECjJclj04kifG7PRApFI4NgwtaE5na/xCEBI572Nvp+FnG+nrzvtutOj1l82qryXQxsbvkwtL24OR8pgIDRS9dYSdenhhyIvZ+yaOk0Giv3sQzHPEybygyONx7iDX7IHF2BAcACQMK0gAAAAAAAAcABQIdmAAACwYBBAEMBgkBAQoLAQACBQQDCAkMCQYjqGC3o1wKKKAAypo7AAAAAJgWfQEAAAAAaiqpZwAAAAAKAAAA"
+      suggestedPrioritizationFees: 10000,
+      taker: "5v2Vd71VoJ1wZhz1PkhTY48mrJwS6wF4LfvDbYPnJ3bc",
+      tokenIn: params.MINT_B,
+      tokenOut: params.MINT_A,
+    }
+
+    await axios.post(url, payload, { headers: HEADERS }).then((response) => {
+      console.log("response --> ", response.data);
+      assert.fail('expected 400 response');
+    }).catch((error) => {
+      if (error.response) {
+        console.log("error.response.data --> ", error.response.data);
+        expect(error.response.status).toBe(400);
+        expect(error.response.data).toHaveProperty('message');
+      } else if (error.request) {
+        console.log("error.request --> ", error.request.data);
+        assert.fail('failed to get quote: no response from server');
+      } else {
+        console.log("error --> ", error);
+        assert.fail('failed to get quote: unknown error');
+      }
+    });
+  });
+
+  it('should return a successful swap response', async () => {
+    // the swapped transaction fills a quote issued by the webhook, as with the RFQ system
+    const taker = await generateKeyPairSigner();
+    const quotePayload = {
+      amount: `${params.AMOUNT}`,
+      feeBps: params.FEE_BPS,
+      protocol: "v1",
+      quoteId: crypto.randomUUID(),
+      quoteType: "exactIn",
+      requestId: crypto.randomUUID(),
+      suggestedPrioritizationFees: 10000,
+      taker: taker.address,
+      tokenIn: params.MINT_B,
+      tokenOut: params.MINT_A,
+    }
+    const quoteResponse = await axios.post(`${WEBHOOK_URL}/quote`, quotePayload, { headers: HEADERS });
+    expect(quoteResponse.status).toBe(200);
+    const quote = quoteResponse.data;
+
+    const url = `${WEBHOOK_URL}/swap`;
+    console.log('request url: ', url);
+
+    const payload = {
+      quoteId: quote.quoteId,
+      requestId: quote.requestId,
+      transaction: await buildFillTransaction({
+        taker,
+        maker: address(quote.maker),
+        inAmount: BigInt(quote.amountIn),
+        inputMint: address(quote.tokenIn),
+        outAmount: BigInt(quote.amountOut),
+        outputMint: address(quote.tokenOut),
+        expireAt: BigInt(Math.floor(Date.now() / 1000) + 60),
+        feeBps: Number(params.FEE_BPS),
+        exactOut: false,
+      }),
     };
 
-    await axios.post(url, payload).then((response) => {
+    await axios.post(url, payload, { headers: HEADERS }).then((response) => {
       console.log("response --> ", response.data);
 
 
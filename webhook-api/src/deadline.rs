@@ -0,0 +1,170 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+
+/// Millisecond timestamp at which the RFQ system sent the quote request
+pub const REQUEST_START_HEADER: &str = "x-request-start";
+/// Milliseconds the RFQ system waits for the quote before dropping it
+pub const REQUEST_TIMEOUT_HEADER: &str = "x-request-timeout";
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RfqDeadlineError {
+    #[error("Missing header {0}")]
+    MissingHeader(&'static str),
+    #[error("Invalid header {header}: {value}")]
+    InvalidHeader { header: &'static str, value: String },
+}
+
+/// Time budget of a quote request, from the `x-request-start` and `x-request-timeout` headers
+///
+/// Quotes received by the RFQ system after the deadline are dropped, so any work past it is
+/// wasted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RfqDeadline {
+    pub request_start: SystemTime,
+    pub timeout: Duration,
+}
+
+impl RfqDeadline {
+    /// Parse the raw values of the `x-request-start` and `x-request-timeout` headers
+    pub fn parse(request_start: &str, request_timeout: &str) -> Result<Self, RfqDeadlineError> {
+        let parse_millis = |header: &'static str, value: &str| {
+            value
+                .trim()
+                .parse::<u64>()
+                .map(Duration::from_millis)
+                .map_err(|_| RfqDeadlineError::InvalidHeader {
+                    header,
+                    value: value.to_string(),
+                })
+        };
+        Ok(Self {
+            request_start: UNIX_EPOCH + parse_millis(REQUEST_START_HEADER, request_start)?,
+            timeout: parse_millis(REQUEST_TIMEOUT_HEADER, request_timeout)?,
+        })
+    }
+
+    pub fn deadline(&self) -> SystemTime {
+        self.request_start + self.timeout
+    }
+
+    /// Budget left before the quote is dropped, zero once the deadline has passed
+    pub fn remaining(&self) -> Duration {
+        self.remaining_at(SystemTime::now())
+    }
+
+    pub fn remaining_at(&self, now: SystemTime) -> Duration {
+        self.deadline()
+            .duration_since(now)
+            .unwrap_or(Duration::ZERO)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
+
+#[cfg(feature = "axum")]
+mod extract {
+    use axum::{
+        extract::{FromRequestParts, OptionalFromRequestParts},
+        http::{request::Parts, HeaderMap, StatusCode},
+        response::{IntoResponse, Response},
+        Json,
+    };
+
+    use super::*;
+    use crate::{enums::ErrorCode, responses::ErrorResponse};
+
+    impl IntoResponse for RfqDeadlineError {
+        fn into_response(self) -> Response {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new(
+                    ErrorCode::InvalidRequest,
+                    self.to_string(),
+                )),
+            )
+                .into_response()
+        }
+    }
+
+    fn header<'a>(
+        headers: &'a HeaderMap,
+        header: &'static str,
+    ) -> Result<Option<&'a str>, RfqDeadlineError> {
+        headers
+            .get(header)
+            .map(|value| {
+                value.to_str().map_err(|_| RfqDeadlineError::InvalidHeader {
+                    header,
+                    value: String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                })
+            })
+            .transpose()
+    }
+
+    /// Rejects the request with a 400 when the headers are missing or malformed
+    impl<S: Send + Sync> FromRequestParts<S> for RfqDeadline {
+        type Rejection = RfqDeadlineError;
+
+        async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+            <RfqDeadline as OptionalFromRequestParts<S>>::from_request_parts(parts, state)
+                .await?
+                .ok_or(RfqDeadlineError::MissingHeader(REQUEST_START_HEADER))
+        }
+    }
+
+    /// `None` when the request carries neither header, as when it does not come from the RFQ system
+    impl<S: Send + Sync> OptionalFromRequestParts<S> for RfqDeadline {
+        type Rejection = RfqDeadlineError;
+
+        async fn from_request_parts(
+            parts: &mut Parts,
+            _state: &S,
+        ) -> Result<Option<Self>, Self::Rejection> {
+            match (
+                header(&parts.headers, REQUEST_START_HEADER)?,
+                header(&parts.headers, REQUEST_TIMEOUT_HEADER)?,
+            ) {
+                (Some(request_start), Some(request_timeout)) => {
+                    RfqDeadline::parse(request_start, request_timeout).map(Some)
+                }
+                (None, None) => Ok(None),
+                (None, Some(_)) => Err(RfqDeadlineError::MissingHeader(REQUEST_START_HEADER)),
+                (Some(_), None) => Err(RfqDeadlineError::MissingHeader(REQUEST_TIMEOUT_HEADER)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfq_deadline() {
+        let deadline = RfqDeadline::parse("1739139690000", "250").unwrap();
+        assert_eq!(
+            deadline.deadline(),
+            UNIX_EPOCH + Duration::from_millis(1_739_139_690_250)
+        );
+        assert_eq!(
+            deadline.remaining_at(UNIX_EPOCH + Duration::from_millis(1_739_139_690_100)),
+            Duration::from_millis(150)
+        );
+        assert_eq!(
+            deadline.remaining_at(UNIX_EPOCH + Duration::from_millis(1_739_139_691_000)),
+            Duration::ZERO
+        );
+        assert!(deadline.is_expired());
+
+        assert_eq!(
+            RfqDeadline::parse("1739139690000", "250ms").unwrap_err(),
+            RfqDeadlineError::InvalidHeader {
+                header: REQUEST_TIMEOUT_HEADER,
+                value: "250ms".to_string()
+            }
+        );
+    }
+}
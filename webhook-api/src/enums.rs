@@ -1,6 +1,12 @@
 use serde::{Deserialize, Serialize};
-use strum::{Display, EnumString};
-use utoipa::ToSchema;
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
+use utoipa::{
+    openapi::{
+        schema::{ObjectBuilder, OneOfBuilder, Type},
+        Ref, RefOr, Schema,
+    },
+    PartialSchema, ToSchema,
+};
 
 #[derive(
     Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema, EnumString, Display,
@@ -13,42 +19,204 @@ pub enum QuoteType {
     ExactOut,
 }
 
-#[derive(
-    Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema, EnumString, Display,
-)]
+/// Version of the webhook payloads
+///
+/// The quote request carries the newest protocol the RFQ system accepts, the quote response
+/// carries the protocol the maker answers with, which must not be newer. Makers answer with the
+/// newest protocol they support among those, see [`Protocol::negotiate`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, EnumString, Display, EnumIter)]
 #[serde(rename_all = "camelCase")]
 pub enum Protocol {
+    #[strum(serialize = "v1", to_string = "V1")]
     V1,
+    /// Additive over V1, fields unknown to V1 makers are kept in `unknown_fields`
+    #[strum(serialize = "v2", to_string = "V2")]
+    V2,
+    /// A protocol newer than this crate
+    #[serde(untagged)]
+    #[strum(default, to_string = "{0}")]
+    Unknown(String),
 }
 
+/// Protocols implemented by this crate, oldest first
+pub const SUPPORTED_PROTOCOLS: [Protocol; 2] = [Protocol::V1, Protocol::V2];
+
+impl Protocol {
+    /// Known protocols are ordered, unknown ones are assumed to be newer than any known one
+    fn version(&self) -> u32 {
+        match self {
+            Self::V1 => 1,
+            Self::V2 => 2,
+            Self::Unknown(_) => u32::MAX,
+        }
+    }
+
+    /// Protocol of the response to a request made with `self`: the newest of `supported` that is
+    /// not newer than the request, `None` when all of them are newer
+    pub fn negotiate(&self, supported: &[Protocol]) -> Option<Protocol> {
+        supported
+            .iter()
+            .filter(|protocol| !matches!(protocol, Self::Unknown(_)))
+            .filter(|protocol| protocol.version() <= self.version())
+            .max_by_key(|protocol| protocol.version())
+            .cloned()
+    }
+
+    /// Whether a response with the `response` protocol answers a request made with `self`
+    pub fn accepts(&self, response: &Protocol) -> bool {
+        !matches!(response, Self::Unknown(_)) && response.version() <= self.version()
+    }
+}
+
+/// Unknown reasons deserialize into [`RejectionReason::Unknown`] instead of failing
 #[derive(
-    Debug,
-    Copy,
-    Clone,
-    Serialize,
-    Deserialize,
-    PartialEq,
-    Eq,
-    ToSchema,
-    Display,
-    EnumString,
-    Default,
+    Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Display, EnumString, EnumIter, Default,
 )]
 #[serde(rename_all = "camelCase")]
 pub enum RejectionReason {
+    #[strum(serialize = "insufficientBalance", to_string = "InsufficientBalance")]
     InsufficientBalance,
+    #[strum(
+        serialize = "insufficientBalanceForAtaCreation",
+        to_string = "InsufficientBalanceForAtaCreation"
+    )]
     InsufficientBalanceForAtaCreation,
+    #[strum(
+        serialize = "signatureVerificationFailed",
+        to_string = "SignatureVerificationFailed"
+    )]
     SignatureVerificationFailed,
     #[default]
+    #[strum(serialize = "botActivityDetected", to_string = "BotActivityDetected")]
     BotActivityDetected,
+    /// The quote expired before the swap request
+    #[strum(serialize = "quoteExpired", to_string = "QuoteExpired")]
+    QuoteExpired,
+    /// The quote was not issued by the maker, or is no longer known to it
+    #[strum(serialize = "quoteNotFound", to_string = "QuoteNotFound")]
+    QuoteNotFound,
+    /// The market moved too far from the quoted price
+    #[strum(serialize = "priceMoved", to_string = "PriceMoved")]
+    PriceMoved,
+    /// Filling would exceed a risk limit of the maker, such as an exposure or inventory bound
+    #[strum(serialize = "riskLimitExceeded", to_string = "RiskLimitExceeded")]
+    RiskLimitExceeded,
+    /// The transaction does not fill the quote as issued, e.g. different amounts or accounts
+    #[strum(serialize = "transactionMismatch", to_string = "TransactionMismatch")]
+    TransactionMismatch,
+    /// The quote was already filled or is being filled
+    #[strum(serialize = "duplicateSwap", to_string = "DuplicateSwap")]
+    DuplicateSwap,
+    /// The maker stopped filling quotes
+    #[strum(serialize = "makerPaused", to_string = "MakerPaused")]
+    MakerPaused,
+    #[serde(untagged)]
+    #[strum(default, to_string = "{0}")]
+    Unknown(String),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema, Display, EnumString)]
+/// Stable code of an [`ErrorResponse`](crate::responses::ErrorResponse), unknown codes
+/// deserialize into [`ErrorCode::Unknown`]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Display, EnumString, EnumIter)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    /// The request is malformed or carries invalid values
+    #[strum(serialize = "invalidRequest", to_string = "InvalidRequest")]
+    InvalidRequest,
+    /// The API key is missing or incorrect
+    #[strum(serialize = "unauthorized", to_string = "Unauthorized")]
+    Unauthorized,
+    /// The route does not exist, or the webhook does not quote this request
+    #[strum(serialize = "notFound", to_string = "NotFound")]
+    NotFound,
+    /// The webhook failed to process the request
+    #[strum(serialize = "internalError", to_string = "InternalError")]
+    InternalError,
+    #[serde(untagged)]
+    #[strum(default, to_string = "{0}")]
+    Unknown(String),
+}
+
+/// Unknown states deserialize into [`SwapState::Unknown`] instead of failing
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Display, EnumString)]
 #[serde(rename_all = "camelCase")]
 pub enum SwapState {
+    #[strum(serialize = "accepted", to_string = "Accepted")]
     Accepted,
+    #[strum(serialize = "rejected", to_string = "Rejected")]
     Rejected,
+    #[strum(serialize = "rejectedWithReason", to_string = "RejectedWithReason")]
     RejectedWithReason(RejectionReason),
+    #[serde(untagged)]
+    #[strum(default, to_string = "{0}")]
+    Unknown(String),
+}
+
+// The OpenAPI schemas only list the known values, the `Unknown` fallbacks are not part of the API
+
+/// Schema of the unit variants of an enum, as serialized
+fn string_enum<T: Serialize>(values: impl IntoIterator<Item = T>) -> ObjectBuilder {
+    ObjectBuilder::new()
+        .schema_type(Type::String)
+        .enum_values(Some(
+            values
+                .into_iter()
+                .map(|value| serde_json::to_value(value).expect("Enum serializes")),
+        ))
+}
+
+impl PartialSchema for Protocol {
+    fn schema() -> RefOr<Schema> {
+        string_enum(Protocol::iter().filter(|protocol| !matches!(protocol, Protocol::Unknown(_))))
+            .description(Some(
+                "Version of the webhook payloads, the quote response must not be newer than the \
+                 quote request",
+            ))
+            .into()
+    }
+}
+
+impl ToSchema for Protocol {}
+
+impl PartialSchema for RejectionReason {
+    fn schema() -> RefOr<Schema> {
+        string_enum(
+            RejectionReason::iter().filter(|reason| !matches!(reason, RejectionReason::Unknown(_))),
+        )
+        .into()
+    }
+}
+
+impl ToSchema for RejectionReason {}
+
+impl PartialSchema for ErrorCode {
+    fn schema() -> RefOr<Schema> {
+        string_enum(ErrorCode::iter().filter(|code| !matches!(code, ErrorCode::Unknown(_)))).into()
+    }
+}
+
+impl ToSchema for ErrorCode {}
+
+impl PartialSchema for SwapState {
+    fn schema() -> RefOr<Schema> {
+        OneOfBuilder::new()
+            .item(string_enum([SwapState::Accepted, SwapState::Rejected]))
+            .item(
+                ObjectBuilder::new()
+                    .property(
+                        "rejectedWithReason",
+                        Ref::from_schema_name(RejectionReason::name()),
+                    )
+                    .required("rejectedWithReason"),
+            )
+            .into()
+    }
+}
+
+impl ToSchema for SwapState {
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        schemas.push((RejectionReason::name().into(), RejectionReason::schema()));
+    }
 }
 
 #[cfg(test)]
@@ -113,6 +281,76 @@ mod tests {
         assert_eq!(deserialized_bot_activity, bot_activity);
     }
 
+    #[test]
+    fn test_unknown_variants() {
+        assert_eq!(
+            serde_json::from_str::<Protocol>("\"v3\"").unwrap(),
+            Protocol::Unknown("v3".to_string())
+        );
+        assert_eq!(
+            serde_json::from_str::<SwapState>("{\"rejectedWithReason\": \"newReason\"}").unwrap(),
+            SwapState::RejectedWithReason(RejectionReason::Unknown("newReason".to_string()))
+        );
+        let pending = serde_json::from_str::<SwapState>("\"pending\"").unwrap();
+        assert_eq!(pending, SwapState::Unknown("pending".to_string()));
+        // unknown values are sent back as received
+        assert_eq!(serde_json::to_string(&pending).unwrap(), "\"pending\"");
+        assert_eq!(pending.to_string(), "pending");
+        assert_eq!(
+            "newReason".parse::<RejectionReason>().unwrap(),
+            RejectionReason::Unknown("newReason".to_string())
+        );
+    }
+
+    #[test]
+    fn test_protocol_negotiation() {
+        let v3 = Protocol::Unknown("v3".to_string());
+        assert_eq!(
+            Protocol::V1.negotiate(&SUPPORTED_PROTOCOLS),
+            Some(Protocol::V1)
+        );
+        assert_eq!(
+            Protocol::V2.negotiate(&SUPPORTED_PROTOCOLS),
+            Some(Protocol::V2)
+        );
+        assert_eq!(v3.negotiate(&SUPPORTED_PROTOCOLS), Some(Protocol::V2));
+        assert_eq!(Protocol::V1.negotiate(&[Protocol::V2]), None);
+
+        assert!(Protocol::V2.accepts(&Protocol::V1));
+        assert!(!Protocol::V1.accepts(&Protocol::V2));
+        assert!(!v3.accepts(&v3));
+    }
+
+    #[test]
+    fn test_parse_serde_names() {
+        // the protocol of a request, as sent on the wire
+        let v1 = "v1".parse::<Protocol>().unwrap();
+        assert_eq!(v1, Protocol::V1);
+        assert_eq!(v1.negotiate(&SUPPORTED_PROTOCOLS), Some(Protocol::V1));
+        assert_eq!("V2".parse::<Protocol>().unwrap(), Protocol::V2);
+
+        for reason in RejectionReason::iter().filter(|r| !matches!(r, RejectionReason::Unknown(_)))
+        {
+            let name = serde_json::to_value(&reason).unwrap();
+            assert_eq!(
+                name.as_str().unwrap().parse::<RejectionReason>().unwrap(),
+                reason
+            );
+            assert_eq!(
+                reason.to_string().parse::<RejectionReason>().unwrap(),
+                reason
+            );
+        }
+        for code in ErrorCode::iter().filter(|c| !matches!(c, ErrorCode::Unknown(_))) {
+            let name = serde_json::to_value(&code).unwrap();
+            assert_eq!(name.as_str().unwrap().parse::<ErrorCode>().unwrap(), code);
+        }
+        assert_eq!(
+            "accepted".parse::<SwapState>().unwrap(),
+            SwapState::Accepted
+        );
+    }
+
     #[test]
     fn test_rejection_reason_serialization() {
         let insufficient_balance = RejectionReason::InsufficientBalance;
@@ -138,5 +376,24 @@ mod tests {
             serde_json::to_string(&bot_activity).unwrap(),
             "\"botActivityDetected\""
         );
+
+        for (reason, json) in [
+            (RejectionReason::QuoteExpired, "\"quoteExpired\""),
+            (RejectionReason::QuoteNotFound, "\"quoteNotFound\""),
+            (RejectionReason::PriceMoved, "\"priceMoved\""),
+            (RejectionReason::RiskLimitExceeded, "\"riskLimitExceeded\""),
+            (
+                RejectionReason::TransactionMismatch,
+                "\"transactionMismatch\"",
+            ),
+            (RejectionReason::DuplicateSwap, "\"duplicateSwap\""),
+            (RejectionReason::MakerPaused, "\"makerPaused\""),
+        ] {
+            assert_eq!(serde_json::to_string(&reason).unwrap(), json);
+            assert_eq!(
+                serde_json::from_str::<RejectionReason>(json).unwrap(),
+                reason
+            );
+        }
     }
 }
@@ -1,3 +1,6 @@
+pub mod deadline;
 pub mod enums;
 pub mod requests;
 pub mod responses;
+pub mod tokens;
+pub mod validated;
@@ -1,7 +1,9 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use crate::enums::{Protocol, QuoteType, SwapState};
+use crate::enums::{ErrorCode, Protocol, QuoteType, SwapState};
 
 /// Response to a quote request from the Market Maker
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
@@ -39,6 +41,9 @@ pub struct QuoteResponse {
     #[schema(examples("5v2Vd71VoJ1wZhz1PkhTY48mrJwS6wF4LfvDbYPnJ3bc"))]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub receiver: Option<String>,
+    /// Fields of newer protocols, kept to be sent back as received
+    #[serde(flatten)]
+    pub unknown_fields: BTreeMap<String, serde_json::Value>,
 }
 
 /// Response to a swap request to the Market Maker
@@ -55,17 +60,36 @@ pub struct SwapResponse {
     /// Optional message to provide more context when the swap is rejected
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rejection_reason: Option<String>,
+    /// Fields of newer protocols, kept to be sent back as received
+    #[serde(flatten)]
+    pub unknown_fields: BTreeMap<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ErrorResponse {
+    /// Stable code to match errors on, absent from webhooks predating it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+    /// Human readable description of the error, not meant to be matched on
     #[schema(examples("webhook api error message or code"))]
     pub message: String,
 }
 
+impl ErrorResponse {
+    pub fn new(code: ErrorCode, message: String) -> Self {
+        ErrorResponse {
+            code: Some(code),
+            message,
+        }
+    }
+}
+
 impl From<String> for ErrorResponse {
     fn from(message: String) -> Self {
-        ErrorResponse { message }
+        ErrorResponse {
+            code: None,
+            message,
+        }
     }
 }
@@ -0,0 +1,246 @@
+//! Advertisement of the tokens and pairs quoted by a webhook, served on `/tokens`
+//!
+//! The RFQ system historically expects a plain JSON array of mints. Consumers asking for
+//! [`TOKENS_V2_MEDIA_TYPE`] in their `Accept` header get a [`TokensResponse`] instead, which also
+//! describes the decimals of the tokens and the direction and sizes of the quoted pairs. Both
+//! representations carry an ETag so the periodic poll can be answered with a `304 Not Modified`.
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use crate::enums::QuoteType;
+
+/// Media type of the [`TokensResponse`] representation of `/tokens`
+pub const TOKENS_V2_MEDIA_TYPE: &str = "application/vnd.jup.rfq.tokens.v2+json";
+pub const TOKENS_RESPONSE_VERSION: u32 = 2;
+
+/// Tokens and pairs quoted by the Market Maker
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TokensResponse {
+    #[schema(examples(2))]
+    pub version: u32,
+    pub tokens: Vec<TokenInfo>,
+    /// Quoted directions, empty when every pair of tokens is quoted both ways
+    #[serde(default)]
+    pub pairs: Vec<PairInfo>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenInfo {
+    #[schema(examples("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"))]
+    pub mint: String,
+    #[schema(examples(6))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decimals: Option<u8>,
+}
+
+/// A quoted direction, from `token_in` to `token_out`, with its bounds in atomic units
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PairInfo {
+    #[schema(examples("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"))]
+    pub token_in: String,
+    #[schema(examples("So11111111111111111111111111111111111111112"))]
+    pub token_out: String,
+    pub quote_types: Vec<QuoteType>,
+    #[schema(examples("100000000"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_amount_in: Option<String>,
+    #[schema(examples("1000000000"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_amount_in: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_amount_out: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_amount_out: Option<String>,
+}
+
+impl TokensResponse {
+    pub fn new(tokens: Vec<TokenInfo>, pairs: Vec<PairInfo>) -> Self {
+        Self {
+            version: TOKENS_RESPONSE_VERSION,
+            tokens,
+            pairs,
+        }
+    }
+
+    /// The plain array of mints served to consumers that do not ask for this representation
+    pub fn mints(&self) -> Vec<String> {
+        self.tokens.iter().map(|token| token.mint.clone()).collect()
+    }
+}
+
+/// Representation of `/tokens` picked from the `Accept` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokensFormat {
+    /// A JSON array of mints
+    Mints,
+    /// A [`TokensResponse`]
+    V2,
+}
+
+impl TokensFormat {
+    pub fn from_accept(accept: Option<&str>) -> Self {
+        let accepts_v2 = accept.is_some_and(|accept| {
+            accept.split(',').any(|media_range| {
+                media_range
+                    .split(';')
+                    .next()
+                    .is_some_and(|media_type| media_type.trim() == TOKENS_V2_MEDIA_TYPE)
+            })
+        });
+        if accepts_v2 {
+            Self::V2
+        } else {
+            Self::Mints
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Mints => "application/json",
+            Self::V2 => TOKENS_V2_MEDIA_TYPE,
+        }
+    }
+}
+
+/// Strong ETag of a response body
+pub fn etag(body: &[u8]) -> String {
+    format!(
+        "\"{}\"",
+        BASE64_URL_SAFE_NO_PAD.encode(&Sha256::digest(body)[..16])
+    )
+}
+
+/// Whether an `If-None-Match` header matches `etag`, weak validators included
+pub fn if_none_match(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+#[cfg(feature = "axum")]
+mod respond {
+    use std::convert::Infallible;
+
+    use axum::{
+        extract::FromRequestParts,
+        http::{header, request::Parts, HeaderValue, StatusCode},
+        response::{IntoResponse, Response},
+    };
+
+    use super::*;
+
+    /// What a `/tokens` request asks for, from its `Accept` and `If-None-Match` headers
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct TokensRequest {
+        pub format: TokensFormat,
+        pub if_none_match: Option<String>,
+    }
+
+    impl<S: Send + Sync> FromRequestParts<S> for TokensRequest {
+        type Rejection = Infallible;
+
+        async fn from_request_parts(
+            parts: &mut Parts,
+            _state: &S,
+        ) -> Result<Self, Self::Rejection> {
+            let header = |name| {
+                parts
+                    .headers
+                    .get(name)
+                    .and_then(|value: &HeaderValue| value.to_str().ok())
+            };
+            Ok(Self {
+                format: TokensFormat::from_accept(header(header::ACCEPT)),
+                if_none_match: header(header::IF_NONE_MATCH).map(str::to_string),
+            })
+        }
+    }
+
+    impl TokensRequest {
+        /// `tokens` in the requested representation, or a `304` when the client already has it
+        pub fn respond(&self, tokens: &TokensResponse) -> Response {
+            let body = match self.format {
+                TokensFormat::Mints => serde_json::to_vec(&tokens.mints()),
+                TokensFormat::V2 => serde_json::to_vec(tokens),
+            }
+            .expect("Tokens response serializes");
+            let etag = etag(&body);
+            let headers = [
+                (header::ETAG, etag.clone()),
+                (header::VARY, header::ACCEPT.to_string()),
+            ];
+            if self
+                .if_none_match
+                .as_deref()
+                .is_some_and(|if_none_match| super::if_none_match(if_none_match, &etag))
+            {
+                return (StatusCode::NOT_MODIFIED, headers).into_response();
+            }
+            (
+                headers,
+                [(header::CONTENT_TYPE, self.format.content_type().to_string())],
+                body,
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(feature = "axum")]
+pub use respond::TokensRequest;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_negotiation() {
+        assert_eq!(TokensFormat::from_accept(None), TokensFormat::Mints);
+        assert_eq!(
+            TokensFormat::from_accept(Some("application/json")),
+            TokensFormat::Mints
+        );
+        assert_eq!(
+            TokensFormat::from_accept(Some(
+                "application/json;q=0.5, application/vnd.jup.rfq.tokens.v2+json"
+            )),
+            TokensFormat::V2
+        );
+
+        let etag = etag(b"[]");
+        assert_eq!(etag, super::etag(b"[]"));
+        assert_ne!(etag, super::etag(b"[\"\"]"));
+        assert!(if_none_match(&etag, &etag));
+        assert!(if_none_match(&format!("\"other\", W/{etag}"), &etag));
+        assert!(if_none_match("*", &etag));
+        assert!(!if_none_match("\"other\"", &etag));
+    }
+
+    #[test]
+    fn test_tokens_response_serde() {
+        let tokens = TokensResponse::new(
+            vec![TokenInfo {
+                mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+                decimals: Some(6),
+            }],
+            vec![],
+        );
+        assert_eq!(
+            serde_json::to_value(&tokens).unwrap(),
+            serde_json::json!({
+                "version": 2,
+                "tokens": [{"mint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "decimals": 6}],
+                "pairs": []
+            })
+        );
+        assert_eq!(
+            tokens.mints(),
+            vec!["EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"]
+        );
+    }
+}
@@ -0,0 +1,408 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use base64::prelude::*;
+use solana_sdk::{pubkey::Pubkey, transaction::VersionedTransaction};
+use thiserror::Error;
+
+use crate::{
+    enums::{Protocol, QuoteType},
+    requests::{QuoteRequest, SwapRequest},
+    responses::QuoteResponse,
+};
+
+/// Maximum fee that can be charged, 100%
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+/// A single field that failed validation, named as it appears on the wire
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub value: String,
+    pub reason: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({:?})", self.field, self.reason, self.value)
+    }
+}
+
+/// Every field of a request or response that could not be converted into its typed form
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid fields: {}", self.errors.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+pub struct ValidationError {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationError {
+    /// Names of the fields that failed validation
+    pub fn fields(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.errors.iter().map(|error| error.field)
+    }
+}
+
+/// Collects field errors so that all bad fields are reported at once instead of the first one
+#[derive(Default)]
+struct FieldValidator {
+    errors: Vec<FieldError>,
+}
+
+impl FieldValidator {
+    fn parse<T>(&mut self, field: &'static str, value: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match value.parse::<T>() {
+            Ok(parsed) => Some(parsed),
+            Err(error) => {
+                self.push(field, value, error.to_string());
+                None
+            }
+        }
+    }
+
+    fn parse_optional<T>(&mut self, field: &'static str, value: Option<&str>) -> Option<Option<T>>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match value {
+            Some(value) => self.parse(field, value).map(Some),
+            None => Some(None),
+        }
+    }
+
+    fn push(&mut self, field: &'static str, value: impl ToString, reason: impl ToString) {
+        self.errors.push(FieldError {
+            field,
+            value: value.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    fn finish<T>(self, build: impl FnOnce() -> Option<T>) -> Result<T, ValidationError> {
+        if self.errors.is_empty() {
+            if let Some(validated) = build() {
+                return Ok(validated);
+            }
+        }
+        Err(ValidationError {
+            errors: self.errors,
+        })
+    }
+}
+
+/// Typed companion of [`QuoteRequest`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidatedQuoteRequest {
+    pub request_id: String,
+    pub quote_id: String,
+    pub token_in: Pubkey,
+    pub amount: u64,
+    pub token_out: Pubkey,
+    pub quote_type: QuoteType,
+    pub protocol: Protocol,
+    pub taker: Option<Pubkey>,
+    pub suggested_prioritization_fees: Option<u64>,
+    pub fee_bps: u16,
+    pub is_wsol: Option<bool>,
+    pub receiver: Option<Pubkey>,
+}
+
+impl TryFrom<QuoteRequest> for ValidatedQuoteRequest {
+    type Error = ValidationError;
+
+    fn try_from(quote_request: QuoteRequest) -> Result<Self, Self::Error> {
+        let mut validator = FieldValidator::default();
+
+        let token_in = validator.parse("tokenIn", &quote_request.token_in);
+        let amount = validator.parse("amount", &quote_request.amount);
+        let token_out = validator.parse("tokenOut", &quote_request.token_out);
+        let taker = validator.parse_optional("taker", quote_request.taker.as_deref());
+        let receiver = validator.parse_optional("receiver", quote_request.receiver.as_deref());
+
+        if amount == Some(0) {
+            validator.push("amount", &quote_request.amount, "amount must be positive");
+        }
+        if token_in.is_some() && token_in == token_out {
+            validator.push(
+                "tokenOut",
+                &quote_request.token_out,
+                "tokenOut must differ from tokenIn",
+            );
+        }
+        if quote_request.fee_bps > MAX_FEE_BPS {
+            validator.push(
+                "feeBps",
+                quote_request.fee_bps,
+                format!("fee cannot exceed {MAX_FEE_BPS} bps"),
+            );
+        }
+
+        validator.finish(|| {
+            Some(ValidatedQuoteRequest {
+                request_id: quote_request.request_id,
+                quote_id: quote_request.quote_id,
+                token_in: token_in?,
+                amount: amount?,
+                token_out: token_out?,
+                quote_type: quote_request.quote_type,
+                protocol: quote_request.protocol,
+                taker: taker?,
+                suggested_prioritization_fees: quote_request.suggested_prioritization_fees,
+                fee_bps: quote_request.fee_bps,
+                is_wsol: quote_request.is_wsol,
+                receiver: receiver?,
+            })
+        })
+    }
+}
+
+impl From<ValidatedQuoteRequest> for QuoteRequest {
+    fn from(quote_request: ValidatedQuoteRequest) -> Self {
+        QuoteRequest {
+            request_id: quote_request.request_id,
+            quote_id: quote_request.quote_id,
+            token_in: quote_request.token_in.to_string(),
+            amount: quote_request.amount.to_string(),
+            token_out: quote_request.token_out.to_string(),
+            quote_type: quote_request.quote_type,
+            protocol: quote_request.protocol,
+            taker: quote_request.taker.map(|taker| taker.to_string()),
+            suggested_prioritization_fees: quote_request.suggested_prioritization_fees,
+            fee_bps: quote_request.fee_bps,
+            is_wsol: quote_request.is_wsol,
+            receiver: quote_request.receiver.map(|receiver| receiver.to_string()),
+            unknown_fields: BTreeMap::new(),
+        }
+    }
+}
+
+/// Typed companion of [`QuoteResponse`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidatedQuoteResponse {
+    pub request_id: String,
+    pub quote_id: String,
+    pub token_in: Pubkey,
+    pub amount_in: u64,
+    pub token_out: Pubkey,
+    pub quote_type: QuoteType,
+    pub protocol: Protocol,
+    pub amount_out: u64,
+    pub maker: Pubkey,
+    pub prioritization_fee_to_use: Option<u64>,
+    pub taker: Option<Pubkey>,
+    pub receiver: Option<Pubkey>,
+}
+
+impl TryFrom<QuoteResponse> for ValidatedQuoteResponse {
+    type Error = ValidationError;
+
+    fn try_from(quote_response: QuoteResponse) -> Result<Self, Self::Error> {
+        let mut validator = FieldValidator::default();
+
+        let token_in = validator.parse("tokenIn", &quote_response.token_in);
+        let amount_in = validator.parse("amountIn", &quote_response.amount_in);
+        let token_out = validator.parse("tokenOut", &quote_response.token_out);
+        let amount_out = validator.parse("amountOut", &quote_response.amount_out);
+        let maker = validator.parse("maker", &quote_response.maker);
+        let taker = validator.parse_optional("taker", quote_response.taker.as_deref());
+        let receiver = validator.parse_optional("receiver", quote_response.receiver.as_deref());
+
+        if let Protocol::Unknown(protocol) = &quote_response.protocol {
+            validator.push("protocol", protocol, "unknown protocol");
+        }
+
+        validator.finish(|| {
+            Some(ValidatedQuoteResponse {
+                request_id: quote_response.request_id,
+                quote_id: quote_response.quote_id,
+                token_in: token_in?,
+                amount_in: amount_in?,
+                token_out: token_out?,
+                quote_type: quote_response.quote_type,
+                protocol: quote_response.protocol,
+                amount_out: amount_out?,
+                maker: maker?,
+                prioritization_fee_to_use: quote_response.prioritization_fee_to_use,
+                taker: taker?,
+                receiver: receiver?,
+            })
+        })
+    }
+}
+
+impl From<ValidatedQuoteResponse> for QuoteResponse {
+    fn from(quote_response: ValidatedQuoteResponse) -> Self {
+        QuoteResponse {
+            request_id: quote_response.request_id,
+            quote_id: quote_response.quote_id,
+            token_in: quote_response.token_in.to_string(),
+            amount_in: quote_response.amount_in.to_string(),
+            token_out: quote_response.token_out.to_string(),
+            quote_type: quote_response.quote_type,
+            protocol: quote_response.protocol,
+            amount_out: quote_response.amount_out.to_string(),
+            maker: quote_response.maker.to_string(),
+            prioritization_fee_to_use: quote_response.prioritization_fee_to_use,
+            taker: quote_response.taker.map(|taker| taker.to_string()),
+            receiver: quote_response.receiver.map(|receiver| receiver.to_string()),
+            unknown_fields: BTreeMap::new(),
+        }
+    }
+}
+
+/// Typed companion of [`SwapRequest`], the transaction is decoded but not sanitized
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidatedSwapRequest {
+    pub request_id: String,
+    pub quote_id: String,
+    pub transaction: VersionedTransaction,
+}
+
+impl TryFrom<SwapRequest> for ValidatedSwapRequest {
+    type Error = ValidationError;
+
+    fn try_from(swap_request: SwapRequest) -> Result<Self, Self::Error> {
+        let mut validator = FieldValidator::default();
+
+        let transaction = match BASE64_STANDARD.decode(&swap_request.transaction) {
+            Ok(bytes) => match bincode::deserialize::<VersionedTransaction>(&bytes) {
+                Ok(transaction) => Some(transaction),
+                Err(error) => {
+                    validator.push("transaction", &swap_request.transaction, error);
+                    None
+                }
+            },
+            Err(error) => {
+                validator.push("transaction", &swap_request.transaction, error);
+                None
+            }
+        };
+
+        validator.finish(|| {
+            Some(ValidatedSwapRequest {
+                request_id: swap_request.request_id,
+                quote_id: swap_request.quote_id,
+                transaction: transaction?,
+            })
+        })
+    }
+}
+
+impl From<ValidatedSwapRequest> for SwapRequest {
+    fn from(swap_request: ValidatedSwapRequest) -> Self {
+        SwapRequest {
+            request_id: swap_request.request_id,
+            quote_id: swap_request.quote_id,
+            transaction: BASE64_STANDARD.encode(
+                bincode::serialize(&swap_request.transaction)
+                    .expect("Versioned transaction serialization cannot fail"),
+            ),
+            unknown_fields: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUOTE_REQUEST_JSON: &str = r#"{"requestId":"629bddf3-0038-43a6-8956-f5433d6b1191","quoteId":"59db3e19-c7b0-4753-a8aa-206701004498","tokenIn":"So11111111111111111111111111111111111111112","amount":"250000000","tokenOut":"EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v","quoteType":"exactIn","protocol":"v1","taker":"5v2Vd71VoJ1wZhz1PkhTY48mrJwS6wF4LfvDbYPnJ3bc","suggestedPrioritizationFees":10000,"feeBps":20}"#;
+
+    #[test]
+    fn test_quote_request_round_trip() {
+        let quote_request: QuoteRequest = serde_json::from_str(QUOTE_REQUEST_JSON).unwrap();
+        let validated = ValidatedQuoteRequest::try_from(quote_request).unwrap();
+
+        assert_eq!(validated.amount, 250_000_000);
+        assert_eq!(
+            validated.token_in.to_string(),
+            "So11111111111111111111111111111111111111112"
+        );
+        assert_eq!(validated.receiver, None);
+
+        // The wire format is preserved byte for byte
+        let quote_request = QuoteRequest::from(validated);
+        assert_eq!(
+            serde_json::to_string(&quote_request).unwrap(),
+            QUOTE_REQUEST_JSON
+        );
+    }
+
+    #[test]
+    fn test_quote_request_reports_every_invalid_field() {
+        let mut quote_request: QuoteRequest = serde_json::from_str(QUOTE_REQUEST_JSON).unwrap();
+        quote_request.token_in = "not-a-mint".to_string();
+        quote_request.amount = "-1".to_string();
+        quote_request.receiver = Some("0".to_string());
+        quote_request.fee_bps = 10_001;
+
+        let error = ValidatedQuoteRequest::try_from(quote_request).unwrap_err();
+        assert_eq!(
+            error.fields().collect::<Vec<_>>(),
+            vec!["tokenIn", "amount", "receiver", "feeBps"]
+        );
+    }
+
+    #[test]
+    fn test_quote_request_rejects_zero_amount_and_same_mints() {
+        let mut quote_request: QuoteRequest = serde_json::from_str(QUOTE_REQUEST_JSON).unwrap();
+        quote_request.amount = "0".to_string();
+        quote_request.token_out = quote_request.token_in.clone();
+
+        let error = ValidatedQuoteRequest::try_from(quote_request).unwrap_err();
+        assert_eq!(
+            error.fields().collect::<Vec<_>>(),
+            vec!["amount", "tokenOut"]
+        );
+    }
+
+    #[test]
+    fn test_quote_response_round_trip() {
+        let quote_response = QuoteResponse {
+            request_id: "629bddf3-0038-43a6-8956-f5433d6b1191".to_string(),
+            quote_id: "59db3e19-c7b0-4753-a8aa-206701004498".to_string(),
+            token_in: "So11111111111111111111111111111111111111112".to_string(),
+            amount_in: "250000000".to_string(),
+            token_out: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            quote_type: QuoteType::ExactIn,
+            protocol: Protocol::V1,
+            amount_out: "1000000000".to_string(),
+            maker: "8iJxVDtFxnWpdCvdrgNDSXigxHo9vLf7KCS1pNKrs5Nh".to_string(),
+            prioritization_fee_to_use: Some(10000),
+            taker: None,
+            receiver: None,
+            unknown_fields: BTreeMap::new(),
+        };
+
+        let validated = ValidatedQuoteResponse::try_from(quote_response.clone()).unwrap();
+        assert_eq!(validated.amount_out, 1_000_000_000);
+        assert_eq!(QuoteResponse::from(validated), quote_response);
+
+        let invalid = QuoteResponse {
+            maker: "maker".to_string(),
+            amount_out: "1.5".to_string(),
+            protocol: Protocol::Unknown("v3".to_string()),
+            ..quote_response
+        };
+        let error = ValidatedQuoteResponse::try_from(invalid).unwrap_err();
+        assert_eq!(
+            error.fields().collect::<Vec<_>>(),
+            vec!["amountOut", "maker", "protocol"]
+        );
+    }
+
+    #[test]
+    fn test_swap_request_invalid_transaction() {
+        let swap_request = SwapRequest {
+            request_id: "629bddf3-0038-43a6-8956-f5433d6b1191".to_string(),
+            quote_id: "59db3e19-c7b0-4753-a8aa-206701004498".to_string(),
+            transaction: "not base64!".to_string(),
+            unknown_fields: BTreeMap::new(),
+        };
+        let error = ValidatedSwapRequest::try_from(swap_request).unwrap_err();
+        assert_eq!(error.fields().collect::<Vec<_>>(), vec!["transaction"]);
+    }
+}
@@ -0,0 +1,120 @@
+//! Payloads of every protocol version must keep deserializing, and be sent back unchanged
+use std::{fs, path::PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use webhook_api::{
+    enums::{ErrorCode, Protocol, RejectionReason, SwapState, SUPPORTED_PROTOCOLS},
+    requests::{QuoteRequest, SwapRequest},
+    responses::{ErrorResponse, QuoteResponse, SwapResponse},
+    validated::{ValidatedQuoteRequest, ValidatedQuoteResponse, ValidatedSwapRequest},
+};
+
+const VERSIONS: [&str; 2] = ["v1", "v2"];
+
+fn fixture(version: &str, name: &str) -> Value {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(version)
+        .join(format!("{name}.json"));
+    serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap()
+}
+
+/// Deserialize a fixture and check it serializes back to the same JSON
+fn round_trip<T: Serialize + DeserializeOwned>(version: &str, name: &str) -> T {
+    let json = fixture(version, name);
+    let payload = serde_json::from_value::<T>(json.clone())
+        .unwrap_or_else(|error| panic!("{version}/{name}: {error}"));
+    assert_eq!(
+        serde_json::to_value(&payload).unwrap(),
+        json,
+        "{version}/{name}"
+    );
+    payload
+}
+
+#[test]
+fn test_quote_request_fixtures() {
+    for version in VERSIONS {
+        let quote_request = round_trip::<QuoteRequest>(version, "quote_request");
+        assert_eq!(quote_request.protocol.to_string().to_lowercase(), version);
+        assert_eq!(
+            quote_request.protocol.negotiate(&SUPPORTED_PROTOCOLS),
+            Some(quote_request.protocol.clone())
+        );
+        ValidatedQuoteRequest::try_from(quote_request).unwrap();
+    }
+
+    let quote_request = round_trip::<QuoteRequest>("v2", "quote_request");
+    assert_eq!(quote_request.unknown_fields["slippageBps"], 50);
+    assert!(round_trip::<QuoteRequest>("v1", "quote_request")
+        .unknown_fields
+        .is_empty());
+}
+
+#[test]
+fn test_quote_response_fixtures() {
+    for version in VERSIONS {
+        let quote_request = round_trip::<QuoteRequest>(version, "quote_request");
+        let quote_response = round_trip::<QuoteResponse>(version, "quote_response");
+        assert!(quote_request.protocol.accepts(&quote_response.protocol));
+        ValidatedQuoteResponse::try_from(quote_response).unwrap();
+    }
+
+    // a V2 response does not answer a V1 request
+    let quote_request = round_trip::<QuoteRequest>("v1", "quote_request");
+    let quote_response = round_trip::<QuoteResponse>("v2", "quote_response");
+    assert!(!quote_request.protocol.accepts(&quote_response.protocol));
+}
+
+#[test]
+fn test_swap_fixtures() {
+    for version in VERSIONS {
+        let swap_request = round_trip::<SwapRequest>(version, "swap_request");
+        ValidatedSwapRequest::try_from(swap_request).unwrap();
+        round_trip::<SwapResponse>(version, "swap_response");
+    }
+
+    let swap_response = round_trip::<SwapResponse>("v2", "swap_response");
+    assert_eq!(
+        swap_response.state,
+        SwapState::RejectedWithReason(RejectionReason::Unknown("venueHalted".to_string()))
+    );
+    assert_eq!(swap_response.unknown_fields["retryAfterMs"], 60000);
+}
+
+#[test]
+fn test_newer_protocol() {
+    let mut json = fixture("v2", "quote_request");
+    json["protocol"] = "v3".into();
+    let quote_request = serde_json::from_value::<QuoteRequest>(json).unwrap();
+    assert_eq!(quote_request.protocol, Protocol::Unknown("v3".to_string()));
+    assert_eq!(
+        quote_request.protocol.negotiate(&SUPPORTED_PROTOCOLS),
+        Some(Protocol::V2)
+    );
+}
+
+#[test]
+fn test_error_response_code() {
+    // webhooks predating the code only send a message
+    let error =
+        serde_json::from_str::<ErrorResponse>(r#"{"message": "Malformed request"}"#).unwrap();
+    assert_eq!(error.code, None);
+    assert_eq!(
+        serde_json::to_value(&error).unwrap(),
+        serde_json::json!({"message": "Malformed request"})
+    );
+
+    let error = ErrorResponse::new(ErrorCode::InvalidRequest, "Malformed request".to_string());
+    assert_eq!(
+        serde_json::to_value(&error).unwrap(),
+        serde_json::json!({"code": "invalidRequest", "message": "Malformed request"})
+    );
+    let error =
+        serde_json::from_str::<ErrorResponse>(r#"{"code": "rateLimited", "message": ""}"#).unwrap();
+    assert_eq!(
+        error.code,
+        Some(ErrorCode::Unknown("rateLimited".to_string()))
+    );
+}
@@ -0,0 +1,438 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    future::Future,
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{ensure, Context, Result};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use solana_sdk::pubkey::Pubkey;
+use webhook_api::{
+    deadline::{REQUEST_START_HEADER, REQUEST_TIMEOUT_HEADER},
+    enums::{Protocol, QuoteType, RejectionReason, SwapState},
+    requests::{QuoteRequest, SwapRequest},
+    responses::{ErrorResponse, QuoteResponse, SwapResponse},
+};
+
+use crate::report::{CheckOutcome, CheckResult, Report};
+
+pub const API_KEY_HEADER: &str = "X-API-KEY";
+/// Quotes answered later are dropped by the RFQ system
+pub const DEFAULT_MAX_LATENCY: Duration = Duration::from_millis(250);
+
+pub const WSOL: &str = "So11111111111111111111111111111111111111112";
+pub const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+/// Well-formed address of a token that does not exist, no webhook can support it
+const UNSUPPORTED_TOKEN: &str = "FakeUnsupportedMint111111111111111111111111";
+
+const REQUEST_ID: &str = "629bddf3-0038-43a6-8956-f5433d6b1191";
+const QUOTE_ID: &str = "59db3e19-c7b0-4753-a8aa-206701004498";
+const TAKER: &str = "5v2Vd71VoJ1wZhz1PkhTY48mrJwS6wF4LfvDbYPnJ3bc";
+
+// Request ids the webhooks answer with a simulated outcome, whatever the transaction
+const SIMULATE_REJECTION: &str = "00000000-0000-0000-0000-000000000001";
+const SIMULATE_MALFORMED: &str = "00000000-0000-0000-0000-000000000002";
+const SIMULATE_INSUFFICIENT_BALANCE: &str = "00000000-0000-0000-0000-000000000003";
+const SIMULATE_SIGNATURE_VERIFICATION_FAILED: &str = "00000000-0000-0000-0000-000000000004";
+
+/// Fill transaction sent along the simulated swaps
+const SAMPLE_TRANSACTION: &str = "AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAgAIABw1+jAiHYL/eHd3PMsF/IJuCQu5SqvEx+s2I0OosbQsG8kjAG1BZAFRV2dywxrzs3LT7Wy6rwamoK1c5K6qkDwTmwoAL86DDaPJrpECH4O7FIcjNK8aXLr8U+vEPOkKqMIbT6oz1rKyozQUgdRIXXEPO9Upd2Z7eIKFrVSU3OPOX3N7E3kRk8Ll8XsOf5Ir4ISzHf+0ZUtqBSXSNVE5iS+sA4iF2IlhNfbkvqPIGGddbql5WIVIAOvUkFwCrBoXw04EAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAMGRm/lIRcy/+ytunLDm+e8jOW7xfcSayxDmzpAAAAABHnZx8wQNd5yEfmetIwJ1wsr31vfni5WuKH7taLqMycG3fbh12Whk9nL4UbO63msHLSF7V9bN5E6jPWFfv8AqUpYSftyo7vpH9xbDmpX9jxaHLRbIGem7Qys02OVyKECjJclj04kifG7PRApFI4NgwtaE5na/xCEBI572Nvp+FnG+nrzvtutOj1l82qryXQxsbvkwtL24OR8pgIDRS9dYSdenhhyIvZ+yaOk0Giv3sQzHPEybygyONx7iDX7IHF2BAcACQMK0gAAAAAAAAcABQIdmAAACwYBBAEMBgkBAQoLAQACBQQDCAkMCQYjqGC3o1wKKKAAypo7AAAAAJgWfQEAAAAAaiqpZwAAAAAKAAAA";
+
+#[derive(Debug, Clone)]
+pub struct ConformanceConfig {
+    pub webhook_url: String,
+    /// Sent in the `X-API-KEY` header, `None` skips the authentication check
+    pub api_key: Option<String>,
+    pub max_latency: Duration,
+    pub token_in: String,
+    pub token_out: String,
+    /// Amount quoted, in atomic units of `token_in` for exact in and `token_out` for exact out
+    pub amount: u64,
+    pub fee_bps: u16,
+}
+
+impl ConformanceConfig {
+    /// Quote 1 USDC to WSOL, as the acceptance suite does
+    pub fn new(webhook_url: impl Into<String>) -> Self {
+        Self {
+            webhook_url: webhook_url.into(),
+            api_key: None,
+            max_latency: DEFAULT_MAX_LATENCY,
+            token_in: USDC.to_string(),
+            token_out: WSOL.to_string(),
+            amount: 1_000_000,
+            fee_bps: 2,
+        }
+    }
+}
+
+/// Run every check against the webhook, one after the other
+pub async fn run(config: &ConformanceConfig) -> Report {
+    let conformance = Conformance {
+        client: reqwest::Client::new(),
+        config,
+    };
+    let mut report = Report::default();
+
+    report.push(check("quote exact in", conformance.quote(QuoteType::ExactIn)).await);
+    report.push(check("quote exact out", conformance.quote(QuoteType::ExactOut)).await);
+    report.push(check("quote newer protocol", conformance.quote_newer_protocol()).await);
+    report.push(
+        check(
+            "quote unsupported pair",
+            conformance.quote_unsupported_pair(),
+        )
+        .await,
+    );
+    report.push(
+        check(
+            "quote malformed amount",
+            conformance.quote_malformed_amount(),
+        )
+        .await,
+    );
+    report.push(check("quote malformed mint", conformance.quote_malformed_mint()).await);
+    const MISSING_API_KEY: &str = "quote missing api key";
+    if config.api_key.is_some() {
+        report.push(check(MISSING_API_KEY, conformance.quote_missing_api_key()).await);
+    } else {
+        report.push(CheckResult {
+            name: MISSING_API_KEY,
+            outcome: CheckOutcome::Skipped("no API key configured".to_string()),
+            duration: Duration::ZERO,
+        });
+    }
+
+    report.push(
+        check(
+            "swap simulated rejection",
+            conformance.simulated_swap(SIMULATE_REJECTION, SwapState::Rejected),
+        )
+        .await,
+    );
+    report.push(
+        check(
+            "swap simulated insufficient balance",
+            conformance.simulated_swap(
+                SIMULATE_INSUFFICIENT_BALANCE,
+                SwapState::RejectedWithReason(RejectionReason::InsufficientBalance),
+            ),
+        )
+        .await,
+    );
+    report.push(
+        check(
+            "swap simulated signature verification failure",
+            conformance.simulated_swap(
+                SIMULATE_SIGNATURE_VERIFICATION_FAILED,
+                SwapState::RejectedWithReason(RejectionReason::SignatureVerificationFailed),
+            ),
+        )
+        .await,
+    );
+    report.push(
+        check(
+            "swap simulated malformed request",
+            conformance.simulated_malformed_swap(),
+        )
+        .await,
+    );
+
+    report.push(check("tokens", conformance.tokens()).await);
+    report
+}
+
+async fn check(name: &'static str, check: impl Future<Output = Result<()>>) -> CheckResult {
+    let start = Instant::now();
+    let outcome = match check.await {
+        Ok(()) => CheckOutcome::Passed,
+        Err(error) => CheckOutcome::Failed(format!("{error:#}")),
+    };
+    CheckResult {
+        name,
+        outcome,
+        duration: start.elapsed(),
+    }
+}
+
+struct Conformance<'a> {
+    client: reqwest::Client,
+    config: &'a ConformanceConfig,
+}
+
+impl Conformance<'_> {
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.config.webhook_url.trim_end_matches('/'))
+    }
+
+    fn authenticated(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.config.api_key {
+            Some(api_key) => request.header(API_KEY_HEADER, api_key),
+            None => request,
+        }
+    }
+
+    fn quote_request(&self, quote_type: QuoteType) -> QuoteRequest {
+        QuoteRequest {
+            request_id: REQUEST_ID.to_string(),
+            quote_id: QUOTE_ID.to_string(),
+            token_in: self.config.token_in.clone(),
+            amount: self.config.amount.to_string(),
+            token_out: self.config.token_out.clone(),
+            quote_type,
+            protocol: Protocol::V1,
+            taker: Some(TAKER.to_string()),
+            suggested_prioritization_fees: Some(10_000),
+            fee_bps: self.config.fee_bps,
+            is_wsol: None,
+            receiver: None,
+            unknown_fields: BTreeMap::new(),
+        }
+    }
+
+    /// Send the quote request with the deadline headers set by the RFQ system
+    async fn send_quote(
+        &self,
+        request: RequestBuilder,
+        quote_request: &QuoteRequest,
+    ) -> Result<Response> {
+        let request_start = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        Ok(request
+            .header(REQUEST_START_HEADER, request_start.to_string())
+            .header(
+                REQUEST_TIMEOUT_HEADER,
+                self.config.max_latency.as_millis().to_string(),
+            )
+            .json(quote_request)
+            .send()
+            .await?)
+    }
+
+    /// A request of a protocol newer than the webhook, with a field it does not know of
+    async fn quote_newer_protocol(&self) -> Result<()> {
+        let mut quote_request = self.quote_request(QuoteType::ExactIn);
+        quote_request.protocol = Protocol::Unknown("v99".to_string());
+        quote_request
+            .unknown_fields
+            .insert("futureField".to_string(), serde_json::Value::Bool(true));
+        let response = self
+            .send_quote(
+                self.authenticated(self.client.post(self.url("/quote"))),
+                &quote_request,
+            )
+            .await?;
+        let quote_response: QuoteResponse = json(response, StatusCode::OK).await?;
+        ensure_protocol(&quote_request, &quote_response)
+    }
+
+    async fn quote(&self, quote_type: QuoteType) -> Result<()> {
+        let quote_request = self.quote_request(quote_type);
+        let start = Instant::now();
+        let response = self
+            .send_quote(
+                self.authenticated(self.client.post(self.url("/quote"))),
+                &quote_request,
+            )
+            .await?;
+        let latency = start.elapsed();
+        let quote_response: QuoteResponse = json(response, StatusCode::OK).await?;
+        ensure!(
+            latency <= self.config.max_latency,
+            "answered in {}ms, over the {}ms limit",
+            latency.as_millis(),
+            self.config.max_latency.as_millis()
+        );
+
+        ensure_echoed(
+            "requestId",
+            &quote_request.request_id,
+            &quote_response.request_id,
+        )?;
+        ensure_echoed("quoteId", &quote_request.quote_id, &quote_response.quote_id)?;
+        ensure_echoed("tokenIn", &quote_request.token_in, &quote_response.token_in)?;
+        ensure_echoed(
+            "tokenOut",
+            &quote_request.token_out,
+            &quote_response.token_out,
+        )?;
+        ensure_echoed(
+            "quoteType",
+            &quote_request.quote_type,
+            &quote_response.quote_type,
+        )?;
+        ensure_echoed("taker", &quote_request.taker, &quote_response.taker)?;
+        ensure_protocol(&quote_request, &quote_response)?;
+        let quoted_amount = match quote_type {
+            QuoteType::ExactIn => {
+                ensure_echoed("amountIn", &quote_request.amount, &quote_response.amount_in)?;
+                ("amountOut", &quote_response.amount_out)
+            }
+            QuoteType::ExactOut => {
+                ensure_echoed(
+                    "amountOut",
+                    &quote_request.amount,
+                    &quote_response.amount_out,
+                )?;
+                ("amountIn", &quote_response.amount_in)
+            }
+        };
+        let (field, amount) = quoted_amount;
+        let amount = amount
+            .parse::<u64>()
+            .with_context(|| format!("invalid {field} {amount}"))?;
+        ensure!(amount > 0, "{field} must be positive");
+        Pubkey::from_str(&quote_response.maker)
+            .with_context(|| format!("invalid maker {}", quote_response.maker))?;
+        Ok(())
+    }
+
+    async fn quote_unsupported_pair(&self) -> Result<()> {
+        let quote_request = QuoteRequest {
+            token_out: UNSUPPORTED_TOKEN.to_string(),
+            ..self.quote_request(QuoteType::ExactIn)
+        };
+        let response = self
+            .send_quote(
+                self.authenticated(self.client.post(self.url("/quote"))),
+                &quote_request,
+            )
+            .await?;
+        ensure_status(response, StatusCode::NOT_FOUND).await
+    }
+
+    async fn quote_malformed_amount(&self) -> Result<()> {
+        let quote_request = QuoteRequest {
+            // amounts are integers in atomic units of the token
+            amount: "1.5".to_string(),
+            ..self.quote_request(QuoteType::ExactIn)
+        };
+        let response = self
+            .send_quote(
+                self.authenticated(self.client.post(self.url("/quote"))),
+                &quote_request,
+            )
+            .await?;
+        let _: ErrorResponse = json(response, StatusCode::BAD_REQUEST).await?;
+        Ok(())
+    }
+
+    async fn quote_malformed_mint(&self) -> Result<()> {
+        let quote_request = QuoteRequest {
+            // not a base58 public key
+            token_out: "not-a-mint".to_string(),
+            ..self.quote_request(QuoteType::ExactIn)
+        };
+        let response = self
+            .send_quote(
+                self.authenticated(self.client.post(self.url("/quote"))),
+                &quote_request,
+            )
+            .await?;
+        let _: ErrorResponse = json(response, StatusCode::BAD_REQUEST).await?;
+        Ok(())
+    }
+
+    async fn quote_missing_api_key(&self) -> Result<()> {
+        let response = self
+            .send_quote(
+                self.client.post(self.url("/quote")),
+                &self.quote_request(QuoteType::ExactIn),
+            )
+            .await?;
+        ensure_status(response, StatusCode::UNAUTHORIZED).await
+    }
+
+    async fn send_swap(&self, request_id: &str) -> Result<Response> {
+        let swap_request = SwapRequest {
+            request_id: request_id.to_string(),
+            quote_id: QUOTE_ID.to_string(),
+            transaction: SAMPLE_TRANSACTION.to_string(),
+            unknown_fields: BTreeMap::new(),
+        };
+        Ok(self
+            .authenticated(self.client.post(self.url("/swap")))
+            .json(&swap_request)
+            .send()
+            .await?)
+    }
+
+    async fn simulated_swap(&self, request_id: &str, state: SwapState) -> Result<()> {
+        let response = self.send_swap(request_id).await?;
+        let swap_response: SwapResponse = json(response, StatusCode::OK).await?;
+        ensure_echoed("quoteId", &QUOTE_ID.to_string(), &swap_response.quote_id)?;
+        ensure!(
+            swap_response.state == state,
+            "expected state {state:?}, got {:?}",
+            swap_response.state
+        );
+        if state == SwapState::Rejected {
+            ensure!(
+                swap_response
+                    .rejection_reason
+                    .is_some_and(|rejection_reason| !rejection_reason.is_empty()),
+                "a rejected swap must carry a rejectionReason"
+            );
+        }
+        Ok(())
+    }
+
+    async fn simulated_malformed_swap(&self) -> Result<()> {
+        let response = self.send_swap(SIMULATE_MALFORMED).await?;
+        let _: ErrorResponse = json(response, StatusCode::BAD_REQUEST).await?;
+        Ok(())
+    }
+
+    async fn tokens(&self) -> Result<()> {
+        let response = self
+            .authenticated(self.client.get(self.url("/tokens")))
+            .send()
+            .await?;
+        let tokens: Vec<String> = json(response, StatusCode::OK).await?;
+        for token in tokens {
+            Pubkey::from_str(&token).with_context(|| format!("invalid token {token}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the status and deserialize the body of the response
+async fn json<T: DeserializeOwned>(response: Response, status: StatusCode) -> Result<T> {
+    let actual = response.status();
+    let body = response.text().await?;
+    ensure!(
+        actual == status,
+        "expected status {status}, got {actual}: {body}"
+    );
+    serde_json::from_str(&body).with_context(|| format!("unexpected body: {body}"))
+}
+
+async fn ensure_status(response: Response, status: StatusCode) -> Result<()> {
+    let actual = response.status();
+    ensure!(
+        actual == status,
+        "expected status {status}, got {actual}: {}",
+        response.text().await?
+    );
+    Ok(())
+}
+
+fn ensure_protocol(quote_request: &QuoteRequest, quote_response: &QuoteResponse) -> Result<()> {
+    ensure!(
+        quote_request.protocol.accepts(&quote_response.protocol),
+        "protocol {} does not answer a {} request",
+        quote_response.protocol,
+        quote_request.protocol
+    );
+    Ok(())
+}
+
+fn ensure_echoed<T: PartialEq + Debug>(field: &str, expected: &T, actual: &T) -> Result<()> {
+    ensure!(
+        expected == actual,
+        "{field} must be echoed, expected {expected:?}, got {actual:?}"
+    );
+    Ok(())
+}
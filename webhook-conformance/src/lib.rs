@@ -0,0 +1,11 @@
+//! Conformance checks of an RFQ webhook
+//!
+//! Runs the checks of the acceptance suite with the `webhook-api` types: the quote echo fields,
+//! the 404, 400 and 401 paths, the simulated swaps, the `/tokens` shape and the quote latency.
+//! The checks only need the URL of the webhook, so they can run against a server started in the
+//! same process.
+pub mod checks;
+pub mod report;
+
+pub use checks::{run, ConformanceConfig};
+pub use report::{CheckOutcome, CheckResult, Report};
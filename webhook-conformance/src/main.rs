@@ -0,0 +1,69 @@
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use webhook_conformance::{
+    checks::{USDC, WSOL},
+    run, ConformanceConfig,
+};
+
+#[derive(Parser)]
+struct Args {
+    #[clap(env, long, default_value = "http://localhost:8080")]
+    webhook_url: String,
+
+    /// Sent in the X-API-KEY header, the authentication check is skipped without it
+    #[clap(env = "WEBHOOK_API_KEY", long)]
+    api_key: Option<String>,
+
+    /// Where the JUnit report is written
+    #[clap(env, long, default_value = "webhook-conformance.xml")]
+    junit_path: PathBuf,
+
+    /// Quotes answered later fail the latency check
+    #[clap(env, long, default_value_t = 250)]
+    max_latency_ms: u64,
+
+    #[clap(env, long, default_value = USDC)]
+    token_in: String,
+
+    #[clap(env, long, default_value = WSOL)]
+    token_out: String,
+
+    #[clap(env, long, default_value_t = 1_000_000)]
+    amount: u64,
+
+    #[clap(env, long, default_value_t = 2)]
+    fee_bps: u16,
+}
+
+#[async_std::main]
+async fn main() -> Result<ExitCode> {
+    let args = Args::parse();
+    let config = ConformanceConfig {
+        webhook_url: args.webhook_url,
+        api_key: args.api_key,
+        max_latency: Duration::from_millis(args.max_latency_ms),
+        token_in: args.token_in,
+        token_out: args.token_out,
+        amount: args.amount,
+        fee_bps: args.fee_bps,
+    };
+
+    println!(
+        "Running the conformance checks against {}",
+        config.webhook_url
+    );
+    let report = run(&config).await;
+    println!("{report}");
+
+    std::fs::write(&args.junit_path, report.to_junit_xml())
+        .with_context(|| format!("Failed to write {}", args.junit_path.display()))?;
+    println!("JUnit report written to {}", args.junit_path.display());
+
+    Ok(if report.is_success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
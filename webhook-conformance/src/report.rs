@@ -0,0 +1,162 @@
+use std::{fmt, time::Duration};
+
+/// Name of the test suite in the JUnit report
+const SUITE_NAME: &str = "webhook-conformance";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    Passed,
+    Failed(String),
+    /// The check does not apply to this configuration
+    Skipped(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub outcome: CheckOutcome,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub results: Vec<CheckResult>,
+}
+
+impl Report {
+    pub fn push(&mut self, result: CheckResult) {
+        self.results.push(result);
+    }
+
+    /// No check failed, skipped checks do not count as failures
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, CheckOutcome::Failed(_)))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|outcome| matches!(outcome, CheckOutcome::Skipped(_)))
+    }
+
+    fn count(&self, predicate: impl Fn(&CheckOutcome) -> bool) -> usize {
+        self.results
+            .iter()
+            .filter(|result| predicate(&result.outcome))
+            .count()
+    }
+
+    pub fn to_junit_xml(&self) -> String {
+        let total: Duration = self.results.iter().map(|result| result.duration).sum();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+        xml.push_str(&format!(
+            "  <testsuite name=\"{SUITE_NAME}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            self.results.len(),
+            self.failed(),
+            self.skipped(),
+            total.as_secs_f64()
+        ));
+        for result in &self.results {
+            let testcase = format!(
+                "    <testcase name=\"{}\" classname=\"{SUITE_NAME}\" time=\"{:.3}\"",
+                escape_xml(result.name),
+                result.duration.as_secs_f64()
+            );
+            match &result.outcome {
+                CheckOutcome::Passed => xml.push_str(&format!("{testcase}/>\n")),
+                CheckOutcome::Failed(message) => xml.push_str(&format!(
+                    "{testcase}>\n      <failure message=\"{}\"/>\n    </testcase>\n",
+                    escape_xml(message)
+                )),
+                CheckOutcome::Skipped(message) => xml.push_str(&format!(
+                    "{testcase}>\n      <skipped message=\"{}\"/>\n    </testcase>\n",
+                    escape_xml(message)
+                )),
+            }
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            let duration = result.duration.as_millis();
+            match &result.outcome {
+                CheckOutcome::Passed => writeln!(f, "PASS {} ({duration}ms)", result.name)?,
+                CheckOutcome::Failed(message) => {
+                    writeln!(f, "FAIL {} ({duration}ms): {message}", result.name)?
+                }
+                CheckOutcome::Skipped(message) => writeln!(f, "SKIP {}: {message}", result.name)?,
+            }
+        }
+        write!(
+            f,
+            "{} checks, {} passed, {} failed, {} skipped",
+            self.results.len(),
+            self.results.len() - self.failed() - self.skipped(),
+            self.failed(),
+            self.skipped()
+        )
+    }
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_junit_xml() {
+        let report = Report {
+            results: vec![
+                CheckResult {
+                    name: "quote exact in",
+                    outcome: CheckOutcome::Passed,
+                    duration: Duration::from_millis(12),
+                },
+                CheckResult {
+                    name: "quote unsupported pair",
+                    outcome: CheckOutcome::Failed("expected 404, got <200>".to_string()),
+                    duration: Duration::from_millis(8),
+                },
+                CheckResult {
+                    name: "quote missing api key",
+                    outcome: CheckOutcome::Skipped("no API key configured".to_string()),
+                    duration: Duration::ZERO,
+                },
+            ],
+        };
+        assert!(!report.is_success());
+        assert_eq!(
+            report.to_junit_xml(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="webhook-conformance" tests="3" failures="1" skipped="1" time="0.020">
+    <testcase name="quote exact in" classname="webhook-conformance" time="0.012"/>
+    <testcase name="quote unsupported pair" classname="webhook-conformance" time="0.008">
+      <failure message="expected 404, got &lt;200&gt;"/>
+    </testcase>
+    <testcase name="quote missing api key" classname="webhook-conformance" time="0.000">
+      <skipped message="no API key configured"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+        assert!(report
+            .to_string()
+            .ends_with("3 checks, 1 passed, 1 failed, 1 skipped"));
+    }
+}